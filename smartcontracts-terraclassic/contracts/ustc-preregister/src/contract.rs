@@ -1,44 +1,94 @@
 use cosmwasm_std::{
-    BankMsg, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128, to_json_binary,
+    Addr, BankMsg, Coin, Decimal, Empty, Decimal256, Deps, DepsMut, Env, MessageInfo, Order, Reply, Response, StdResult,
+    SubMsgResult, Uint128, Uint256, WasmMsg, to_json_binary,
 };
 use cw2::set_contract_version;
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+use cw_storage_plus::Bound;
 
 use crate::error::ContractError;
-use crate::helpers::{validate_denom, verify_owner, remove_user_from_index};
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, GetUserDepositResponse, GetAllUsersResponse, GetUserCountResponse, GetTotalDepositsResponse, GetConfigResponse, ValidateIndexResponse, GetWithdrawalInfoResponse};
-use crate::state::{Config, CONFIG, USERS, TOTAL_DEPOSITS, USER_COUNT, USER_INDEX, USER_INDEX_REVERSE, WITHDRAWAL_DESTINATION, WITHDRAWAL_UNLOCK_TIMESTAMP};
+use crate::events::ContractEvent;
+use crate::helpers::{validate_denom, validate_denom_name, validate_address, validate_destination_change, save_withdrawal_destination, reset_roles, transfer_ownership, set_paused, cancel_campaign, balance_changed_hooks, split_deposit_funds, nonpayable, verify_role, verify_any_role, split_sweep, current_sweep_window, record_sweep, sweepable_balance, queue_withdrawal, grant_all_roles, effective_destination, apply_pending_destination, tightens_sweep_limit, save_sweep_limit, apply_pending_sweep_limit, effective_sweep_limit, save_reserve_ratio, apply_pending_reserve_ratio, effective_reserve_ratio, campaign_status, settle_campaign, remove_user_from_index, append_user_history, record_activity, record_daily_deposit, record_daily_withdrawal, update_distribution, sync_repair_progress, bracket_of};
+use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, SudoMsg, GetUserDepositResponse, GetAllUsersResponse, GetUserCountResponse, GetTotalDepositsResponse, GetConfigResponse, ValidateIndexResponse, GetWithdrawalInfoResponse, GetTopDepositorsResponse, GetUserRankResponse, GetUserHistoryResponse, OrderBy, GetActivityResponse, GetLatestSequenceResponse, GetDailyStatsResponse, GetDistributionResponse, DistributionBracket, GetUserDepositsResponse, UserDepositEntry, RepairIndexResponse, RepairIndexCursor, ValidateIndexCursor, ValidatePhase, MigrateMsg, GetRolesResponse, RoleHolders, GetDestinationScheduleResponse, GetSweepAllowanceResponse, GetLiquidityResponse, QueuePosition, GetQueuePositionResponse, GetWithdrawQueueResponse, GetRefundProgressResponse, GetCampaignStatusResponse, RecoverableToken, ListHooksResponse, UncheckedDestinationShare};
+use crate::state::{Config, CONFIG, USERS, TOTAL_DEPOSITS, USER_COUNT, USER_INDEX, USER_INDEX_REVERSE, WITHDRAWAL_DESTINATION, WITHDRAWAL_UNLOCK_TIMESTAMP, HistoryKind, USER_HISTORY, ActivityKind, ACTIVITY, SEQUENCE, DAILY_STATS, DISTRIBUTION, DISTRIBUTION_THRESHOLDS, DISTRIBUTION_REBUILD, RepairCursor, RepairPhase, RepairProgress, REPAIR_PROGRESS, Role, ROLES, PAUSED, PendingDestination, PENDING_DESTINATION, WITHDRAWAL_SHARES, SweepLimit, SWEEP_LIMIT, PendingSweepLimit, PENDING_SWEEP_LIMIT, RESERVE_RATIO, PendingReserveRatio, PENDING_RESERVE_RATIO, WITHDRAW_QUEUE, WITHDRAW_QUEUE_STATE, CAMPAIGN_CANCELLATION, CampaignStatus, DEFAULT_DECIMALS, HOOKS, HOOK_REPLY_IDS, HOOK_GAS_LIMIT, MAX_HOOKS};
 
 const CONTRACT_NAME: &str = "crates.io:ustc-preregister";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
-/// USTC denomination on Terra Classic (hardcoded as per proposal requirement)
-const USTC_DENOM: &str = "uusd";
+/// Deposit denomination when none is configured (USTC on Terra Classic)
+const DEFAULT_DENOM: &str = "uusd";
+/// Maximum display decimals of the deposit denom
+const MAX_DECIMALS: u8 = 18;
 /// Default pagination limit for GetAllUsers query (hardcoded for consistency)
 const DEFAULT_QUERY_LIMIT: u32 = 30;
 /// Maximum pagination limit for GetAllUsers query (hardcoded to prevent excessive gas usage)
 const MAX_QUERY_LIMIT: u32 = 100;
+/// Default distribution bracket thresholds in whole tokens: 1k, 10k, 100k and 1M
+const DEFAULT_DISTRIBUTION_THRESHOLDS: [u128; 4] = [1_000, 10_000, 100_000, 1_000_000];
+/// Maximum number of distribution thresholds (keeps GetDistribution cheap)
+const MAX_DISTRIBUTION_THRESHOLDS: u32 = 20;
+/// Delay between staging a withdrawal destination change and it taking effect (7 days)
+const DESTINATION_CHANGE_DELAY: u64 = 7 * 24 * 60 * 60;
+/// Delay between staging a raise or removal of the sweep limit, or a lower reserve ratio,
+/// and it taking effect (7 days)
+const LOOSENING_DELAY: u64 = 7 * 24 * 60 * 60;
+/// Default number of entries processed per RepairIndex batch
+const DEFAULT_REPAIR_LIMIT: u32 = 50;
+/// Maximum number of entries processed per RepairIndex batch
+const MAX_REPAIR_LIMIT: u32 = 200;
+/// Default number of entries checked per ValidateIndex page
+const DEFAULT_VALIDATE_LIMIT: u32 = 100;
+/// Maximum number of entries checked per ValidateIndex page
+const MAX_VALIDATE_LIMIT: u32 = 500;
+/// Deepest rank GetUserRank computes, counting stops after this many depositors ahead
+const MAX_RANK: u32 = 1000;
+/// Maximum number of queued withdrawals paid by one ReturnFunds call
+const MAX_QUEUE_SETTLEMENTS: u32 = 100;
+/// Default number of users refunded per ProcessRefunds batch
+const DEFAULT_REFUND_LIMIT: u32 = 50;
+/// Maximum number of users refunded per ProcessRefunds batch
+const MAX_REFUND_LIMIT: u32 = 100;
+/// Hook gas one ProcessRefunds batch may commit to, so a batch fits in a block with hooks registered
+const MAX_REFUND_HOOK_GAS: u64 = 25_000_000;
 
 /// Instantiate the contract with owner address
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
-/// * `_env` - Contract environment information
+/// * `env` - Contract environment information
 /// * `_info` - Message information (sender, funds)
-/// * `msg` - Instantiation message containing owner address
+/// * `msg` - Instantiation message containing owner address, optional campaign target and denom
 /// 
 /// # Returns
 /// * `Response` with instantiation attributes
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     
-    // Owner address is already validated as Addr type during deserialization
+    // A soft cap only makes sense with a deadline to check it at
+    match (msg.soft_cap, msg.deadline) {
+        (None, None) => {},
+        (Some(soft_cap), Some(deadline)) if !soft_cap.is_zero() && deadline > env.block.time.seconds() => {},
+        _ => return Err(ContractError::InvalidCampaignTarget {}),
+    }
+    
+    let ustc_denom = msg.denom.unwrap_or_else(|| DEFAULT_DENOM.to_string());
+    validate_denom_name(&ustc_denom)?;
+    let decimals = msg.decimals.unwrap_or(DEFAULT_DECIMALS);
+    if decimals > MAX_DECIMALS {
+        return Err(ContractError::InvalidDecimals { max: MAX_DECIMALS });
+    }
+    
+    let owner = validate_address(deps.api, &msg.owner, |address| ContractError::InvalidOwnerAddress { address })?;
     let config = Config {
-        owner: msg.owner,
-        ustc_denom: USTC_DENOM.to_string(),
+        owner,
+        ustc_denom,
+        decimals,
+        soft_cap: msg.soft_cap,
+        deadline: msg.deadline,
     };
     
     CONFIG.save(deps.storage, &config)?;
@@ -48,46 +98,209 @@ pub fn instantiate(
     // Initialize withdrawal destination to None and unlock timestamp to 0
     WITHDRAWAL_DESTINATION.save(deps.storage, &None)?;
     WITHDRAWAL_UNLOCK_TIMESTAMP.save(deps.storage, &0u64)?;
+    WITHDRAWAL_SHARES.save(deps.storage, &vec![])?;
+    PENDING_DESTINATION.save(deps.storage, &None)?;
+    
+    // Activity ledger starts empty, first entry gets sequence 1
+    SEQUENCE.save(deps.storage, &0u64)?;
+    
+    DISTRIBUTION_THRESHOLDS.save(deps.storage, &default_distribution_thresholds(config.decimals))?;
+    
+    // Owner starts with every role, roles can then be handed out with GrantRole
+    grant_all_roles(deps.storage, &config.owner)?;
+    PAUSED.save(deps.storage, &false)?;
+    SWEEP_LIMIT.save(deps.storage, &None)?;
+    PENDING_SWEEP_LIMIT.save(deps.storage, &None)?;
+    RESERVE_RATIO.save(deps.storage, &Decimal::zero())?;
+    PENDING_RESERVE_RATIO.save(deps.storage, &None)?;
+    WITHDRAW_QUEUE_STATE.save(deps.storage, &Default::default())?;
+    
+    let event = ContractEvent::Instantiate {
+        owner: config.owner.clone(),
+        ustc_denom: config.ustc_denom.clone(),
+        soft_cap: config.soft_cap,
+        deadline: config.deadline,
+    };
     
     Ok(Response::new()
+        .add_event(event.to_event())
         .add_attribute("action", "instantiate")
         .add_attribute("owner", config.owner.to_string())
         .add_attribute("ustc_denom", config.ustc_denom))
 }
 
+/// Default distribution thresholds in base units of a denom with `decimals` display decimals
+fn default_distribution_thresholds(decimals: u8) -> Vec<Uint128> {
+    let unit = Uint128::new(10).pow(decimals as u32);
+    DEFAULT_DISTRIBUTION_THRESHOLDS
+        .iter()
+        .map(|tokens| Uint128::new(*tokens) * unit)
+        .collect()
+}
+
+/// Migrate the contract to this version
+/// 
+/// Initializes storage added since the contract was deployed. If no admin exists yet
+/// (contracts deployed before role-based access control), the owner is granted every
+/// role, so the owner keeps all permissions it had before the migration.
+/// 
+/// Balances stored before the deposit index or distribution tracking existed are
+/// missing from them. Rewriting every balance does not fit in one transaction, so
+/// migrate starts an index repair instead; deposits stay closed until an admin
+/// completes it with RepairIndex, while withdrawals and refunds keep working.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `_env` - Contract environment information
+/// * `_msg` - Migration message (empty)
+/// 
+/// # Returns
+/// * `Response` with migration attributes
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    
+    let config = CONFIG.load(deps.storage)?;
+    
+    let has_admin = ROLES
+        .prefix(Role::Admin.as_str())
+        .keys(deps.storage, None, None, Order::Ascending)
+        .next()
+        .is_some();
+    if !has_admin {
+        grant_all_roles(deps.storage, &config.owner)?;
+    }
+    
+    if !PAUSED.exists(deps.storage) {
+        PAUSED.save(deps.storage, &false)?;
+    }
+    if !SWEEP_LIMIT.exists(deps.storage) {
+        SWEEP_LIMIT.save(deps.storage, &None)?;
+    }
+    if !PENDING_SWEEP_LIMIT.exists(deps.storage) {
+        PENDING_SWEEP_LIMIT.save(deps.storage, &None)?;
+    }
+    if !WITHDRAW_QUEUE_STATE.exists(deps.storage) {
+        WITHDRAW_QUEUE_STATE.save(deps.storage, &Default::default())?;
+    }
+    if !RESERVE_RATIO.exists(deps.storage) {
+        RESERVE_RATIO.save(deps.storage, &Decimal::zero())?;
+    }
+    if !PENDING_RESERVE_RATIO.exists(deps.storage) {
+        PENDING_RESERVE_RATIO.save(deps.storage, &None)?;
+    }
+    if !PENDING_DESTINATION.exists(deps.storage) {
+        PENDING_DESTINATION.save(deps.storage, &None)?;
+    }
+    if !WITHDRAWAL_SHARES.exists(deps.storage) {
+        WITHDRAWAL_SHARES.save(deps.storage, &vec![])?;
+    }
+    if !SEQUENCE.exists(deps.storage) {
+        SEQUENCE.save(deps.storage, &0u64)?;
+    }
+    if !DISTRIBUTION_THRESHOLDS.exists(deps.storage) {
+        let decimals = CONFIG.load(deps.storage)?.decimals;
+        DISTRIBUTION_THRESHOLDS.save(deps.storage, &default_distribution_thresholds(decimals))?;
+    }
+    
+    let user_count = USER_COUNT.load(deps.storage)?;
+    let deposit_index_empty = USERS
+        .idx
+        .deposit
+        .keys_raw(deps.storage, None, None, Order::Ascending)
+        .next()
+        .is_none();
+    let bucketed = DISTRIBUTION
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|entry| entry.map(|(_, stats)| stats.count as u64))
+        .sum::<StdResult<u64>>()?;
+    let repair_required = (user_count > 0 && deposit_index_empty) || bucketed != user_count as u64;
+    if repair_required && !REPAIR_PROGRESS.exists(deps.storage) {
+        REPAIR_PROGRESS.save(deps.storage, &RepairProgress::initial())?;
+    }
+    
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("version", CONTRACT_VERSION)
+        .add_attribute("repair_required", repair_required.to_string()))
+}
+
 pub fn execute(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    // Only deposits and fund returns take funds, anything else sent along would end up
+    // as untracked balance
+    if !matches!(msg, ExecuteMsg::Deposit {} | ExecuteMsg::ReturnFunds {}) {
+        nonpayable(&info)?;
+    }
+    
     match msg {
-        ExecuteMsg::Deposit {} => execute_deposit(deps, info),
-        ExecuteMsg::Withdraw { amount } => execute_withdraw(deps, info, amount),
-        ExecuteMsg::OwnerWithdraw {} => execute_owner_withdraw(deps, env, info),
-        ExecuteMsg::UpdateConfig { owner } => execute_update_config(deps, info, owner),
-        ExecuteMsg::SetWithdrawalDestination { destination, unlock_timestamp } => {
-            execute_set_withdrawal_destination(deps, env, info, destination, unlock_timestamp)
+        ExecuteMsg::Deposit {} => execute_deposit(deps, env, info),
+        ExecuteMsg::Withdraw { amount } => execute_withdraw(deps, env, info, amount),
+        ExecuteMsg::OwnerWithdraw { amount } => execute_owner_withdraw(deps, env, info, amount),
+        ExecuteMsg::UpdateConfig { owner } => execute_update_config(deps, env, info, owner),
+        ExecuteMsg::SetWithdrawalDestination { destination, shares, unlock_timestamp } => {
+            execute_set_withdrawal_destination(deps, env, info, destination, shares, unlock_timestamp)
+        },
+        ExecuteMsg::SetDistributionBrackets { thresholds } => {
+            execute_set_distribution_brackets(deps, env, info, thresholds)
+        },
+        ExecuteMsg::RepairIndex { start, limit } => execute_repair_index(deps, env, info, start, limit),
+        ExecuteMsg::GrantRole { role, address } => execute_grant_role(deps, env, info, role, address),
+        ExecuteMsg::RevokeRole { role, address } => execute_revoke_role(deps, env, info, role, address),
+        ExecuteMsg::SetPaused { paused } => execute_set_paused(deps, env, info, paused),
+        ExecuteMsg::CancelPendingDestination {} => execute_cancel_pending_destination(deps, env, info),
+        ExecuteMsg::SetSweepLimit { limit } => execute_set_sweep_limit(deps, env, info, limit),
+        ExecuteMsg::SetReserveRatio { ratio } => execute_set_reserve_ratio(deps, env, info, ratio),
+        ExecuteMsg::ReturnFunds {} => execute_return_funds(deps, env, info),
+        ExecuteMsg::CancelCampaign {} => execute_cancel_campaign(deps, env, info),
+        ExecuteMsg::ProcessRefunds { limit } => execute_process_refunds(deps, env, limit),
+        ExecuteMsg::RecoverTokens { denom_or_cw20, recipient } => {
+            execute_recover_tokens(deps, env, info, denom_or_cw20, recipient)
         },
+        ExecuteMsg::SetDenom { denom, decimals } => execute_set_denom(deps, env, info, denom, decimals),
+        ExecuteMsg::AddHook { addr } => execute_add_hook(deps, env, info, addr),
+        ExecuteMsg::RemoveHook { addr } => execute_remove_hook(deps, env, info, addr),
     }
 }
 
 /// Execute a deposit of USTC tokens
 /// 
+/// Coins of other denominations sent with the deposit are sent back to the sender.
+/// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
 /// * `info` - Message information containing sender and funds
 /// 
 /// # Returns
-/// * `Response` with deposit event attributes
+/// * `Response` with deposit event attributes and a BankMsg refunding other denominations
 pub fn execute_deposit(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     
-    // Validate funds contain only USTC with correct denom
-    let amount = validate_denom(&info.funds, &config.ustc_denom)?;
+    if PAUSED.load(deps.storage)? {
+        return Err(ContractError::Paused {});
+    }
+    
+    match campaign_status(deps.storage, env.block.time.seconds())? {
+        CampaignStatus::Active => {},
+        CampaignStatus::Cancelled => return Err(ContractError::CampaignCancelled {}),
+        CampaignStatus::Succeeded | CampaignStatus::Failed => return Err(ContractError::CampaignEnded {}),
+    }
+    
+    // Index is being rebuilt, user set must not change until it completes
+    if REPAIR_PROGRESS.exists(deps.storage) {
+        return Err(ContractError::RepairInProgress {});
+    }
+    
+    // Take the USTC, other denominations sent along are refunded below
+    let (amount, refund) = split_deposit_funds(&info.funds, &config.ustc_denom)?;
     
     let user = &info.sender;
     
@@ -100,8 +313,8 @@ pub fn execute_deposit(
     USERS.save(deps.storage, user, &new_deposit)?;
     
     // Update total deposits
-    let total_deposits = TOTAL_DEPOSITS.load(deps.storage)?;
-    TOTAL_DEPOSITS.save(deps.storage, &(total_deposits + amount))?;
+    let total_deposits = TOTAL_DEPOSITS.load(deps.storage)? + amount;
+    TOTAL_DEPOSITS.save(deps.storage, &total_deposits)?;
     
     // If new user, add to index-based storage
     if is_new_user {
@@ -113,14 +326,35 @@ pub fn execute_deposit(
         USER_COUNT.save(deps.storage, &(user_count + 1))?;
     }
     
+    append_user_history(deps.storage, &env, user, HistoryKind::Deposit, amount, new_deposit)?;
+    record_activity(deps.storage, &env, ActivityKind::Deposit { user: user.clone(), amount })?;
+    record_daily_deposit(deps.storage, &env, amount, is_new_user, total_deposits)?;
+    update_distribution(deps.storage, current_deposit, new_deposit)?;
+    
+    let event = ContractEvent::Deposit {
+        user: user.clone(),
+        amount,
+        new_balance: new_deposit,
+        total_deposits,
+        new_user: is_new_user,
+    };
+    
+    let hooks = balance_changed_hooks(deps.storage, user, current_deposit, new_deposit)?;
     let mut response = Response::new()
+        .add_submessages(hooks)
+        .add_event(event.to_event())
         .add_attribute("action", "deposit")
         .add_attribute("user", user.to_string())
-        .add_attribute("amount", amount.to_string())
-        .add_attribute("event", "deposit");
+        .add_attribute("amount", amount.to_string());
     
-    if is_new_user {
-        response = response.add_attribute("event", "user_added");
+    if !refund.is_empty() {
+        let refunded = refund.iter().map(|coin| coin.to_string()).collect::<Vec<_>>().join(",");
+        response = response
+            .add_message(BankMsg::Send {
+                to_address: user.to_string(),
+                amount: refund,
+            })
+            .add_attribute("refunded", refunded);
     }
     
     Ok(response)
@@ -130,6 +364,7 @@ pub fn execute_deposit(
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
 /// * `info` - Message information containing sender
 /// * `amount` - Amount to withdraw
 /// 
@@ -137,6 +372,7 @@ pub fn execute_deposit(
 /// * `Response` with withdrawal event and BankMsg to transfer tokens
 pub fn execute_withdraw(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     amount: Uint128,
 ) -> Result<Response, ContractError> {
@@ -158,12 +394,18 @@ pub fn execute_withdraw(
         return Err(ContractError::InsufficientBalance {});
     }
     
+    // The outcome is fixed before this withdrawal lowers total deposits
+    let mut response = Response::new();
+    if let Some(event) = settle_campaign(deps.storage, &env)? {
+        response = response.add_event(event.to_event());
+    }
+    
     // Subtract amount from deposit
     let new_deposit = current_deposit - amount;
     
     // Update user's deposit record and total deposits
     if new_deposit.is_zero() {
-        USERS.remove(deps.storage, user);
+        USERS.remove(deps.storage, user)?;
         
         // Remove user from index-based storage when balance becomes zero
         // This uses the swap-and-remove pattern to maintain compact indices
@@ -173,27 +415,309 @@ pub fn execute_withdraw(
     }
     
     // Update total deposits
-    let total_deposits = TOTAL_DEPOSITS.load(deps.storage)?;
-    TOTAL_DEPOSITS.save(deps.storage, &(total_deposits - amount))?;
+    let total_deposits = TOTAL_DEPOSITS.load(deps.storage)? - amount;
+    TOTAL_DEPOSITS.save(deps.storage, &total_deposits)?;
     
-    // Transfer tokens via BankMsg
-    let bank_msg = BankMsg::Send {
-        to_address: user.to_string(),
-        amount: vec![cosmwasm_std::Coin {
-            denom: config.ustc_denom.clone(),
-            amount,
-        }],
+    append_user_history(deps.storage, &env, user, HistoryKind::Withdraw, amount, new_deposit)?;
+    record_activity(deps.storage, &env, ActivityKind::Withdraw { user: user.clone(), amount })?;
+    record_daily_withdrawal(deps.storage, &env, amount, new_deposit.is_zero(), total_deposits)?;
+    update_distribution(deps.storage, current_deposit, new_deposit)?;
+    sync_repair_progress(deps.storage, user, current_deposit, new_deposit)?;
+    
+    let event = ContractEvent::Withdraw {
+        user: user.clone(),
+        amount,
+        new_balance: new_deposit,
+        total_deposits,
+        user_removed: new_deposit.is_zero(),
     };
     
-    Ok(Response::new()
-        .add_message(bank_msg)
+    let hooks = balance_changed_hooks(deps.storage, user, current_deposit, new_deposit)?;
+    let response = response
+        .add_submessages(hooks)
+        .add_event(event.to_event())
         .add_attribute("action", "withdraw")
         .add_attribute("user", user.to_string())
-        .add_attribute("amount", amount.to_string())
-        .add_attribute("event", "withdraw"))
+        .add_attribute("amount", amount.to_string());
+    
+    // Funds may have been swept: queue the request rather than failing at the bank layer,
+    // and keep queueing while earlier requests wait so they are paid first
+    let available = deps.querier.query_balance(&env.contract.address, &config.ustc_denom)?.amount;
+    let queue = WITHDRAW_QUEUE_STATE.load(deps.storage)?;
+    if queue.head == queue.next && available >= amount {
+        // Transfer tokens via BankMsg
+        let bank_msg = BankMsg::Send {
+            to_address: user.to_string(),
+            amount: vec![cosmwasm_std::Coin {
+                denom: config.ustc_denom.clone(),
+                amount,
+            }],
+        };
+        return Ok(response.add_message(bank_msg));
+    }
+    
+    let (entry, position) = queue_withdrawal(deps.storage, &env, user, amount)?;
+    
+    let event = ContractEvent::WithdrawalQueued {
+        id: entry.id,
+        user: user.clone(),
+        amount,
+    };
+    
+    Ok(response
+        .add_event(event.to_event())
+        .add_attribute("queue_id", entry.id.to_string())
+        .add_attribute("queue_position", position.to_string()))
+}
+
+/// Admin function to cancel the campaign
+/// 
+/// Snapshots the depositors and total deposits for refund progress. Deposits and sweeps
+/// are rejected from then on; refunds are paid by `execute_process_refunds`.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// 
+/// # Returns
+/// * `Response` with cancellation event
+pub fn execute_cancel_campaign(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    // Check caller is admin
+    verify_role(deps.storage, &info, Role::Admin)?;
+    
+    let cancellation = cancel_campaign(deps.storage, &env)?;
+    let user_count = cancellation.users_at_cancel;
+    let total_deposits = cancellation.deposits_at_cancel;
+    
+    let event = ContractEvent::CampaignCancelled { user_count, total_deposits };
+    
+    Ok(Response::new()
+        .add_event(event.to_event())
+        .add_attribute("action", "cancel_campaign")
+        .add_attribute("user_count", user_count.to_string())
+        .add_attribute("total_deposits", total_deposits.to_string()))
+}
+
+/// Refund a batch of depositors of a cancelled or failed campaign
+/// 
+/// Callable by anyone. Walks USER_INDEX from the last position, so every removal is a
+/// plain pop and no other user moves. While an index repair is in progress the index is
+/// not trusted and users are taken from USERS by address instead. Refunds are paid from the contract balance left
+/// after queued withdrawals. A refund the balance cannot cover yet is moved into the
+/// withdraw queue instead, so one large depositor does not block everyone behind them.
+/// Each refund calls every hook with HOOK_GAS_LIMIT, so with hooks registered the batch
+/// is further capped to keep their gas within MAX_REFUND_HOOK_GAS.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `limit` - Optional number of users to refund (default: 50, max: 100)
+/// 
+/// # Returns
+/// * `Response` with one refund event per refunded user, and a BankMsg or queued withdrawal event
+pub fn execute_process_refunds(
+    deps: DepsMut,
+    env: Env,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let status = campaign_status(deps.storage, env.block.time.seconds())?;
+    if !matches!(status, CampaignStatus::Cancelled | CampaignStatus::Failed) {
+        return Err(ContractError::CampaignNotCancelled {});
+    }
+    
+    let config = CONFIG.load(deps.storage)?;
+    let mut limit = limit.unwrap_or(DEFAULT_REFUND_LIMIT).min(MAX_REFUND_LIMIT);
+    // Every refund calls every hook, the more hooks the fewer users fit in a batch
+    let hook_count = HOOKS.keys(deps.storage, None, None, Order::Ascending).count() as u64;
+    if hook_count > 0 {
+        let hook_limit = (MAX_REFUND_HOOK_GAS / (hook_count * HOOK_GAS_LIMIT)).max(1);
+        limit = limit.min(hook_limit as u32);
+    }
+    // The index tail cannot be trusted while it is being rebuilt, users are then taken by address
+    let repairing = REPAIR_PROGRESS.exists(deps.storage);
+    
+    // Queued withdrawals were requested first and keep their claim on the balance
+    let balance = deps.querier.query_balance(&env.contract.address, &config.ustc_denom)?.amount;
+    let mut available = balance.saturating_sub(WITHDRAW_QUEUE_STATE.load(deps.storage)?.total);
+    
+    let mut response = Response::new();
+    if let Some(event) = settle_campaign(deps.storage, &env)? {
+        response = response.add_event(event.to_event());
+    }
+    
+    let mut refunded: u32 = 0;
+    let mut queued: u32 = 0;
+    let mut refunded_amount = Uint128::zero();
+    let mut total_deposits = TOTAL_DEPOSITS.load(deps.storage)?;
+    while refunded < limit {
+        let user = if repairing {
+            match USERS.keys(deps.storage, None, None, Order::Descending).next() {
+                Some(user) => user?,
+                None => break,
+            }
+        } else {
+            let user_count = USER_COUNT.load(deps.storage)?;
+            if user_count == 0 {
+                break;
+            }
+            
+            USER_INDEX
+                .may_load(deps.storage, user_count - 1)?
+                .ok_or(ContractError::IndexInconsistency {})?
+        };
+        let amount = USERS.may_load(deps.storage, &user)?.unwrap_or_default();
+        
+        USERS.remove(deps.storage, &user)?;
+        remove_user_from_index(deps.storage, &user)?;
+        total_deposits -= amount;
+        refunded += 1;
+        refunded_amount += amount;
+        
+        append_user_history(deps.storage, &env, &user, HistoryKind::Withdraw, amount, Uint128::zero())?;
+        record_activity(deps.storage, &env, ActivityKind::Refund { user: user.clone(), amount })?;
+        record_daily_withdrawal(deps.storage, &env, amount, true, total_deposits)?;
+        update_distribution(deps.storage, amount, Uint128::zero())?;
+        sync_repair_progress(deps.storage, &user, amount, Uint128::zero())?;
+        
+        let event = ContractEvent::Refund {
+            user: user.clone(),
+            amount,
+        };
+        let hooks = balance_changed_hooks(deps.storage, &user, amount, Uint128::zero())?;
+        response = response.add_submessages(hooks).add_event(event.to_event());
+        
+        // Funds may have been swept: queue what cannot be paid yet, ReturnFunds settles it
+        if amount > available {
+            let (entry, _) = queue_withdrawal(deps.storage, &env, &user, amount)?;
+            let event = ContractEvent::WithdrawalQueued {
+                id: entry.id,
+                user: user.clone(),
+                amount,
+            };
+            response = response.add_event(event.to_event());
+            queued += 1;
+            continue;
+        }
+        available -= amount;
+        
+        // Users with a zero balance left in the index have nothing to receive
+        if !amount.is_zero() {
+            response = response.add_message(BankMsg::Send {
+                to_address: user.to_string(),
+                amount: vec![Coin {
+                    denom: config.ustc_denom.clone(),
+                    amount,
+                }],
+            });
+        }
+    }
+    TOTAL_DEPOSITS.save(deps.storage, &total_deposits)?;
+    
+    // Progress is tracked against the cancellation snapshot, a failed campaign has none
+    if let Some(mut cancellation) = CAMPAIGN_CANCELLATION.may_load(deps.storage)? {
+        cancellation.refunded_users += refunded;
+        cancellation.refunded_amount += refunded_amount;
+        CAMPAIGN_CANCELLATION.save(deps.storage, &cancellation)?;
+    }
+    
+    Ok(response
+        .add_attribute("action", "process_refunds")
+        .add_attribute("refunded", refunded.to_string())
+        .add_attribute("amount", refunded_amount.to_string())
+        .add_attribute("remaining_users", USER_COUNT.load(deps.storage)?.to_string())
+        .add_attribute("queued", queued.to_string()))
+}
+
+/// Admin or treasurer function to return USTC and settle queued withdrawals
+/// 
+/// Pays queued withdrawals in request order from the contract balance, including the
+/// returned funds, and stops at the first entry the balance cannot cover so that no
+/// request is overtaken. At most `MAX_QUEUE_SETTLEMENTS` entries are paid per call;
+/// calling again without funds continues with the rest.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender and the returned funds
+/// 
+/// # Returns
+/// * `Response` with one BankMsg and event per settled withdrawal
+pub fn execute_return_funds(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    // Check caller is admin or treasurer
+    verify_any_role(deps.storage, &info, &[Role::Admin, Role::Treasurer])?;
+    
+    let config = CONFIG.load(deps.storage)?;
+    let returned = if info.funds.is_empty() {
+        Uint128::zero()
+    } else {
+        validate_denom(&info.funds, &config.ustc_denom)?
+    };
+    
+    record_activity(deps.storage, &env, ActivityKind::FundsReturned { sender: info.sender.clone(), amount: returned })?;
+    
+    let event = ContractEvent::FundsReturned {
+        sender: info.sender.clone(),
+        amount: returned,
+    };
+    let mut response = Response::new().add_event(event.to_event());
+    
+    // The queried balance already includes the returned funds
+    let mut available = deps.querier.query_balance(&env.contract.address, &config.ustc_denom)?.amount;
+    let mut queue = WITHDRAW_QUEUE_STATE.load(deps.storage)?;
+    let mut settled: u32 = 0;
+    while queue.head < queue.next && settled < MAX_QUEUE_SETTLEMENTS {
+        let entry = WITHDRAW_QUEUE.load(deps.storage, queue.head)?;
+        if entry.amount > available {
+            break;
+        }
+        
+        WITHDRAW_QUEUE.remove(deps.storage, entry.id)?;
+        queue.head += 1;
+        queue.total -= entry.amount;
+        available -= entry.amount;
+        settled += 1;
+        
+        record_activity(
+            deps.storage,
+            &env,
+            ActivityKind::QueuedWithdrawalPaid { id: entry.id, user: entry.user.clone(), amount: entry.amount },
+        )?;
+        
+        let event = ContractEvent::QueuedWithdrawalPaid {
+            id: entry.id,
+            user: entry.user.clone(),
+            amount: entry.amount,
+        };
+        // The deposit was already lowered, and hooks notified, when the withdrawal was queued
+        response = response
+            .add_message(BankMsg::Send {
+                to_address: entry.user.to_string(),
+                amount: vec![Coin {
+                    denom: config.ustc_denom.clone(),
+                    amount: entry.amount,
+                }],
+            })
+            .add_event(event.to_event());
+    }
+    WITHDRAW_QUEUE_STATE.save(deps.storage, &queue)?;
+    
+    Ok(response
+        .add_attribute("action", "return_funds")
+        .add_attribute("amount", returned.to_string())
+        .add_attribute("settled", settled.to_string())
+        .add_attribute("remaining_queued", queue.total.to_string()))
 }
 
-/// Owner-only function to withdraw all accumulated USTC tokens
+/// Treasurer-only function to withdraw all accumulated USTC tokens
 /// 
 /// This function transfers all USTC tokens from the preregistration contract to the
 /// withdrawal destination address (set via SetWithdrawalDestination). The withdrawal
@@ -208,6 +732,14 @@ pub fn execute_withdraw(
 /// USTC, the owner can call this function again to withdraw the new balance (subject to the
 /// timelock requirements).
 /// 
+/// The reserve set via SetReserveRatio always stays in the contract so users can withdraw.
+/// If a sweep limit is set, the amount is also capped by what is left in the current window;
+/// without an explicit amount the sweep takes as much as the window allows.
+/// 
+/// If the destination has shares, the sweep is split into one BankMsg per share, each leg
+/// rounded down and the remainder sent to the destination itself. Every leg is recorded as
+/// its own activity entry, event and `leg` attribute (`address:amount`).
+/// 
 /// Requires:
 /// - Withdrawal destination must be set via SetWithdrawalDestination
 /// - Withdrawal unlock timestamp must be set and current time >= unlock timestamp
@@ -216,6 +748,7 @@ pub fn execute_withdraw(
 /// * `deps` - Dependencies for storage and API access
 /// * `env` - Contract environment information
 /// * `info` - Message information containing sender
+/// * `amount` - Amount to sweep, or None for the whole contract balance
 /// 
 /// # Returns
 /// * `Response` with owner withdrawal event and BankMsg to transfer tokens
@@ -223,11 +756,36 @@ pub fn execute_owner_withdraw(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
+    amount: Option<Uint128>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     
-    // Check caller is owner
-    verify_owner(&info, &config)?;
+    // Check caller is treasurer
+    verify_role(deps.storage, &info, Role::Treasurer)?;
+    
+    // Funds of a cancelled or failed campaign belong to the depositors, and with a soft cap
+    // nothing leaves before the campaign has succeeded, or a later failure could not refund
+    match campaign_status(deps.storage, env.block.time.seconds())? {
+        CampaignStatus::Cancelled => return Err(ContractError::CampaignCancelled {}),
+        CampaignStatus::Failed => return Err(ContractError::CampaignFailed {}),
+        CampaignStatus::Active if config.soft_cap.is_some() => return Err(ContractError::CampaignInProgress {}),
+        CampaignStatus::Active | CampaignStatus::Succeeded => {},
+    }
+    
+    // Destination, limit and reserve changes whose delay has elapsed apply before this sweep
+    let mut response = Response::new();
+    if let Some(event) = apply_pending_destination(deps.storage, &env)? {
+        response = response.add_event(event.to_event());
+    }
+    if let Some(event) = apply_pending_sweep_limit(deps.storage, &env)? {
+        response = response.add_event(event.to_event());
+    }
+    if let Some(event) = apply_pending_reserve_ratio(deps.storage, &env)? {
+        response = response.add_event(event.to_event());
+    }
+    if let Some(event) = settle_campaign(deps.storage, &env)? {
+        response = response.add_event(event.to_event());
+    }
     
     // Check withdrawal destination is set
     let destination = WITHDRAWAL_DESTINATION
@@ -245,39 +803,105 @@ pub fn execute_owner_withdraw(
         return Err(ContractError::WithdrawalNotUnlocked {});
     }
     
-    // Get contract balance
+    // Get contract balance, minus the reserve left for user withdrawals
     let balance = deps.querier.query_balance(&env.contract.address, &config.ustc_denom)?;
+    let (_, sweepable) = sweepable_balance(deps.storage, env.block.time.seconds(), balance.amount)?;
     
-    if balance.amount.is_zero() {
+    if sweepable.is_zero() {
         return Err(ContractError::NoBalanceToWithdraw {});
     }
     
-    // Transfer all to withdrawal destination via BankMsg
-    let bank_msg = BankMsg::Send {
-        to_address: destination.to_string(),
-        amount: vec![balance.clone()],
+    // Sweeps are capped by what is left in the current window, if throttled
+    let throttle = match SWEEP_LIMIT.load(deps.storage)? {
+        Some(limit) => {
+            let window = current_sweep_window(deps.storage, &limit, current_time)?;
+            Some((limit, window))
+        }
+        None => None,
+    };
+    let remaining = throttle
+        .as_ref()
+        .map(|(limit, window)| limit.max_amount.saturating_sub(window.swept));
+    
+    let amount = match amount {
+        None => remaining.map_or(sweepable, |remaining| sweepable.min(remaining)),
+        Some(amount) if amount.is_zero() => return Err(ContractError::InvalidAmount {}),
+        Some(amount) if amount > sweepable => {
+            return Err(ContractError::InsufficientContractBalance {
+                requested: amount,
+                available: sweepable,
+            })
+        }
+        Some(amount) => amount,
     };
     
-    Ok(Response::new()
-        .add_message(bank_msg)
+    if let Some((limit, window)) = throttle {
+        let remaining = limit.max_amount.saturating_sub(window.swept);
+        if amount.is_zero() || amount > remaining {
+            return Err(ContractError::SweepLimitExceeded {
+                remaining,
+                // Nothing to wait for when the amount is over the limit on its own
+                resets_at: window.resets_at.unwrap_or(current_time),
+            });
+        }
+        record_sweep(deps.storage, &limit, current_time, amount)?;
+    }
+    
+    response = response
         .add_attribute("action", "owner_withdraw")
         .add_attribute("destination", destination.to_string())
-        .add_attribute("amount", balance.amount.to_string())
-        .add_attribute("event", "owner_withdraw"))
+        .add_attribute("amount", amount.to_string());
+    
+    // Transfer each leg of the sweep via BankMsg
+    let shares = WITHDRAWAL_SHARES.load(deps.storage)?;
+    for (leg_destination, leg_amount) in split_sweep(&destination, &shares, amount) {
+        record_activity(
+            deps.storage,
+            &env,
+            ActivityKind::OwnerWithdraw {
+                destination: leg_destination.clone(),
+                amount: leg_amount,
+            },
+        )?;
+        
+        let event = ContractEvent::OwnerWithdraw {
+            destination: leg_destination.clone(),
+            amount: leg_amount,
+        };
+        
+        response = response
+            .add_message(BankMsg::Send {
+                to_address: leg_destination.to_string(),
+                amount: vec![Coin {
+                    denom: config.ustc_denom.clone(),
+                    amount: leg_amount,
+                }],
+            })
+            .add_event(event.to_event())
+            .add_attribute("leg", format!("{}:{}", leg_destination, leg_amount));
+    }
+    
+    Ok(response)
 }
 
-/// Owner-only function to set withdrawal destination and unlock timestamp
+/// Config manager function to set withdrawal destination and unlock timestamp
 /// 
 /// Sets the destination address for timelocked withdrawals and the timestamp when
 /// withdrawal becomes available. The timestamp must be at least 7 days (604800 seconds)
-/// in the future. Can be called multiple times to update the destination, but timestamp
-/// always resets to the new value.
+/// in the future.
+/// 
+/// The first destination applies immediately, since there is no sweep to redirect yet.
+/// Changing an existing destination is staged as a pending change that takes effect
+/// `DESTINATION_CHANGE_DELAY` seconds later, giving guardians time to cancel it if the
+/// config manager key is compromised. Staging a new change replaces any pending one;
+/// the unlock timestamp of the pending change applies together with its destination.
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
 /// * `env` - Contract environment information
 /// * `info` - Message information containing sender
 /// * `destination` - Address to receive USTC withdrawals
+/// * `shares` - Optional split of each sweep in basis points, empty for a single destination
 /// * `unlock_timestamp` - Unix timestamp (in seconds) when withdrawal becomes available
 /// 
 /// # Returns
@@ -286,36 +910,126 @@ pub fn execute_set_withdrawal_destination(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    destination: cosmwasm_std::Addr,
+    destination: String,
+    shares: Vec<UncheckedDestinationShare>,
     unlock_timestamp: u64,
 ) -> Result<Response, ContractError> {
-    let config = CONFIG.load(deps.storage)?;
-    
-    // Check caller is owner
-    verify_owner(&info, &config)?;
+    // Check caller is config manager
+    verify_role(deps.storage, &info, Role::ConfigManager)?;
     
-    // Validate timestamp is at least 7 days in the future
+    let (destination, shares) = validate_destination_change(deps.api, &env, &destination, &shares, unlock_timestamp)?;
     let current_time = env.block.time.seconds();
-    let min_timestamp = current_time + 7 * 24 * 60 * 60; // 7 days in seconds
     
-    if unlock_timestamp < min_timestamp {
-        return Err(ContractError::InvalidTimestamp {});
+    // A change whose delay has elapsed becomes the current destination first
+    let mut response = Response::new();
+    if let Some(event) = apply_pending_destination(deps.storage, &env)? {
+        response = response.add_event(event.to_event());
+    }
+    
+    // Replacing an existing destination is staged behind the change delay
+    if WITHDRAWAL_DESTINATION.load(deps.storage)?.is_some() {
+        let pending = PendingDestination {
+            destination: destination.clone(),
+            shares: shares.clone(),
+            unlock_timestamp,
+            effective_at: current_time + DESTINATION_CHANGE_DELAY,
+        };
+        PENDING_DESTINATION.save(deps.storage, &Some(pending.clone()))?;
+        
+        record_activity(
+            deps.storage,
+            &env,
+            ActivityKind::WithdrawalDestinationProposed {
+                destination: destination.clone(),
+                shares: shares.clone(),
+                unlock_timestamp,
+                effective_at: pending.effective_at,
+            },
+        )?;
+        
+        let event = ContractEvent::WithdrawalDestinationProposed {
+            destination: destination.clone(),
+            shares,
+            unlock_timestamp,
+            effective_at: pending.effective_at,
+        };
+        
+        return Ok(response
+            .add_event(event.to_event())
+            .add_attribute("action", "set_withdrawal_destination")
+            .add_attribute("destination", destination.to_string())
+            .add_attribute("unlock_timestamp", unlock_timestamp.to_string())
+            .add_attribute("effective_at", pending.effective_at.to_string()));
     }
     
     // Save withdrawal destination and unlock timestamp
-    WITHDRAWAL_DESTINATION.save(deps.storage, &Some(destination.clone()))?;
-    WITHDRAWAL_UNLOCK_TIMESTAMP.save(deps.storage, &unlock_timestamp)?;
+    let event = save_withdrawal_destination(deps.storage, &env, destination.clone(), shares, unlock_timestamp)?;
     
-    Ok(Response::new()
+    Ok(response
+        .add_event(event.to_event())
         .add_attribute("action", "set_withdrawal_destination")
         .add_attribute("destination", destination.to_string())
-        .add_attribute("unlock_timestamp", unlock_timestamp.to_string()))
+        .add_attribute("unlock_timestamp", unlock_timestamp.to_string())
+        .add_attribute("effective_at", current_time.to_string()))
+}
+
+/// Config manager or guardian function to cancel a pending destination change
+/// 
+/// A change whose delay has already elapsed is in effect and can no longer be cancelled;
+/// it has to be replaced by staging another change.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// 
+/// # Returns
+/// * `Response` with pending destination cancelled event attributes
+pub fn execute_cancel_pending_destination(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    // Check caller is config manager or guardian
+    verify_any_role(deps.storage, &info, &[Role::ConfigManager, Role::Guardian])?;
+    
+    let mut response = Response::new();
+    if let Some(event) = apply_pending_destination(deps.storage, &env)? {
+        response = response.add_event(event.to_event());
+    }
+    
+    let pending = PENDING_DESTINATION
+        .load(deps.storage)?
+        .ok_or(ContractError::NoPendingDestination {})?;
+    PENDING_DESTINATION.save(deps.storage, &None)?;
+    
+    record_activity(
+        deps.storage,
+        &env,
+        ActivityKind::PendingDestinationCancelled {
+            destination: pending.destination.clone(),
+        },
+    )?;
+    
+    let event = ContractEvent::PendingDestinationCancelled {
+        destination: pending.destination.clone(),
+    };
+    
+    Ok(response
+        .add_event(event.to_event())
+        .add_attribute("action", "cancel_pending_destination")
+        .add_attribute("destination", pending.destination.to_string()))
 }
 
-/// Owner-only function to update contract configuration
+/// Admin-only function to update contract configuration
+/// 
+/// Transferring ownership moves every role held by the current owner to the new owner,
+/// so the new owner takes over exactly the permissions the previous one had. Roles held
+/// by other addresses are not affected.
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
 /// * `info` - Message information containing sender
 /// * `owner` - Optional new owner address
 /// 
@@ -323,66 +1037,1654 @@ pub fn execute_set_withdrawal_destination(
 /// * `Response` with config update event attributes
 pub fn execute_update_config(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
-    owner: Option<cosmwasm_std::Addr>,
+    owner: Option<String>,
 ) -> Result<Response, ContractError> {
-    let mut config = CONFIG.load(deps.storage)?;
+    let mut response = Response::new();
     
-    // Check caller is owner
-    verify_owner(&info, &config)?;
+    // Check caller is admin
+    verify_role(deps.storage, &info, Role::Admin)?;
     
     // Update owner if provided
     if let Some(new_owner) = owner {
-        // Owner address is already validated as Addr type during deserialization
-        config.owner = new_owner;
-        CONFIG.save(deps.storage, &config)?;
+        let new_owner = validate_address(deps.api, &new_owner, |address| ContractError::InvalidOwnerAddress { address })?;
+        let event = transfer_ownership(deps.storage, &env, new_owner)?;
+        response = response.add_event(event.to_event());
     }
+    let config = CONFIG.load(deps.storage)?;
     
-    Ok(Response::new()
+    Ok(response
         .add_attribute("action", "update_config")
-        .add_attribute("owner", config.owner.to_string())
-        .add_attribute("event", "config_updated"))
+        .add_attribute("owner", config.owner.to_string()))
 }
 
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<cosmwasm_std::Binary> {
-    match msg {
-        QueryMsg::GetUserDeposit { user } => to_json_binary(&query_user_deposit(deps, user)?),
-        QueryMsg::GetAllUsers { start_after, limit } => {
-            query_all_users(deps, start_after, limit)
-                .map_err(|e| cosmwasm_std::StdError::generic_err(e.to_string()))
-                .and_then(|res| to_json_binary(&res))
-        },
-        QueryMsg::GetUserCount {} => to_json_binary(&query_user_count(deps)?),
-        QueryMsg::GetTotalDeposits {} => to_json_binary(&query_total_deposits(deps)?),
-        QueryMsg::GetConfig {} => to_json_binary(&query_config(deps)?),
-        QueryMsg::ValidateIndex {} => to_json_binary(&query_validate_index(deps)?),
-        QueryMsg::GetWithdrawalInfo {} => to_json_binary(&query_withdrawal_info(deps)?),
+/// Config manager function to configure the deposit distribution brackets
+/// 
+/// Thresholds are the lower bounds of every bracket but the first, in uusd. They must be
+/// non-zero and strictly ascending. Brackets can only be changed while there are no
+/// depositors, since moving every existing user into new brackets would require
+/// iterating all of USERS in a single transaction.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `thresholds` - New bracket thresholds
+/// 
+/// # Returns
+/// * `Response` with distribution brackets set event attributes
+pub fn execute_set_distribution_brackets(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    thresholds: Vec<Uint128>,
+) -> Result<Response, ContractError> {
+    // Check caller is config manager
+    verify_role(deps.storage, &info, Role::ConfigManager)?;
+    
+    let is_valid = thresholds.len() <= MAX_DISTRIBUTION_THRESHOLDS as usize
+        && !thresholds.first().is_some_and(|first| first.is_zero())
+        && thresholds.windows(2).all(|pair| pair[0] < pair[1]);
+    if !is_valid {
+        return Err(ContractError::InvalidDistributionThresholds {
+            max: MAX_DISTRIBUTION_THRESHOLDS,
+        });
     }
+    
+    if USER_COUNT.load(deps.storage)? != 0 {
+        return Err(ContractError::DistributionBracketsLocked {});
+    }
+    
+    DISTRIBUTION_THRESHOLDS.save(deps.storage, &thresholds)?;
+    DISTRIBUTION.clear(deps.storage);
+    
+    record_activity(deps.storage, &env, ActivityKind::DistributionBracketsSet { thresholds: thresholds.clone() })?;
+    
+    let thresholds_attr = thresholds
+        .iter()
+        .map(|threshold| threshold.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    
+    let event = ContractEvent::DistributionBracketsSet { thresholds };
+    
+    Ok(Response::new()
+        .add_event(event.to_event())
+        .add_attribute("action", "set_distribution_brackets")
+        .add_attribute("thresholds", thresholds_attr))
 }
 
-/// Query the deposit balance for a specific user
+/// Admin-only function to repair the user enumeration index in resumable batches
+/// 
+/// Rebuilds USER_INDEX, USER_INDEX_REVERSE and USER_COUNT from USERS, and recomputes
+/// TOTAL_DEPOSITS, in three phases:
+/// 1. Index: walk USER_INDEX by position, keeping entries whose user has a balance and
+///    whose reverse entry points back, and shifting them down over any gaps. All other
+///    entries are removed.
+/// 2. Users: walk USERS by address, appending users missing from the index, restoring
+///    missing deposit index entries, removing zero balances and summing balances into
+///    the new total and the rebuilt distribution.
+/// 3. Reverse: walk USER_INDEX_REVERSE by address, removing entries that do not match
+///    the forward index.
+/// 
+/// Each call processes at most `limit` entries and stores its progress. Deposits are
+/// rejected while a repair is in progress so no user is added between batches.
+/// Withdrawals and refunds stay open and keep the repair progress in step with the
+/// balances they lower. On a consistent index no corrections are reported.
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
-/// * `user` - Address of the user to query
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `start` - Cursor returned by the previous batch, or None to start a new repair
+/// * `limit` - Maximum number of entries to process (default: 50, max: 200)
+/// 
+/// # Returns
+/// * `Response` with a `RepairIndexResponse` as data and one `correction` attribute per fix
+pub fn execute_repair_index(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    start: Option<RepairIndexCursor>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    // Check caller is admin
+    verify_role(deps.storage, &info, Role::Admin)?;
+    
+    let mut progress = match start {
+        Some(start) => {
+            // Continuing a repair, the caller must be in sync with the stored progress
+            let start = RepairCursor {
+                phase: start.phase,
+                after_position: start.after_position,
+                after_address: start.after_address.map(|address| deps.api.addr_validate(&address)).transpose()?,
+            };
+            let progress = REPAIR_PROGRESS
+                .may_load(deps.storage)?
+                .ok_or(ContractError::RepairCursorMismatch {})?;
+            if progress.cursor != start {
+                return Err(ContractError::RepairCursorMismatch {});
+            }
+            progress
+        }
+        None => RepairProgress::initial(),
+    };
+    
+    let mut budget = limit.unwrap_or(DEFAULT_REPAIR_LIMIT).min(MAX_REPAIR_LIMIT) as usize;
+    let mut corrections = Vec::new();
+    let mut complete = false;
+    
+    while budget > 0 && !complete {
+        let (processed, phase_done) = match progress.cursor.phase {
+            RepairPhase::Index => repair_index_phase(deps.storage, &mut progress, budget, &mut corrections)?,
+            RepairPhase::Users => repair_users_phase(deps.storage, &mut progress, budget, &mut corrections)?,
+            RepairPhase::Reverse => repair_reverse_phase(deps.storage, &mut progress, budget, &mut corrections)?,
+        };
+        budget -= processed;
+        
+        if phase_done {
+            match progress.cursor.phase {
+                RepairPhase::Index => {
+                    // Left over if an earlier repair was restarted before completing
+                    DISTRIBUTION_REBUILD.clear(deps.storage);
+                    progress.cursor.phase = RepairPhase::Users;
+                },
+                RepairPhase::Users => progress.cursor.phase = RepairPhase::Reverse,
+                RepairPhase::Reverse => complete = true,
+            }
+            progress.cursor.after_position = None;
+            progress.cursor.after_address = None;
+        }
+    }
+    
+    if complete {
+        let user_count = USER_COUNT.load(deps.storage)?;
+        if user_count != progress.next_index {
+            corrections.push(format!(
+                "User count corrected from {} to {}",
+                user_count, progress.next_index
+            ));
+            USER_COUNT.save(deps.storage, &progress.next_index)?;
+        }
+        
+        let total_deposits = TOTAL_DEPOSITS.load(deps.storage)?;
+        if total_deposits != progress.total {
+            corrections.push(format!(
+                "Total deposits corrected from {} to {}",
+                total_deposits, progress.total
+            ));
+            TOTAL_DEPOSITS.save(deps.storage, &progress.total)?;
+        }
+        
+        let brackets = DISTRIBUTION_THRESHOLDS.load(deps.storage)?.len() as u32 + 1;
+        for bracket in 0..brackets {
+            let stats = DISTRIBUTION.may_load(deps.storage, bracket)?.unwrap_or_default();
+            let rebuilt = DISTRIBUTION_REBUILD.may_load(deps.storage, bracket)?.unwrap_or_default();
+            if stats != rebuilt {
+                corrections.push(format!(
+                    "Distribution bracket {} corrected from {} users ({}) to {} users ({})",
+                    bracket, stats.count, stats.volume, rebuilt.count, rebuilt.volume
+                ));
+                DISTRIBUTION.save(deps.storage, bracket, &rebuilt)?;
+            }
+        }
+        DISTRIBUTION_REBUILD.clear(deps.storage);
+        
+        REPAIR_PROGRESS.remove(deps.storage);
+    } else {
+        REPAIR_PROGRESS.save(deps.storage, &progress)?;
+    }
+    
+    let next_start = if complete { None } else { Some(progress.cursor.into()) };
+    
+    let total = TOTAL_DEPOSITS.load(deps.storage)?;
+    record_activity(
+        deps.storage,
+        &env,
+        ActivityKind::IndexRepaired {
+            complete,
+            corrections: corrections.len() as u32,
+            total,
+        },
+    )?;
+    
+    let event = ContractEvent::IndexRepaired {
+        complete,
+        corrections: corrections.len() as u32,
+    };
+    
+    let response = Response::new()
+        .add_event(event.to_event())
+        .add_attribute("action", "repair_index")
+        .add_attribute("complete", complete.to_string())
+        .add_attribute("corrections", corrections.len().to_string())
+        .add_attributes(corrections.iter().map(|correction| ("correction", correction.clone())))
+        .set_data(to_json_binary(&RepairIndexResponse {
+            corrections,
+            next_start,
+            complete,
+        })?);
+    
+    Ok(response)
+}
+
+/// Admin-only function to grant a role to an address
+/// 
+/// Granting a role the address already holds succeeds without changes.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `role` - Role to grant
+/// * `address` - Address receiving the role
+/// 
+/// # Returns
+/// * `Response` with role granted event attributes
+pub fn execute_grant_role(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    role: Role,
+    address: String,
+) -> Result<Response, ContractError> {
+    // Check caller is admin
+    verify_role(deps.storage, &info, Role::Admin)?;
+    
+    let address = validate_address(deps.api, &address, |address| ContractError::InvalidRoleAddress { address })?;
+    let mut response = Response::new();
+    if !ROLES.has(deps.storage, (role.as_str(), &address)) {
+        ROLES.save(deps.storage, (role.as_str(), &address), &Empty {})?;
+        record_activity(
+            deps.storage,
+            &env,
+            ActivityKind::RoleGranted {
+                role,
+                address: address.clone(),
+            },
+        )?;
+        
+        let event = ContractEvent::RoleGranted {
+            role,
+            address: address.clone(),
+        };
+        response = response.add_event(event.to_event());
+    }
+    
+    Ok(response
+        .add_attribute("action", "grant_role")
+        .add_attribute("role", role.as_str())
+        .add_attribute("address", address.to_string()))
+}
+
+/// Admin-only function to revoke a role from an address
+/// 
+/// Revoking a role the address does not hold succeeds without changes. The last admin
+/// cannot be revoked, otherwise roles could never be granted again.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `role` - Role to revoke
+/// * `address` - Address losing the role
+/// 
+/// # Returns
+/// * `Response` with role revoked event attributes
+pub fn execute_revoke_role(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    role: Role,
+    address: String,
+) -> Result<Response, ContractError> {
+    // Check caller is admin
+    verify_role(deps.storage, &info, Role::Admin)?;
+    
+    let address = validate_address(deps.api, &address, |address| ContractError::InvalidRoleAddress { address })?;
+    let mut response = Response::new();
+    if ROLES.has(deps.storage, (role.as_str(), &address)) {
+        if role == Role::Admin {
+            let admin_count = ROLES
+                .prefix(Role::Admin.as_str())
+                .keys(deps.storage, None, None, Order::Ascending)
+                .take(2)
+                .count();
+            if admin_count < 2 {
+                return Err(ContractError::CannotRevokeLastAdmin {});
+            }
+        }
+        
+        ROLES.remove(deps.storage, (role.as_str(), &address));
+        record_activity(
+            deps.storage,
+            &env,
+            ActivityKind::RoleRevoked {
+                role,
+                address: address.clone(),
+            },
+        )?;
+        
+        let event = ContractEvent::RoleRevoked {
+            role,
+            address: address.clone(),
+        };
+        response = response.add_event(event.to_event());
+    }
+    
+    Ok(response
+        .add_attribute("action", "revoke_role")
+        .add_attribute("role", role.as_str())
+        .add_attribute("address", address.to_string()))
+}
+
+/// Guardian-only function to pause or unpause deposits
+/// 
+/// Only deposits are paused; withdrawals stay available so users can always exit.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `paused` - True to pause deposits, false to resume them
+/// 
+/// # Returns
+/// * `Response` with paused set event attributes
+pub fn execute_set_paused(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    // Check caller is guardian
+    verify_role(deps.storage, &info, Role::Guardian)?;
+    
+    let mut response = Response::new();
+    if let Some(event) = set_paused(deps.storage, &env, paused)? {
+        response = response.add_event(event.to_event());
+    }
+    
+    Ok(response
+        .add_attribute("action", "set_paused")
+        .add_attribute("paused", paused.to_string()))
+}
+
+/// Config manager function to set or remove the sweep limit
+/// 
+/// The amount already swept in the current window keeps counting against a new limit,
+/// so lowering the limit takes effect immediately. A limit that allows no more over a
+/// window no shorter applies at once and discards any staged change. Raising or removing
+/// the limit is staged and takes effect `LOOSENING_DELAY` seconds later, so the throttle
+/// cannot be lifted and the balance swept in the same block. Staging a new change
+/// replaces any pending one.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `limit` - New sweep limit, or None to remove it
+/// 
+/// # Returns
+/// * `Response` with sweep limit set event attributes
+pub fn execute_set_sweep_limit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    limit: Option<SweepLimit>,
+) -> Result<Response, ContractError> {
+    // Check caller is config manager
+    verify_role(deps.storage, &info, Role::ConfigManager)?;
+    
+    if let Some(limit) = &limit {
+        if limit.max_amount.is_zero() || limit.window == 0 {
+            return Err(ContractError::InvalidSweepLimit {});
+        }
+    }
+    
+    let current_time = env.block.time.seconds();
+    
+    // A change whose delay has elapsed becomes the current limit first
+    let mut response = Response::new();
+    if let Some(event) = apply_pending_sweep_limit(deps.storage, &env)? {
+        response = response.add_event(event.to_event());
+    }
+    
+    response = response
+        .add_attribute("action", "set_sweep_limit")
+        .add_attribute("max_amount", limit.as_ref().map(|l| l.max_amount.to_string()).unwrap_or_default())
+        .add_attribute("window", limit.as_ref().map(|l| l.window.to_string()).unwrap_or_default());
+    
+    // Loosening the throttle is staged behind the delay
+    if !tightens_sweep_limit(&SWEEP_LIMIT.load(deps.storage)?, &limit) {
+        let pending = PendingSweepLimit {
+            limit: limit.clone(),
+            effective_at: current_time + LOOSENING_DELAY,
+        };
+        PENDING_SWEEP_LIMIT.save(deps.storage, &Some(pending.clone()))?;
+        
+        record_activity(
+            deps.storage,
+            &env,
+            ActivityKind::SweepLimitProposed {
+                limit: limit.clone(),
+                effective_at: pending.effective_at,
+            },
+        )?;
+        
+        let event = ContractEvent::SweepLimitProposed {
+            limit,
+            effective_at: pending.effective_at,
+        };
+        
+        return Ok(response
+            .add_event(event.to_event())
+            .add_attribute("effective_at", pending.effective_at.to_string()));
+    }
+    
+    // A tighter limit supersedes any staged loosening
+    PENDING_SWEEP_LIMIT.save(deps.storage, &None)?;
+    let event = save_sweep_limit(deps.storage, &env, limit)?;
+    
+    Ok(response
+        .add_event(event.to_event())
+        .add_attribute("effective_at", current_time.to_string()))
+}
+
+/// Config manager function to set the reserve ratio
+/// 
+/// Raising the ratio, or setting the current one, applies immediately and discards any
+/// staged change. Lowering it is staged and takes effect `LOOSENING_DELAY` seconds later,
+/// so the reserve cannot be dropped and swept in the same block. Staging a new change
+/// replaces any pending one.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `ratio` - Share of TOTAL_DEPOSITS sweeps must leave behind, at most 1
+/// 
+/// # Returns
+/// * `Response` with reserve ratio set event attributes
+pub fn execute_set_reserve_ratio(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    ratio: Decimal,
+) -> Result<Response, ContractError> {
+    // Check caller is config manager
+    verify_role(deps.storage, &info, Role::ConfigManager)?;
+    
+    if ratio > Decimal::one() {
+        return Err(ContractError::InvalidReserveRatio {});
+    }
+    
+    let current_time = env.block.time.seconds();
+    
+    // A change whose delay has elapsed becomes the current ratio first
+    let mut response = Response::new();
+    if let Some(event) = apply_pending_reserve_ratio(deps.storage, &env)? {
+        response = response.add_event(event.to_event());
+    }
+    
+    response = response
+        .add_attribute("action", "set_reserve_ratio")
+        .add_attribute("ratio", ratio.to_string());
+    
+    // Lowering the reserve is staged behind the delay
+    if ratio < RESERVE_RATIO.load(deps.storage)? {
+        let pending = PendingReserveRatio {
+            ratio,
+            effective_at: current_time + LOOSENING_DELAY,
+        };
+        PENDING_RESERVE_RATIO.save(deps.storage, &Some(pending.clone()))?;
+        
+        record_activity(
+            deps.storage,
+            &env,
+            ActivityKind::ReserveRatioProposed {
+                ratio,
+                effective_at: pending.effective_at,
+            },
+        )?;
+        
+        let event = ContractEvent::ReserveRatioProposed {
+            ratio,
+            effective_at: pending.effective_at,
+        };
+        
+        return Ok(response
+            .add_event(event.to_event())
+            .add_attribute("effective_at", pending.effective_at.to_string()));
+    }
+    
+    // A higher ratio supersedes any staged lowering
+    PENDING_RESERVE_RATIO.save(deps.storage, &None)?;
+    let event = save_reserve_ratio(deps.storage, &env, ratio)?;
+    
+    Ok(response
+        .add_event(event.to_event())
+        .add_attribute("effective_at", current_time.to_string()))
+}
+
+/// Admin function to recover tokens sent to the contract by mistake
+/// 
+/// Sends the contract's whole balance of the token, queried from the bank or the CW20
+/// contract, to `recipient`. The deposit denom is refused. The contract tracks no CW20
+/// assets, so any CW20 balance is stray.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `token` - Native denom or CW20 contract to recover
+/// * `recipient` - Address receiving the tokens
+/// 
+/// # Returns
+/// * `Response` with the transfer message and recovery event
+pub fn execute_recover_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token: RecoverableToken,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    // Check caller is admin
+    verify_role(deps.storage, &info, Role::Admin)?;
+    
+    let recipient = validate_address(deps.api, &recipient, |address| ContractError::InvalidRecipientAddress { address })?;
+    let config = CONFIG.load(deps.storage)?;
+    let (token, amount, msg) = match token {
+        RecoverableToken::Native { denom } => {
+            if denom == config.ustc_denom {
+                return Err(ContractError::CannotRecoverTrackedAsset { token: denom });
+            }
+            let amount = deps
+                .querier
+                .query_all_balances(&env.contract.address)?
+                .into_iter()
+                .find(|coin| coin.denom == denom)
+                .map(|coin| coin.amount)
+                .unwrap_or_default();
+            let msg: cosmwasm_std::CosmosMsg = BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![Coin { denom: denom.clone(), amount }],
+            }
+            .into();
+            (denom, amount, msg)
+        },
+        RecoverableToken::Cw20 { address } => {
+            let address = validate_address(deps.api, &address, |address| ContractError::InvalidTokenAddress { address })?;
+            let balance: BalanceResponse = deps.querier.query_wasm_smart(
+                &address,
+                &Cw20QueryMsg::Balance { address: env.contract.address.to_string() },
+            )?;
+            let msg = WasmMsg::Execute {
+                contract_addr: address.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount: balance.balance,
+                })?,
+                funds: vec![],
+            }
+            .into();
+            (address.to_string(), balance.balance, msg)
+        },
+    };
+    if amount.is_zero() {
+        return Err(ContractError::NothingToRecover { token });
+    }
+    
+    record_activity(
+        deps.storage,
+        &env,
+        ActivityKind::TokensRecovered { token: token.clone(), recipient: recipient.clone(), amount },
+    )?;
+    
+    let event = ContractEvent::TokensRecovered {
+        token: token.clone(),
+        recipient: recipient.clone(),
+        amount,
+    };
+    
+    Ok(Response::new()
+        .add_message(msg)
+        .add_event(event.to_event())
+        .add_attribute("action", "recover_tokens")
+        .add_attribute("token", token)
+        .add_attribute("recipient", recipient.to_string())
+        .add_attribute("amount", amount.to_string()))
+}
+
+/// Config manager function to change the deposit denomination
+/// 
+/// Balances carry no denom of their own, so the denom can only change while nothing is
+/// deposited or owed to queued withdrawals.
+/// 
+/// Distribution thresholds still at the defaults for the old decimals are scaled to the
+/// new ones; thresholds set with SetDistributionBrackets are kept.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `denom` - New deposit denomination
+/// * `decimals` - Display decimals of the new denom, at most 18
+/// 
+/// # Returns
+/// * `Response` with denom set event attributes
+pub fn execute_set_denom(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    decimals: u8,
+) -> Result<Response, ContractError> {
+    // Check caller is config manager
+    verify_role(deps.storage, &info, Role::ConfigManager)?;
+    
+    validate_denom_name(&denom)?;
+    if decimals > MAX_DECIMALS {
+        return Err(ContractError::InvalidDecimals { max: MAX_DECIMALS });
+    }
+    
+    if !TOTAL_DEPOSITS.load(deps.storage)?.is_zero() || !WITHDRAW_QUEUE_STATE.load(deps.storage)?.total.is_zero() {
+        return Err(ContractError::DenomLocked {});
+    }
+    
+    let mut config = CONFIG.load(deps.storage)?;
+    let old_decimals = config.decimals;
+    config.ustc_denom = denom.clone();
+    config.decimals = decimals;
+    CONFIG.save(deps.storage, &config)?;
+    
+    record_activity(deps.storage, &env, ActivityKind::DenomSet { denom: denom.clone(), decimals })?;
+    
+    let event = ContractEvent::DenomSet {
+        denom: denom.clone(),
+        decimals,
+    };
+    let mut response = Response::new().add_event(event.to_event());
+    
+    // Nothing is deposited, so every bracket is empty and no user needs rebucketing
+    if decimals != old_decimals && DISTRIBUTION_THRESHOLDS.load(deps.storage)? == default_distribution_thresholds(old_decimals) {
+        let thresholds = default_distribution_thresholds(decimals);
+        DISTRIBUTION_THRESHOLDS.save(deps.storage, &thresholds)?;
+        record_activity(deps.storage, &env, ActivityKind::DistributionBracketsSet { thresholds: thresholds.clone() })?;
+        response = response.add_event(ContractEvent::DistributionBracketsSet { thresholds }.to_event());
+    }
+    
+    Ok(response
+        .add_attribute("action", "set_denom")
+        .add_attribute("denom", denom)
+        .add_attribute("decimals", decimals.to_string()))
+}
+
+/// Admin function to register a balance change hook
+/// 
+/// The hook gets a reply id no other hook had before, so replies to calls dispatched
+/// before a hook list change still name the right hook.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `addr` - Address of the hook contract
+/// 
+/// # Returns
+/// * `Response` with hook added event
+pub fn execute_add_hook(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    // Check caller is admin
+    verify_role(deps.storage, &info, Role::Admin)?;
+    
+    let hook = validate_address(deps.api, &addr, |address| ContractError::InvalidHookAddress { address })?;
+    if HOOKS.has(deps.storage, &hook) {
+        return Err(ContractError::HookAlreadyRegistered { hook: hook.to_string() });
+    }
+    let hook_count = HOOKS.keys(deps.storage, None, None, Order::Ascending).count() as u32;
+    if hook_count >= MAX_HOOKS {
+        return Err(ContractError::TooManyHooks { max: MAX_HOOKS });
+    }
+    let reply_id = HOOK_REPLY_IDS
+        .keys(deps.storage, None, None, Order::Descending)
+        .next()
+        .transpose()?
+        .map_or(0, |last| last + 1);
+    HOOKS.save(deps.storage, &hook, &reply_id)?;
+    HOOK_REPLY_IDS.save(deps.storage, reply_id, &hook)?;
+    
+    record_activity(deps.storage, &env, ActivityKind::HookAdded { hook: hook.clone() })?;
+    
+    let event = ContractEvent::HookAdded { hook: hook.clone() };
+    
+    Ok(Response::new()
+        .add_event(event.to_event())
+        .add_attribute("action", "add_hook")
+        .add_attribute("hook", hook.to_string()))
+}
+
+/// Admin function to unregister a balance change hook
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `info` - Message information containing sender
+/// * `addr` - Address of the hook contract
+/// 
+/// # Returns
+/// * `Response` with hook removed event
+pub fn execute_remove_hook(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    // Check caller is admin
+    verify_role(deps.storage, &info, Role::Admin)?;
+    
+    let hook = validate_address(deps.api, &addr, |address| ContractError::InvalidHookAddress { address })?;
+    if !HOOKS.has(deps.storage, &hook) {
+        return Err(ContractError::HookNotRegistered { hook: hook.to_string() });
+    }
+    HOOKS.remove(deps.storage, &hook);
+    
+    record_activity(deps.storage, &env, ActivityKind::HookRemoved { hook: hook.clone() })?;
+    
+    let event = ContractEvent::HookRemoved { hook: hook.clone() };
+    
+    Ok(Response::new()
+        .add_event(event.to_event())
+        .add_attribute("action", "remove_hook")
+        .add_attribute("hook", hook.to_string()))
+}
+
+/// Handle the reply of a failed balance change hook
+/// 
+/// Hooks reply on error only. The failure is recorded and the reply succeeds, so the
+/// balance change that triggered the hook goes through. A reply id no hook was ever
+/// assigned cannot come from a hook call; it is still only reported, never reverted.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `msg` - Reply whose id is the failed hook's id in HOOK_REPLY_IDS
+/// 
+/// # Returns
+/// * `Response` with hook failed event
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    let error = match msg.result {
+        SubMsgResult::Err(error) => error,
+        SubMsgResult::Ok(_) => return Err(ContractError::UnexpectedReply { id: msg.id }),
+    };
+    let Some(hook) = HOOK_REPLY_IDS.may_load(deps.storage, msg.id)? else {
+        return Ok(Response::new()
+            .add_attribute("action", "hook_failed")
+            .add_attribute("reply_id", msg.id.to_string())
+            .add_attribute("error", error));
+    };
+    
+    record_activity(deps.storage, &env, ActivityKind::HookFailed { hook: hook.clone() })?;
+    
+    let event = ContractEvent::HookFailed {
+        hook: hook.clone(),
+        error: error.clone(),
+    };
+    
+    Ok(Response::new()
+        .add_event(event.to_event())
+        .add_attribute("action", "hook_failed")
+        .add_attribute("hook", hook.to_string())
+        .add_attribute("error", error))
+}
+
+/// Dispatch a governance sudo message
+/// 
+/// Sudo messages can only be sent by the chain itself, so no role is checked. Each
+/// action emits the same event as its execute counterpart, tagged with a `sudo_` action.
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::ReplaceOwner { owner } => sudo_replace_owner(deps, env, owner),
+        SudoMsg::ForceCancelCampaign {} => sudo_force_cancel_campaign(deps, env),
+        SudoMsg::ForcePause { paused } => sudo_force_pause(deps, env, paused),
+        SudoMsg::SetWithdrawalDestination { destination, shares, unlock_timestamp } => {
+            sudo_set_withdrawal_destination(deps, env, destination, shares, unlock_timestamp)
+        },
+    }
+}
+
+/// Governance function to replace the owner
+/// 
+/// The new owner gets every role and all other role holders are revoked, since the lost
+/// key may be any of them rather than the owner's.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `owner` - New owner address
+/// 
+/// # Returns
+/// * `Response` with config update event
+pub fn sudo_replace_owner(deps: DepsMut, env: Env, owner: String) -> Result<Response, ContractError> {
+    let owner = validate_address(deps.api, &owner, |address| ContractError::InvalidOwnerAddress { address })?;
+    let role_events = reset_roles(deps.storage, &env, &owner)?;
+    let event = transfer_ownership(deps.storage, &env, owner.clone())?;
+    
+    Ok(Response::new()
+        .add_events(role_events.iter().map(ContractEvent::to_event))
+        .add_event(event.to_event())
+        .add_attribute("action", "sudo_replace_owner")
+        .add_attribute("owner", owner.to_string()))
+}
+
+/// Governance function to cancel the campaign
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// 
+/// # Returns
+/// * `Response` with cancellation event
+pub fn sudo_force_cancel_campaign(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let cancellation = cancel_campaign(deps.storage, &env)?;
+    let user_count = cancellation.users_at_cancel;
+    let total_deposits = cancellation.deposits_at_cancel;
+    
+    let event = ContractEvent::CampaignCancelled { user_count, total_deposits };
+    
+    Ok(Response::new()
+        .add_event(event.to_event())
+        .add_attribute("action", "sudo_force_cancel_campaign")
+        .add_attribute("user_count", user_count.to_string())
+        .add_attribute("total_deposits", total_deposits.to_string()))
+}
+
+/// Governance function to pause or unpause deposits
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `paused` - Whether deposits are paused
+/// 
+/// # Returns
+/// * `Response` with paused set event if the flag changed
+pub fn sudo_force_pause(deps: DepsMut, env: Env, paused: bool) -> Result<Response, ContractError> {
+    let mut response = Response::new();
+    if let Some(event) = set_paused(deps.storage, &env, paused)? {
+        response = response.add_event(event.to_event());
+    }
+    
+    Ok(response
+        .add_attribute("action", "sudo_force_pause")
+        .add_attribute("paused", paused.to_string()))
+}
+
+/// Governance function to set the withdrawal destination immediately
+/// 
+/// Skips the destination change delay, discarding any pending change.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// * `destination` - Address to receive USTC withdrawals
+/// * `shares` - Optional split of each sweep in basis points
+/// * `unlock_timestamp` - Unix timestamp when withdrawal becomes available (min 7 days)
+/// 
+/// # Returns
+/// * `Response` with withdrawal destination set event
+pub fn sudo_set_withdrawal_destination(
+    deps: DepsMut,
+    env: Env,
+    destination: String,
+    shares: Vec<UncheckedDestinationShare>,
+    unlock_timestamp: u64,
+) -> Result<Response, ContractError> {
+    let (destination, shares) = validate_destination_change(deps.api, &env, &destination, &shares, unlock_timestamp)?;
+    
+    let mut response = Response::new();
+    if let Some(pending) = PENDING_DESTINATION.load(deps.storage)? {
+        PENDING_DESTINATION.save(deps.storage, &None)?;
+        record_activity(
+            deps.storage,
+            &env,
+            ActivityKind::PendingDestinationCancelled {
+                destination: pending.destination.clone(),
+            },
+        )?;
+        let event = ContractEvent::PendingDestinationCancelled {
+            destination: pending.destination,
+        };
+        response = response.add_event(event.to_event());
+    }
+    
+    let event = save_withdrawal_destination(deps.storage, &env, destination.clone(), shares, unlock_timestamp)?;
+    
+    Ok(response
+        .add_event(event.to_event())
+        .add_attribute("action", "sudo_set_withdrawal_destination")
+        .add_attribute("destination", destination.to_string())
+        .add_attribute("unlock_timestamp", unlock_timestamp.to_string()))
+}
+
+/// Index phase of `execute_repair_index`
+/// 
+/// Returns the number of entries processed and whether the phase is finished.
+fn repair_index_phase(
+    storage: &mut dyn cosmwasm_std::Storage,
+    progress: &mut RepairProgress,
+    budget: usize,
+    corrections: &mut Vec<String>,
+) -> StdResult<(usize, bool)> {
+    let min = progress.cursor.after_position.map(Bound::exclusive);
+    let entries = USER_INDEX
+        .range(storage, min, None, Order::Ascending)
+        .take(budget)
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    for (position, user) in &entries {
+        let balance = USERS.may_load(storage, user)?.unwrap_or(Uint128::zero());
+        let reverse = USER_INDEX_REVERSE.may_load(storage, user)?;
+        
+        if !balance.is_zero() && reverse == Some(*position) {
+            // Valid entry, shift it down over any gap left by removed entries.
+            // Positions below next_index were already processed, so nothing is overwritten.
+            if *position != progress.next_index {
+                USER_INDEX.remove(storage, *position);
+                USER_INDEX.save(storage, progress.next_index, user)?;
+                USER_INDEX_REVERSE.save(storage, user, &progress.next_index)?;
+                corrections.push(format!(
+                    "Moved user {} from index {} to {}",
+                    user, position, progress.next_index
+                ));
+            }
+            progress.next_index += 1;
+        } else {
+            USER_INDEX.remove(storage, *position);
+            if reverse == Some(*position) {
+                USER_INDEX_REVERSE.remove(storage, user);
+            }
+            corrections.push(format!(
+                "Removed index {} pointing to user {} (balance {}, reverse index {:?})",
+                position, user, balance, reverse
+            ));
+        }
+        progress.cursor.after_position = Some(*position);
+    }
+    
+    Ok((entries.len(), entries.len() < budget))
+}
+
+/// Users phase of `execute_repair_index`
+/// 
+/// Returns the number of entries processed and whether the phase is finished.
+fn repair_users_phase(
+    storage: &mut dyn cosmwasm_std::Storage,
+    progress: &mut RepairProgress,
+    budget: usize,
+    corrections: &mut Vec<String>,
+) -> Result<(usize, bool), ContractError> {
+    let thresholds = DISTRIBUTION_THRESHOLDS.load(storage)?;
+    let min = progress.cursor.after_address.as_ref().map(Bound::exclusive);
+    let users = USERS
+        .range(storage, min, None, Order::Ascending)
+        .take(budget)
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    for (user, balance) in &users {
+        if balance.is_zero() {
+            USERS.remove(storage, user)?;
+            corrections.push(format!("Removed user {} with zero balance", user));
+        } else {
+            progress.total = progress.total.checked_add(*balance).map_err(cosmwasm_std::StdError::from)?;
+            
+            let bracket = bracket_of(&thresholds, *balance);
+            let mut stats = DISTRIBUTION_REBUILD.may_load(storage, bracket)?.unwrap_or_default();
+            stats.count += 1;
+            stats.volume += *balance;
+            DISTRIBUTION_REBUILD.save(storage, bracket, &stats)?;
+            
+            let indexed = match USER_INDEX_REVERSE.may_load(storage, user)? {
+                Some(idx) => USER_INDEX.may_load(storage, idx)?.as_ref() == Some(user),
+                None => false,
+            };
+            if !indexed {
+                USER_INDEX.save(storage, progress.next_index, user)?;
+                USER_INDEX_REVERSE.save(storage, user, &progress.next_index)?;
+                corrections.push(format!(
+                    "Added missing user {} at index {}",
+                    user, progress.next_index
+                ));
+                progress.next_index += 1;
+            }
+            
+            // Balances stored before the deposit index existed have no entry in it;
+            // saving the balance again writes the entry
+            let ranked = USERS
+                .idx
+                .deposit
+                .prefix(balance.u128())
+                .keys(storage, Some(Bound::inclusive(user.clone())), Some(Bound::inclusive(user.clone())), Order::Ascending)
+                .next()
+                .is_some();
+            if !ranked {
+                USERS.save(storage, user, balance)?;
+                corrections.push(format!("Added missing deposit index entry for user {}", user));
+            }
+        }
+        progress.cursor.after_address = Some(user.clone());
+    }
+    
+    Ok((users.len(), users.len() < budget))
+}
+
+/// Reverse phase of `execute_repair_index`
+/// 
+/// Returns the number of entries processed and whether the phase is finished.
+fn repair_reverse_phase(
+    storage: &mut dyn cosmwasm_std::Storage,
+    progress: &mut RepairProgress,
+    budget: usize,
+    corrections: &mut Vec<String>,
+) -> StdResult<(usize, bool)> {
+    let min = progress.cursor.after_address.as_ref().map(Bound::exclusive);
+    let entries = USER_INDEX_REVERSE
+        .range(storage, min, None, Order::Ascending)
+        .take(budget)
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    for (user, idx) in &entries {
+        if USER_INDEX.may_load(storage, *idx)?.as_ref() != Some(user) {
+            USER_INDEX_REVERSE.remove(storage, user);
+            corrections.push(format!("Removed orphaned reverse index entry for user {} (index {})", user, idx));
+        }
+        progress.cursor.after_address = Some(user.clone());
+    }
+    
+    Ok((entries.len(), entries.len() < budget))
+}
+
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<cosmwasm_std::Binary> {
+    match msg {
+        QueryMsg::GetUserDeposit { user } => to_json_binary(&query_user_deposit(deps, user)?),
+        QueryMsg::GetUserDeposits { users } => to_json_binary(&query_user_deposits(deps, users)?),
+        QueryMsg::GetAllUsers { start_after, limit } => {
+            query_all_users(deps, start_after, limit)
+                .map_err(|e| cosmwasm_std::StdError::generic_err(e.to_string()))
+                .and_then(|res| to_json_binary(&res))
+        },
+        QueryMsg::GetAllUsersByAddress { start_after, limit } => {
+            to_json_binary(&query_all_users_by_address(deps, start_after, limit)?)
+        },
+        QueryMsg::GetUserCount {} => to_json_binary(&query_user_count(deps)?),
+        QueryMsg::GetTotalDeposits {} => to_json_binary(&query_total_deposits(deps)?),
+        QueryMsg::GetConfig {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::ValidateIndex { start, limit } => to_json_binary(&query_validate_index(deps, start, limit)?),
+        QueryMsg::GetWithdrawalInfo {} => to_json_binary(&query_withdrawal_info(deps, env)?),
+        QueryMsg::GetDestinationSchedule {} => to_json_binary(&query_destination_schedule(deps, env)?),
+        QueryMsg::GetTopDepositors { start_after, limit } => {
+            query_top_depositors(deps, start_after, limit)
+                .map_err(|e| cosmwasm_std::StdError::generic_err(e.to_string()))
+                .and_then(|res| to_json_binary(&res))
+        },
+        QueryMsg::GetUserRank { user } => to_json_binary(&query_user_rank(deps, user)?),
+        QueryMsg::GetUserHistory { user, start_after, limit, order } => {
+            to_json_binary(&query_user_history(deps, user, start_after, limit, order)?)
+        },
+        QueryMsg::GetActivity { start_after_seq, limit } => {
+            to_json_binary(&query_activity(deps, start_after_seq, limit)?)
+        },
+        QueryMsg::GetLatestSequence {} => to_json_binary(&query_latest_sequence(deps)?),
+        QueryMsg::GetDailyStats { from_day, to_day } => {
+            to_json_binary(&query_daily_stats(deps, from_day, to_day)?)
+        },
+        QueryMsg::GetDistribution {} => to_json_binary(&query_distribution(deps)?),
+        QueryMsg::GetRoles {} => to_json_binary(&query_roles(deps)?),
+        QueryMsg::GetSweepAllowance {} => to_json_binary(&query_sweep_allowance(deps, env)?),
+        QueryMsg::GetLiquidity {} => to_json_binary(&query_liquidity(deps, env)?),
+        QueryMsg::GetQueuePosition { user } => to_json_binary(&query_queue_position(deps, user)?),
+        QueryMsg::GetWithdrawQueue { start_after, limit } => {
+            to_json_binary(&query_withdraw_queue(deps, start_after, limit)?)
+        },
+        QueryMsg::GetRefundProgress {} => to_json_binary(&query_refund_progress(deps)?),
+        QueryMsg::GetCampaignStatus {} => to_json_binary(&query_campaign_status(deps, env)?),
+        QueryMsg::ListHooks {} => to_json_binary(&query_list_hooks(deps)?),
+    }
+}
+
+/// Query the deposit balance for a specific user
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `user` - Address of the user to query
+/// 
+/// # Returns
+/// * `GetUserDepositResponse` containing user address and deposit amount
+pub fn query_user_deposit(
+    deps: Deps,
+    user: String,
+) -> StdResult<GetUserDepositResponse> {
+    let user = deps.api.addr_validate(&user)?;
+    let deposit = USERS.may_load(deps.storage, &user)?.unwrap_or(Uint128::zero());
+    Ok(GetUserDepositResponse {
+        user,
+        deposit,
+    })
+}
+
+/// Query deposits for a list of addresses in one call
+/// 
+/// Each address is validated with `addr_validate`. Invalid addresses are reported in
+/// their own entry instead of failing the whole query, so reconciliation tools can
+/// submit raw address lists.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `users` - Addresses to look up (at most 100)
+/// 
+/// # Returns
+/// * `GetUserDepositsResponse` with one entry per requested address, in request order
+pub fn query_user_deposits(
+    deps: Deps,
+    users: Vec<String>,
+) -> StdResult<GetUserDepositsResponse> {
+    if users.len() > MAX_QUERY_LIMIT as usize {
+        return Err(cosmwasm_std::StdError::generic_err(format!(
+            "Too many users: at most {} addresses per query",
+            MAX_QUERY_LIMIT
+        )));
+    }
+    
+    let deposits = users
+        .into_iter()
+        .map(|user| {
+            let addr = match deps.api.addr_validate(&user) {
+                Ok(addr) => addr,
+                Err(e) => {
+                    return Ok(UserDepositEntry {
+                        user,
+                        deposit: None,
+                        index: None,
+                        first_deposit_time: None,
+                        error: Some(e.to_string()),
+                    })
+                }
+            };
+            
+            let deposit = USERS.may_load(deps.storage, &addr)?.unwrap_or(Uint128::zero());
+            let index = USER_INDEX_REVERSE.may_load(deps.storage, &addr)?;
+            // Sequence 0 is always the user's first deposit
+            let first_deposit_time = USER_HISTORY
+                .may_load(deps.storage, (&addr, 0))?
+                .map(|entry| entry.time);
+            
+            Ok(UserDepositEntry {
+                user,
+                deposit: Some(deposit),
+                index,
+                first_deposit_time,
+                error: None,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    Ok(GetUserDepositsResponse { deposits })
+}
+
+/// Query all users with pagination support
+/// 
+/// Returns a paginated list of users and their deposits. Uses index-based storage
+/// for efficient enumeration without loading the entire user list.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `start_after` - Optional user address to start pagination after
+/// * `limit` - Optional limit on number of results (default: 30, max: 100)
+/// 
+/// # Returns
+/// * `GetAllUsersResponse` containing users list and optional next cursor
+pub fn query_all_users(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<GetAllUsersResponse, ContractError> {
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let user_count_u32 = USER_COUNT.load(deps.storage)?;
+    let user_count = user_count_u32 as usize;
+    
+    // Determine starting index
+    let start_idx = if let Some(start_after) = start_after {
+        // Find the index of the start_after user
+        // Return explicit error if start_after user is not found in index
+        // 
+        // PAGINATION EDGE CASE: If a user withdraws all funds between pagination calls,
+        // they will be removed from the index. If that user was used as a cursor (start_after),
+        // the next pagination query will fail with StartAfterUserNotFound error.
+        // Frontend should handle this gracefully by restarting pagination from the beginning
+        // if this error is encountered, or use GetAllUsersByAddress which has no such edge case.
+        let idx = USER_INDEX_REVERSE
+            .may_load(deps.storage, &start_after)?
+            .ok_or(ContractError::StartAfterUserNotFound {})?;
+        (idx as usize) + 1
+    } else {
+        0
+    };
+    
+    let mut users = Vec::new();
+    
+    // Iterate through user indices starting from start_idx
+    for idx in start_idx..user_count {
+        // Get user at this index
+        // Convert usize to u32, error if conversion fails (shouldn't happen in practice)
+        let idx_u32 = u32::try_from(idx)
+            .map_err(|_| ContractError::IndexConversionFailed {})?;
+        if let Some(user) = USER_INDEX.may_load(deps.storage, idx_u32)? {
+            // Only include users with non-zero balance
+            if let Some(deposit) = USERS.may_load(deps.storage, &user)? {
+                if !deposit.is_zero() {
+                    users.push((user.clone(), deposit));
+                    
+                    // If we've reached the limit, determine if there are more users
+                    if users.len() >= limit {
+                        // Check if there are more indices to process
+                        // We don't need to look ahead through all remaining users - just check if there are more indices
+                        let has_more = (idx + 1) < user_count;
+                        
+                        if has_more {
+                            // next cursor is the last user we included (for cursor-based pagination)
+                            let last_user = users.last().map(|(user, _)| user.clone());
+                            if let Some(next_cursor) = last_user {
+                                return Ok(GetAllUsersResponse {
+                                    users,
+                                    next: Some(next_cursor),
+                                });
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    
+    // No more users to paginate, so next is None
+    Ok(GetAllUsersResponse { users, next: None })
+}
+
+/// Query all users ordered by address, with pagination support
+/// 
+/// Ranges over USERS by address using an exclusive bound on the cursor. Unlike
+/// `query_all_users`, the cursor does not have to exist in storage, and the order does
+/// not depend on the swap-and-remove index, so a full scan returns every user that
+/// held a deposit throughout the scan exactly once even if others withdraw in between.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `start_after` - Optional address to start pagination after
+/// * `limit` - Optional limit on number of results (default: 30, max: 100)
+/// 
+/// # Returns
+/// * `GetAllUsersResponse` containing users list and optional next cursor
+pub fn query_all_users_by_address(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<GetAllUsersResponse> {
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let min = start_after.as_ref().map(Bound::exclusive);
+    
+    // Fetch one extra entry to know whether another page exists
+    let mut users = USERS
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    let next = if users.len() > limit {
+        users.truncate(limit);
+        users.last().map(|(user, _)| user.clone())
+    } else {
+        None
+    };
+    
+    Ok(GetAllUsersResponse { users, next })
+}
+
+/// Query the total number of unique users with non-zero balances
+/// 
+/// Uses the stored user_count which is efficiently maintained during deposits/withdrawals.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// 
+/// # Returns
+/// * `GetUserCountResponse` containing the user count
+pub fn query_user_count(deps: Deps) -> StdResult<GetUserCountResponse> {
+    let count = USER_COUNT.load(deps.storage)?;
+    Ok(GetUserCountResponse { count })
+}
+
+/// Query the total amount of USTC deposited across all users
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// 
+/// # Returns
+/// * `GetTotalDepositsResponse` containing the total deposit amount
+pub fn query_total_deposits(deps: Deps) -> StdResult<GetTotalDepositsResponse> {
+    let total = TOTAL_DEPOSITS.load(deps.storage)?;
+    Ok(GetTotalDepositsResponse { total })
+}
+
+/// Query the contract configuration
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// 
+/// # Returns
+/// * `GetConfigResponse` containing owner address and USTC denomination
+pub fn query_config(deps: Deps) -> StdResult<GetConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(GetConfigResponse {
+        owner: config.owner,
+        ustc_denom: config.ustc_denom,
+        decimals: config.decimals,
+        paused: PAUSED.load(deps.storage)?,
+    })
+}
+
+/// Validate index consistency
+/// 
+/// Performs comprehensive validation of the index storage system, one page at a time,
+/// in three passes:
+/// 1. Index: verifies all indices are valid and point to users with non-zero balances,
+///    validates reverse index matches forward index and checks for gaps in indices
+/// 2. Reverse: checks every reverse index entry is matched by the forward index
+/// 3. Users: checks every user with a balance is indexed and sums all balances
+/// 
+/// Once the last pass finishes, checks that user_count matches the actual number of
+/// users in index and that the sum of balances matches total deposits.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `start` - Cursor returned by the previous page, or None to start from the beginning
+/// * `limit` - Maximum number of entries to check (default: 100, max: 500)
+/// 
+/// # Returns
+/// * `ValidateIndexResponse` containing the issues found in this page and the next cursor
+pub fn query_validate_index(
+    deps: Deps,
+    start: Option<ValidateIndexCursor>,
+    limit: Option<u32>,
+) -> StdResult<ValidateIndexResponse> {
+    let user_count_stored = USER_COUNT.load(deps.storage)?;
+    let total_deposits_stored = TOTAL_DEPOSITS.load(deps.storage)?;
+    let mut issues = Vec::new();
+    
+    let mut cursor = start.unwrap_or(ValidateIndexCursor {
+        phase: ValidatePhase::Index,
+        after_position: None,
+        after_address: None,
+        total_users_in_index: 0,
+        user_count_actual: 0,
+        total_deposits_actual: Uint128::zero(),
+    });
+    // The cursor comes from the caller, its address must be valid before it bounds a range
+    if let Some(address) = &cursor.after_address {
+        deps.api.addr_validate(address)?;
+    }
+    
+    let mut budget = limit.unwrap_or(DEFAULT_VALIDATE_LIMIT).min(MAX_VALIDATE_LIMIT) as usize;
+    let mut complete = false;
+    
+    while budget > 0 && !complete {
+        let (checked, phase_done) = match cursor.phase {
+            ValidatePhase::Index => validate_index_phase(deps, &mut cursor, user_count_stored, budget, &mut issues)?,
+            ValidatePhase::Reverse => validate_reverse_phase(deps, &mut cursor, budget, &mut issues)?,
+            ValidatePhase::Users => validate_users_phase(deps, &mut cursor, budget, &mut issues)?,
+        };
+        budget -= checked;
+        
+        if phase_done {
+            match cursor.phase {
+                ValidatePhase::Index => cursor.phase = ValidatePhase::Reverse,
+                ValidatePhase::Reverse => cursor.phase = ValidatePhase::Users,
+                ValidatePhase::Users => complete = true,
+            }
+            cursor.after_position = None;
+            cursor.after_address = None;
+        }
+    }
+    
+    if complete {
+        // Check stored count vs actual count
+        if user_count_stored != cursor.user_count_actual {
+            issues.push(format!(
+                "User count mismatch: stored count is {}, actual users with non-zero balance is {}",
+                user_count_stored, cursor.user_count_actual
+            ));
+        }
+        
+        // Check stored total vs sum of balances
+        if total_deposits_stored != cursor.total_deposits_actual {
+            issues.push(format!(
+                "Total deposits mismatch: stored total is {}, sum of user balances is {}",
+                total_deposits_stored, cursor.total_deposits_actual
+            ));
+        }
+    }
+    
+    let is_consistent = issues.is_empty();
+    
+    Ok(ValidateIndexResponse {
+        is_consistent,
+        issues,
+        user_count_stored,
+        user_count_actual: cursor.user_count_actual,
+        total_users_in_index: cursor.total_users_in_index,
+        total_deposits_stored,
+        total_deposits_actual: cursor.total_deposits_actual,
+        next_start: if complete { None } else { Some(cursor) },
+        complete,
+    })
+}
+
+/// Reports missing positions `from..to` (exclusive) as a gap issue
+fn push_gap(issues: &mut Vec<String>, from: u32, to: u32) {
+    if to == from + 1 {
+        issues.push(format!("Gap detected: index {} is missing", from));
+    } else if to > from {
+        issues.push(format!("Gap detected: indices {} to {} are missing", from, to - 1));
+    }
+}
+
+/// Index pass of `query_validate_index`
+/// 
+/// Returns the number of entries checked and whether the pass is finished.
+fn validate_index_phase(
+    deps: Deps,
+    cursor: &mut ValidateIndexCursor,
+    user_count_stored: u32,
+    budget: usize,
+    issues: &mut Vec<String>,
+) -> StdResult<(usize, bool)> {
+    let min = cursor.after_position.map(Bound::exclusive);
+    let entries = USER_INDEX
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(budget)
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    for (idx, user) in &entries {
+        // Positions skipped since the previous entry are gaps if they are below the count
+        let expected = cursor.after_position.map_or(0, |position| position + 1);
+        push_gap(issues, expected, (*idx).min(user_count_stored));
+        
+        cursor.total_users_in_index += 1;
+        if *idx >= user_count_stored {
+            issues.push(format!(
+                "User {} at index {} is beyond the stored user count {}",
+                user, idx, user_count_stored
+            ));
+        }
+        
+        // Check if user exists in USERS map
+        if let Some(deposit) = USERS.may_load(deps.storage, user)? {
+            if !deposit.is_zero() {
+                cursor.user_count_actual += 1;
+            } else {
+                issues.push(format!("User {} at index {} has zero balance but is in index", user, idx));
+            }
+        } else {
+            issues.push(format!("User {} at index {} not found in USERS map", user, idx));
+        }
+        
+        // Check reverse index consistency
+        if let Some(reverse_idx) = USER_INDEX_REVERSE.may_load(deps.storage, user)? {
+            if reverse_idx != *idx {
+                issues.push(format!("Reverse index mismatch: user {} has index {} in forward, {} in reverse", user, idx, reverse_idx));
+            }
+        } else {
+            issues.push(format!("User {} at index {} missing in reverse index", user, idx));
+        }
+        
+        cursor.after_position = Some(*idx);
+    }
+    
+    let phase_done = entries.len() < budget;
+    if phase_done {
+        // Positions after the last entry up to the count are gaps
+        let expected = cursor.after_position.map_or(0, |position| position + 1);
+        push_gap(issues, expected, user_count_stored);
+    }
+    
+    Ok((entries.len(), phase_done))
+}
+
+/// Reverse pass of `query_validate_index`
+/// 
+/// Returns the number of entries checked and whether the pass is finished.
+fn validate_reverse_phase(
+    deps: Deps,
+    cursor: &mut ValidateIndexCursor,
+    budget: usize,
+    issues: &mut Vec<String>,
+) -> StdResult<(usize, bool)> {
+    let after = cursor.after_address.as_deref().map(Addr::unchecked);
+    let min = after.as_ref().map(Bound::exclusive);
+    let entries = USER_INDEX_REVERSE
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(budget)
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    for (user, idx) in &entries {
+        match USER_INDEX.may_load(deps.storage, *idx)? {
+            Some(indexed) if indexed == *user => {}
+            Some(indexed) => issues.push(format!(
+                "Orphaned reverse index entry: user {} points to index {} which holds user {}",
+                user, idx, indexed
+            )),
+            None => issues.push(format!(
+                "Orphaned reverse index entry: user {} points to missing index {}",
+                user, idx
+            )),
+        }
+        cursor.after_address = Some(user.to_string());
+    }
+    
+    Ok((entries.len(), entries.len() < budget))
+}
+
+/// Users pass of `query_validate_index`
+/// 
+/// Returns the number of entries checked and whether the pass is finished.
+fn validate_users_phase(
+    deps: Deps,
+    cursor: &mut ValidateIndexCursor,
+    budget: usize,
+    issues: &mut Vec<String>,
+) -> StdResult<(usize, bool)> {
+    let after = cursor.after_address.as_deref().map(Addr::unchecked);
+    let min = after.as_ref().map(Bound::exclusive);
+    let users = USERS
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(budget)
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    for (user, deposit) in &users {
+        cursor.total_deposits_actual = cursor.total_deposits_actual.checked_add(*deposit)?;
+        
+        if deposit.is_zero() {
+            issues.push(format!("User {} has zero balance but remains in USERS map", user));
+        } else {
+            let indexed = match USER_INDEX_REVERSE.may_load(deps.storage, user)? {
+                Some(idx) => USER_INDEX.may_load(deps.storage, idx)?.as_ref() == Some(user),
+                None => false,
+            };
+            if !indexed {
+                issues.push(format!("User {} with balance {} is missing from the index", user, deposit));
+            }
+        }
+        cursor.after_address = Some(user.to_string());
+    }
+    
+    Ok((users.len(), users.len() < budget))
+}
+
+/// Query withdrawal information
+/// 
+/// Returns the withdrawal destination address, unlock timestamp, and whether
+/// withdrawal is configured (both destination and timestamp are set).
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// 
+/// # Returns
+/// * `GetWithdrawalInfoResponse` containing withdrawal configuration
+pub fn query_withdrawal_info(deps: Deps, env: Env) -> StdResult<GetWithdrawalInfoResponse> {
+    let effective = effective_destination(deps.storage, env.block.time.seconds())?;
+    let is_configured = effective.destination.is_some() && effective.unlock_timestamp != 0;
+    
+    Ok(GetWithdrawalInfoResponse {
+        destination: effective.destination,
+        shares: effective.shares,
+        unlock_timestamp: effective.unlock_timestamp,
+        is_configured,
+    })
+}
+
+/// Query the current and pending withdrawal destination
+/// 
+/// A pending change whose delay has elapsed is reported as current, since the next
+/// sweep applies it.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
 /// 
 /// # Returns
-/// * `GetUserDepositResponse` containing user address and deposit amount
-pub fn query_user_deposit(
-    deps: Deps,
-    user: cosmwasm_std::Addr,
-) -> StdResult<GetUserDepositResponse> {
-    let deposit = USERS.may_load(deps.storage, &user)?.unwrap_or(Uint128::zero());
-    Ok(GetUserDepositResponse {
-        user,
-        deposit,
+/// * `GetDestinationScheduleResponse` with current and pending destination
+pub fn query_destination_schedule(deps: Deps, env: Env) -> StdResult<GetDestinationScheduleResponse> {
+    let effective = effective_destination(deps.storage, env.block.time.seconds())?;
+    
+    Ok(GetDestinationScheduleResponse {
+        current: effective.destination,
+        current_shares: effective.shares,
+        current_unlock_timestamp: effective.unlock_timestamp,
+        pending: effective.pending,
+        change_delay: DESTINATION_CHANGE_DELAY,
     })
 }
 
-/// Query all users with pagination support
+/// Query depositors ordered by balance, largest first
 /// 
-/// Returns a paginated list of users and their deposits. Uses index-based storage
-/// for efficient enumeration without loading the entire user list.
+/// Ranges the `(deposit, addr)` secondary index of USERS in descending order, so the
+/// cost of a page only depends on `limit`. The cursor is the last user of the previous
+/// page; if that user's balance changed in between, pagination continues from their
+/// new position in the ranking.
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
@@ -390,205 +2692,506 @@ pub fn query_user_deposit(
 /// * `limit` - Optional limit on number of results (default: 30, max: 100)
 /// 
 /// # Returns
-/// * `GetAllUsersResponse` containing users list and optional next cursor
-pub fn query_all_users(
+/// * `GetTopDepositorsResponse` containing depositors and optional next cursor
+pub fn query_top_depositors(
     deps: Deps,
-    start_after: Option<cosmwasm_std::Addr>,
+    start_after: Option<String>,
     limit: Option<u32>,
-) -> Result<GetAllUsersResponse, ContractError> {
+) -> Result<GetTopDepositorsResponse, ContractError> {
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
     let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
-    let user_count_u32 = USER_COUNT.load(deps.storage)?;
-    let user_count = user_count_u32 as usize;
     
-    // Determine starting index
-    let start_idx = if let Some(start_after) = start_after {
-        // Find the index of the start_after user
-        // Return explicit error if start_after user is not found in index
-        // 
-        // PAGINATION EDGE CASE: If a user withdraws all funds between pagination calls,
-        // they will be removed from the index. If that user was used as a cursor (start_after),
-        // the next pagination query will fail with StartAfterUserNotFound error.
-        // Frontend should handle this gracefully by restarting pagination from the beginning
-        // if this error is encountered.
-        let idx = USER_INDEX_REVERSE
-            .may_load(deps.storage, &start_after)?
-            .ok_or(ContractError::StartAfterUserNotFound {})?;
-        (idx as usize) + 1
+    // Descending order, so the cursor is the exclusive upper bound
+    let max = match start_after {
+        Some(start_after) => {
+            let deposit = USERS
+                .may_load(deps.storage, &start_after)?
+                .ok_or(ContractError::StartAfterUserNotFound {})?;
+            Some(Bound::exclusive((deposit.u128(), start_after)))
+        }
+        None => None,
+    };
+    
+    // Fetch one extra entry to know whether another page exists
+    let mut depositors = USERS
+        .idx
+        .deposit
+        .range(deps.storage, None, max, Order::Descending)
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    let next = if depositors.len() > limit {
+        depositors.truncate(limit);
+        depositors.last().map(|(user, _)| user.clone())
     } else {
-        0
+        None
     };
     
-    let mut users = Vec::new();
+    Ok(GetTopDepositorsResponse { depositors, next })
+}
+
+/// Query a user's rank by deposit
+/// 
+/// The rank is the number of depositors ordered before the user in GetTopDepositors
+/// plus one. Counting walks the index entries above the user, so it is capped at
+/// MAX_RANK entries; users ranked lower get `rank: None` and `beyond_max_rank: true`.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `user` - Address of the user to rank
+/// 
+/// # Returns
+/// * `GetUserRankResponse` containing the user's deposit and rank
+pub fn query_user_rank(
+    deps: Deps,
+    user: String,
+) -> StdResult<GetUserRankResponse> {
+    let user = deps.api.addr_validate(&user)?;
+    let user_count = USER_COUNT.load(deps.storage)?;
+    let deposit = USERS.may_load(deps.storage, &user)?.unwrap_or(Uint128::zero());
     
-    // Iterate through user indices starting from start_idx
-    for idx in start_idx..user_count {
-        // Get user at this index
-        // Convert usize to u32, error if conversion fails (shouldn't happen in practice)
-        let idx_u32 = u32::try_from(idx)
-            .map_err(|_| ContractError::IndexConversionFailed {})?;
-        if let Some(user) = USER_INDEX.may_load(deps.storage, idx_u32)? {
-            // Only include users with non-zero balance
-            if let Some(deposit) = USERS.may_load(deps.storage, &user)? {
-                if !deposit.is_zero() {
-                    users.push((user.clone(), deposit));
-                    
-                    // If we've reached the limit, determine if there are more users
-                    if users.len() >= limit {
-                        // Check if there are more indices to process
-                        // We don't need to look ahead through all remaining users - just check if there are more indices
-                        let has_more = (idx + 1) < user_count;
-                        
-                        if has_more {
-                            // next cursor is the last user we included (for cursor-based pagination)
-                            let last_user = users.last().map(|(user, _)| user.clone());
-                            if let Some(next_cursor) = last_user {
-                                return Ok(GetAllUsersResponse {
-                                    users,
-                                    next: Some(next_cursor),
-                                });
-                            }
-                        }
-                        break;
-                    }
-                }
-            }
-        }
+    if deposit.is_zero() {
+        return Ok(GetUserRankResponse {
+            user,
+            deposit,
+            rank: None,
+            beyond_max_rank: false,
+            user_count,
+        });
     }
     
-    // No more users to paginate, so next is None
-    Ok(GetAllUsersResponse { users, next: None })
+    let ahead = USERS
+        .idx
+        .deposit
+        .keys_raw(
+            deps.storage,
+            Some(Bound::exclusive((deposit.u128(), user.clone()))),
+            None,
+            Order::Ascending,
+        )
+        .take(MAX_RANK as usize)
+        .count() as u32;
+    let beyond_max_rank = ahead >= MAX_RANK;
+    let rank = if beyond_max_rank { None } else { Some(ahead + 1) };
+    
+    Ok(GetUserRankResponse {
+        user,
+        deposit,
+        rank,
+        beyond_max_rank,
+        user_count,
+    })
 }
 
-/// Query the total number of unique users with non-zero balances
+/// Query a user's deposit and withdrawal history
 /// 
-/// Uses the stored user_count which is efficiently maintained during deposits/withdrawals.
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `user` - Address of the user to query
+/// * `start_after` - Optional sequence number to start pagination after
+/// * `limit` - Optional limit on number of results (default: 30, max: 100)
+/// * `order` - Optional ordering by sequence (default: ascending, oldest first)
+/// 
+/// # Returns
+/// * `GetUserHistoryResponse` containing history entries and optional next cursor
+pub fn query_user_history(
+    deps: Deps,
+    user: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<GetUserHistoryResponse> {
+    let user = deps.api.addr_validate(&user)?;
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let order: Order = order.unwrap_or(OrderBy::Asc).into();
+    let (min, max) = match order {
+        Order::Ascending => (start_after.map(Bound::exclusive), None),
+        Order::Descending => (None, start_after.map(Bound::exclusive)),
+    };
+    
+    // Fetch one extra entry to know whether another page exists
+    let mut entries = USER_HISTORY
+        .prefix(&user)
+        .range(deps.storage, min, max, order)
+        .take(limit + 1)
+        .map(|item| item.map(|(_, entry)| entry))
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    let next = if entries.len() > limit {
+        entries.truncate(limit);
+        entries.last().map(|entry| entry.sequence)
+    } else {
+        None
+    };
+    
+    Ok(GetUserHistoryResponse {
+        user,
+        entries,
+        next,
+    })
+}
+
+/// Query the global activity ledger in sequence order
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
+/// * `start_after_seq` - Optional sequence number to start after (last sequence already synced)
+/// * `limit` - Optional limit on number of results (default: 30, max: 100)
 /// 
 /// # Returns
-/// * `GetUserCountResponse` containing the user count
-pub fn query_user_count(deps: Deps) -> StdResult<GetUserCountResponse> {
-    let count = USER_COUNT.load(deps.storage)?;
-    Ok(GetUserCountResponse { count })
+/// * `GetActivityResponse` containing activity entries and the latest sequence number
+pub fn query_activity(
+    deps: Deps,
+    start_after_seq: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<GetActivityResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let min = start_after_seq.map(Bound::exclusive);
+    
+    let activities = ACTIVITY
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, activity)| activity))
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    Ok(GetActivityResponse {
+        activities,
+        latest_sequence: SEQUENCE.load(deps.storage)?,
+    })
 }
 
-/// Query the total amount of USTC deposited across all users
+/// Query the sequence number of the latest activity entry
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
 /// 
 /// # Returns
-/// * `GetTotalDepositsResponse` containing the total deposit amount
-pub fn query_total_deposits(deps: Deps) -> StdResult<GetTotalDepositsResponse> {
-    let total = TOTAL_DEPOSITS.load(deps.storage)?;
-    Ok(GetTotalDepositsResponse { total })
+/// * `GetLatestSequenceResponse` containing the latest sequence (0 if no activity yet)
+pub fn query_latest_sequence(deps: Deps) -> StdResult<GetLatestSequenceResponse> {
+    let sequence = SEQUENCE.load(deps.storage)?;
+    Ok(GetLatestSequenceResponse { sequence })
 }
 
-/// Query the contract configuration
+/// Query daily statistics buckets for a range of days
+/// 
+/// Days are numbered as `block time / 86400` (UTC days since the Unix epoch). Only days
+/// with at least one deposit or withdrawal have a bucket; for days without one the
+/// closing total equals the closing total of the previous bucket.
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
+/// * `from_day` - First day to include
+/// * `to_day` - Last day to include
 /// 
 /// # Returns
-/// * `GetConfigResponse` containing owner address and USTC denomination
-pub fn query_config(deps: Deps) -> StdResult<GetConfigResponse> {
-    let config = CONFIG.load(deps.storage)?;
-    Ok(GetConfigResponse {
-        owner: config.owner,
-        ustc_denom: config.ustc_denom,
-    })
+/// * `GetDailyStatsResponse` containing up to 100 buckets in day order, and where to
+///   continue if the range holds more
+pub fn query_daily_stats(
+    deps: Deps,
+    from_day: u64,
+    to_day: u64,
+) -> StdResult<GetDailyStatsResponse> {
+    if from_day > to_day {
+        return Err(cosmwasm_std::StdError::generic_err("from_day must not be after to_day"));
+    }
+    
+    // One extra bucket tells whether the range continues past this page
+    let mut days = DAILY_STATS
+        .range(
+            deps.storage,
+            Some(Bound::inclusive(from_day)),
+            Some(Bound::inclusive(to_day)),
+            Order::Ascending,
+        )
+        .take(MAX_QUERY_LIMIT as usize + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    let next_from_day = if days.len() > MAX_QUERY_LIMIT as usize {
+        days.pop().map(|(day, _)| day)
+    } else {
+        None
+    };
+    
+    Ok(GetDailyStatsResponse { days, next_from_day })
 }
 
-/// Validate index consistency
+/// Query the deposit size distribution
 /// 
-/// Performs comprehensive validation of the index storage system:
-/// - Checks that user_count matches actual number of users in index
-/// - Verifies all indices are valid and point to users with non-zero balances
-/// - Validates reverse index matches forward index
-/// - Checks for gaps in indices
+/// Returns the count and volume of every bracket, each bracket's share of users and of
+/// total volume, and a Gini coefficient computed from the brackets.
+/// 
+/// The Gini coefficient treats every user in a bracket as holding the bracket average,
+/// so it only captures inequality between brackets and is a lower bound of the exact
+/// per-user figure. With n_i users and volume v_i in bracket i, N users and V total:
+/// `G = sum over i<j of |n_j * v_i - n_i * v_j| / (N * V)`.
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
 /// 
 /// # Returns
-/// * `ValidateIndexResponse` containing validation results and any issues found
-pub fn query_validate_index(deps: Deps) -> StdResult<ValidateIndexResponse> {
-    let user_count_stored = USER_COUNT.load(deps.storage)?;
-    let mut issues = Vec::new();
-    let mut users_found = 0u32;
-    let mut total_in_index = 0u32;
+/// * `GetDistributionResponse` containing brackets and concentration figures
+pub fn query_distribution(deps: Deps) -> StdResult<GetDistributionResponse> {
+    let thresholds = DISTRIBUTION_THRESHOLDS.load(deps.storage)?;
     
-    // Iterate through all stored indices
-    for idx in 0..user_count_stored {
-        if let Some(user) = USER_INDEX.may_load(deps.storage, idx)? {
-            total_in_index += 1;
-            
-            // Check if user exists in USERS map
-            if let Some(deposit) = USERS.may_load(deps.storage, &user)? {
-                if !deposit.is_zero() {
-                    users_found += 1;
-                } else {
-                    issues.push(format!("User {} at index {} has zero balance but is in index", user, idx));
-                }
-            } else {
-                issues.push(format!("User {} at index {} not found in USERS map", user, idx));
-            }
-            
-            // Check reverse index consistency
-            if let Some(reverse_idx) = USER_INDEX_REVERSE.may_load(deps.storage, &user)? {
-                if reverse_idx != idx {
-                    issues.push(format!("Reverse index mismatch: user {} has index {} in forward, {} in reverse", user, idx, reverse_idx));
-                }
-            } else {
-                issues.push(format!("User {} at index {} missing in reverse index", user, idx));
-            }
+    let mut stats = Vec::with_capacity(thresholds.len() + 1);
+    for idx in 0..=thresholds.len() as u32 {
+        stats.push(DISTRIBUTION.may_load(deps.storage, idx)?.unwrap_or_default());
+    }
+    
+    let total_users: u32 = stats.iter().map(|bracket| bracket.count).sum();
+    let total_volume = stats
+        .iter()
+        .try_fold(Uint128::zero(), |acc, bracket| acc.checked_add(bracket.volume))?;
+    
+    let share = |part: Uint128, whole: Uint128| {
+        if whole.is_zero() {
+            Decimal::zero()
         } else {
-            issues.push(format!("Gap detected: index {} is missing", idx));
+            Decimal::from_ratio(part, whole)
         }
-    }
+    };
     
-    // Check for users in reverse index that aren't in forward index
-    // Note: We can't easily iterate USER_INDEX_REVERSE, so we rely on the forward check above
+    let brackets = stats
+        .iter()
+        .enumerate()
+        .map(|(idx, bracket)| DistributionBracket {
+            min: if idx == 0 { Uint128::zero() } else { thresholds[idx - 1] },
+            max: thresholds.get(idx).copied(),
+            count: bracket.count,
+            volume: bracket.volume,
+            user_share: share(Uint128::from(bracket.count), Uint128::from(total_users)),
+            volume_share: share(bracket.volume, total_volume),
+        })
+        .collect();
     
-    // Check stored count vs actual count
-    if user_count_stored != users_found {
-        issues.push(format!(
-            "User count mismatch: stored count is {}, actual users with non-zero balance is {}",
-            user_count_stored, users_found
-        ));
+    let mut gini = Decimal::zero();
+    if total_users > 0 && !total_volume.is_zero() {
+        let mut numerator = Uint256::zero();
+        for (i, a) in stats.iter().enumerate() {
+            for b in stats.iter().skip(i + 1) {
+                let lhs = Uint256::from(b.count).checked_mul(a.volume.into())?;
+                let rhs = Uint256::from(a.count).checked_mul(b.volume.into())?;
+                numerator += if lhs > rhs { lhs - rhs } else { rhs - lhs };
+            }
+        }
+        let denominator = Uint256::from(total_users).checked_mul(total_volume.into())?;
+        gini = Decimal::try_from(Decimal256::from_ratio(numerator, denominator))
+            .map_err(|e| cosmwasm_std::StdError::generic_err(e.to_string()))?;
     }
     
-    let is_consistent = issues.is_empty();
+    Ok(GetDistributionResponse {
+        brackets,
+        total_users,
+        total_volume,
+        gini,
+    })
+}
+
+/// Query the holders of every role
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// 
+/// # Returns
+/// * `GetRolesResponse` with one entry per role, in declaration order
+pub fn query_roles(deps: Deps) -> StdResult<GetRolesResponse> {
+    let roles = Role::ALL
+        .into_iter()
+        .map(|role| {
+            let holders = ROLES
+                .prefix(role.as_str())
+                .keys(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            Ok(RoleHolders { role, holders })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
     
-    Ok(ValidateIndexResponse {
-        is_consistent,
-        issues,
-        user_count_stored,
-        user_count_actual: users_found,
-        total_users_in_index: total_in_index,
+    Ok(GetRolesResponse { roles })
+}
+
+/// Query how much the treasurer can sweep now
+/// 
+/// Only the sweep limit, the reserve and the contract balance are taken into account; whether the
+/// destination is set and the unlock timestamp has passed is reported by GetWithdrawalInfo.
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// 
+/// # Returns
+/// * `GetSweepAllowanceResponse` with the available amount and window reset time
+pub fn query_sweep_allowance(deps: Deps, env: Env) -> StdResult<GetSweepAllowanceResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let balance = deps.querier.query_balance(&env.contract.address, &config.ustc_denom)?.amount;
+    let now = env.block.time.seconds();
+    let (_, balance) = sweepable_balance(deps.storage, now, balance)?;
+    
+    let (limit, pending) = effective_sweep_limit(deps.storage, now)?;
+    let (swept_in_window, available, resets_at) = match &limit {
+        Some(limit) => {
+            let window = current_sweep_window(deps.storage, limit, now)?;
+            let remaining = limit.max_amount.saturating_sub(window.swept);
+            (window.swept, balance.min(remaining), window.resets_at)
+        }
+        None => (Uint128::zero(), balance, None),
+    };
+    
+    Ok(GetSweepAllowanceResponse {
+        limit,
+        swept_in_window,
+        available,
+        resets_at,
+        pending,
     })
 }
 
-/// Query withdrawal information
+/// Query the contract liquidity
 /// 
-/// Returns the withdrawal destination address, unlock timestamp, and whether
-/// withdrawal is configured (both destination and timestamp are set).
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment information
+/// 
+/// # Returns
+/// * `GetLiquidityResponse` with the balance, required reserve and sweepable amount
+pub fn query_liquidity(deps: Deps, env: Env) -> StdResult<GetLiquidityResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let balance = deps.querier.query_balance(&env.contract.address, &config.ustc_denom)?.amount;
+    let now = env.block.time.seconds();
+    let (required_reserve, sweepable) = sweepable_balance(deps.storage, now, balance)?;
+    let (reserve_ratio, pending_reserve_ratio) = effective_reserve_ratio(deps.storage, now)?;
+    
+    Ok(GetLiquidityResponse {
+        balance,
+        total_deposits: TOTAL_DEPOSITS.load(deps.storage)?,
+        reserve_ratio,
+        pending_reserve_ratio,
+        required_reserve,
+        queued: WITHDRAW_QUEUE_STATE.load(deps.storage)?.total,
+        sweepable,
+    })
+}
+
+/// Query a user's queued withdrawals
 /// 
 /// # Arguments
 /// * `deps` - Dependencies for storage and API access
+/// * `user` - User address to query
 /// 
 /// # Returns
-/// * `GetWithdrawalInfoResponse` containing withdrawal configuration
-pub fn query_withdrawal_info(deps: Deps) -> StdResult<GetWithdrawalInfoResponse> {
-    let destination = WITHDRAWAL_DESTINATION.load(deps.storage)?;
-    let unlock_timestamp = WITHDRAWAL_UNLOCK_TIMESTAMP.load(deps.storage)?;
-    let is_configured = destination.is_some() && unlock_timestamp != 0;
+/// * `GetQueuePositionResponse` with the user's entries (at most 100), oldest first
+pub fn query_queue_position(deps: Deps, user: String) -> StdResult<GetQueuePositionResponse> {
+    let user = deps.api.addr_validate(&user)?;
+    let queue = WITHDRAW_QUEUE_STATE.load(deps.storage)?;
+    let entries = WITHDRAW_QUEUE
+        .idx
+        .user
+        .prefix(user.clone())
+        .range(deps.storage, None, None, Order::Ascending)
+        .take(MAX_QUERY_LIMIT as usize)
+        .map(|item| {
+            item.map(|(id, entry)| QueuePosition {
+                entry,
+                position: id - queue.head,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
     
-    Ok(GetWithdrawalInfoResponse {
-        destination,
-        unlock_timestamp,
-        is_configured,
+    Ok(GetQueuePositionResponse {
+        user,
+        entries,
+        total_queued: queue.total,
+    })
+}
+
+/// Query the withdrawal queue in request order
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `start_after` - Optional queue id to start pagination after
+/// * `limit` - Optional limit on number of results (default: 30, max: 100)
+/// 
+/// # Returns
+/// * `GetWithdrawQueueResponse` with queued entries and pagination cursor
+pub fn query_withdraw_queue(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<GetWithdrawQueueResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let queue = WITHDRAW_QUEUE_STATE.load(deps.storage)?;
+    
+    // Fetch one extra entry to know whether another page exists
+    let mut entries = WITHDRAW_QUEUE
+        .range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| {
+            item.map(|(id, entry)| QueuePosition {
+                entry,
+                position: id - queue.head,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    
+    let next = if entries.len() > limit {
+        entries.truncate(limit);
+        entries.last().map(|position| position.entry.id)
+    } else {
+        None
+    };
+    
+    Ok(GetWithdrawQueueResponse {
+        entries,
+        length: queue.next - queue.head,
+        total_queued: queue.total,
+        next,
+    })
+}
+
+/// Query the campaign cancellation and refund progress
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// 
+/// # Returns
+/// * `GetRefundProgressResponse` with the cancellation snapshot and remaining users and deposits
+pub fn query_refund_progress(deps: Deps) -> StdResult<GetRefundProgressResponse> {
+    Ok(GetRefundProgressResponse {
+        cancellation: CAMPAIGN_CANCELLATION.may_load(deps.storage)?,
+        remaining_users: USER_COUNT.load(deps.storage)?,
+        remaining_deposits: TOTAL_DEPOSITS.load(deps.storage)?,
     })
 }
 
+/// Query the campaign status against its soft cap and deadline
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// * `env` - Contract environment, the deadline is checked against block time
+/// 
+/// # Returns
+/// * `GetCampaignStatusResponse` with the status, target and shortfall
+pub fn query_campaign_status(deps: Deps, env: Env) -> StdResult<GetCampaignStatusResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let total_deposits = TOTAL_DEPOSITS.load(deps.storage)?;
+    Ok(GetCampaignStatusResponse {
+        status: campaign_status(deps.storage, env.block.time.seconds())?,
+        soft_cap: config.soft_cap,
+        deadline: config.deadline,
+        total_deposits,
+        shortfall: config.soft_cap.unwrap_or_default().saturating_sub(total_deposits),
+    })
+}
+
+/// Query the contracts notified of balance changes
+/// 
+/// # Arguments
+/// * `deps` - Dependencies for storage and API access
+/// 
+/// # Returns
+/// * `ListHooksResponse` with every hook, in address order (at most 10)
+pub fn query_list_hooks(deps: Deps) -> StdResult<ListHooksResponse> {
+    let hooks = HOOKS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(ListHooksResponse { hooks })
+}
@@ -1,6 +1,8 @@
 use cosmwasm_std::StdError;
 use thiserror::Error;
 
+use crate::state::Role;
+
 #[derive(Error, Debug)]
 pub enum ContractError {
     #[error("{0}")]
@@ -18,6 +20,48 @@ pub enum ContractError {
     #[error("Invalid denomination: Expected {expected}, got {got}")]
     InvalidDenom { expected: String, got: String },
     
+    #[error("Invalid denomination {denom}: must be 3 to 128 characters, start with a letter and contain only letters, digits and /:._-")]
+    InvalidDenomName { denom: String },
+    
+    #[error("Invalid decimals: must be at most {max}")]
+    InvalidDecimals { max: u8 },
+    
+    #[error("Denomination can only be changed while there are no deposits or queued withdrawals")]
+    DenomLocked {},
+    
+    #[error("Invalid owner address: {address}")]
+    InvalidOwnerAddress { address: String },
+    
+    #[error("Invalid withdrawal destination address: {address}")]
+    InvalidDestinationAddress { address: String },
+    
+    #[error("Invalid role holder address: {address}")]
+    InvalidRoleAddress { address: String },
+    
+    #[error("Invalid recipient address: {address}")]
+    InvalidRecipientAddress { address: String },
+    
+    #[error("Invalid CW20 token address: {address}")]
+    InvalidTokenAddress { address: String },
+    
+    #[error("Invalid hook address: {address}")]
+    InvalidHookAddress { address: String },
+    
+    #[error("Hook {hook} is already registered")]
+    HookAlreadyRegistered { hook: String },
+    
+    #[error("Hook {hook} is not registered")]
+    HookNotRegistered { hook: String },
+    
+    #[error("Too many hooks (max {max})")]
+    TooManyHooks { max: u32 },
+    
+    #[error("Unexpected reply (id {id})")]
+    UnexpectedReply { id: u64 },
+    
+    #[error("This message does not accept funds")]
+    NonPayable {},
+    
     #[error("User not found")]
     UserNotFound {},
     
@@ -44,5 +88,91 @@ pub enum ContractError {
     
     #[error("Invalid timestamp: must be at least 7 days in the future")]
     InvalidTimestamp {},
+    
+    #[error("Invalid distribution thresholds: must be non-zero, strictly ascending and at most {max}")]
+    InvalidDistributionThresholds { max: u32 },
+    
+    #[error("Distribution brackets can only be changed while there are no depositors")]
+    DistributionBracketsLocked {},
+    
+    #[error("Index repair in progress: deposits are paused until it completes")]
+    RepairInProgress {},
+    
+    #[error("Repair cursor does not match the repair in progress")]
+    RepairCursorMismatch {},
+    
+    #[error("Unauthorized: Caller does not hold the {role} role")]
+    MissingRole { role: Role },
+    
+    #[error("Cannot revoke the last admin")]
+    CannotRevokeLastAdmin {},
+    
+    #[error("Deposits are paused")]
+    Paused {},
+    
+    #[error("No pending withdrawal destination change")]
+    NoPendingDestination {},
+    
+    #[error("Invalid destination shares: must be non-zero, unique, include the destination and sum to {total} bps")]
+    InvalidDestinationShares { total: u16 },
+    
+    #[error("Invalid sweep limit: amount and window must be greater than zero")]
+    InvalidSweepLimit {},
+    
+    #[error("Sweep limit exceeded: {remaining} left in the window until {resets_at}")]
+    SweepLimitExceeded { remaining: cosmwasm_std::Uint128, resets_at: u64 },
+    
+    #[error("Invalid reserve ratio: must be at most 1")]
+    InvalidReserveRatio {},
+    
+    #[error("Campaign is cancelled: deposits and sweeps are disabled")]
+    CampaignCancelled {},
+    
+    #[error("Refunds require a cancelled or failed campaign")]
+    CampaignNotCancelled {},
+    
+    #[error("Invalid campaign target: soft cap and deadline must be set together, with a non-zero cap and a future deadline")]
+    InvalidCampaignTarget {},
+    
+    #[error("Campaign deadline has passed: deposits are closed")]
+    CampaignEnded {},
+    
+    #[error("Campaign missed its soft cap: sweeps are disabled")]
+    CampaignFailed {},
+    
+    #[error("Campaign is still running: sweeps open once it reaches its soft cap at the deadline")]
+    CampaignInProgress {},
+    
+    #[error("Cannot recover {token}: it is a tracked asset of the contract")]
+    CannotRecoverTrackedAsset { token: String },
+    
+    #[error("Nothing to recover: the contract holds no {token}")]
+    NothingToRecover { token: String },
+    
+    #[error("Insufficient contract balance: Requested {requested}, available {available}")]
+    InsufficientContractBalance { requested: cosmwasm_std::Uint128, available: cosmwasm_std::Uint128 },
 }
 
+
+/// Errors decoding contract events from a transaction
+#[cfg(any(test, feature = "decoder"))]
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum EventDecodeError {
+    #[error("Invalid tx JSON: {msg}")]
+    InvalidJson { msg: String },
+    
+    #[error("Unknown event type {event_type}")]
+    UnknownEvent { event_type: String },
+    
+    #[error("Unsupported schema version {version} for event {event_type}")]
+    UnsupportedSchemaVersion { event_type: String, version: u32 },
+    
+    #[error("Missing attribute {key} in event {event_type}")]
+    MissingAttribute { event_type: String, key: String },
+    
+    #[error("Invalid value {value} for attribute {key} in event {event_type}")]
+    InvalidAttribute { event_type: String, key: String, value: String },
+    
+    #[error("Legacy {action} action found without a contract address to attribute it to")]
+    UnattributedLegacyEvent { action: String },
+}
@@ -0,0 +1,830 @@
+use cosmwasm_std::{Addr, Decimal, Event, Uint128};
+#[cfg(any(test, feature = "decoder"))]
+use cosmwasm_std::{from_json, Attribute};
+#[cfg(any(test, feature = "decoder"))]
+use serde::Deserialize;
+
+#[cfg(any(test, feature = "decoder"))]
+use crate::error::EventDecodeError;
+use crate::state::{DestinationShare, Role, SweepLimit};
+
+/// Prefix of every custom event type emitted by the contract
+///
+/// The chain prepends `wasm-` when the event is included in a transaction,
+/// e.g. `ustc_preregister.deposit` is indexed as `wasm-ustc_preregister.deposit`.
+pub const EVENT_PREFIX: &str = "ustc_preregister";
+
+/// Version of the event attribute schema, emitted as the `schema_version` attribute
+///
+/// Bumped whenever an attribute is renamed, removed or changes meaning.
+/// Adding an attribute does not require a bump.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// Prefix the chain adds to custom contract event types
+#[cfg(any(test, feature = "decoder"))]
+const WASM_EVENT_PREFIX: &str = "wasm-";
+
+/// Type of the event holding the flat `action` attributes of every contract call
+#[cfg(any(test, feature = "decoder"))]
+const LEGACY_EVENT_TYPE: &str = "wasm";
+
+/// Typed state change event emitted by the contract
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContractEvent {
+    Instantiate {
+        owner: Addr,
+        ustc_denom: String,
+        /// None if the campaign has no target
+        soft_cap: Option<Uint128>,
+        deadline: Option<u64>,
+    },
+    Deposit {
+        user: Addr,
+        amount: Uint128,
+        /// User balance after the deposit
+        new_balance: Uint128,
+        /// Total deposits after the deposit
+        total_deposits: Uint128,
+        /// True if the user had no balance before and was added to the index
+        new_user: bool,
+    },
+    Withdraw {
+        user: Addr,
+        amount: Uint128,
+        /// User balance after the withdrawal
+        new_balance: Uint128,
+        /// Total deposits after the withdrawal
+        total_deposits: Uint128,
+        /// True if the user withdrew everything and was removed from the index
+        user_removed: bool,
+    },
+    OwnerWithdraw {
+        destination: Addr,
+        amount: Uint128,
+    },
+    ConfigUpdate {
+        owner: Addr,
+    },
+    WithdrawalDestinationSet {
+        destination: Addr,
+        /// Split of each sweep, empty if everything goes to `destination`
+        shares: Vec<DestinationShare>,
+        unlock_timestamp: u64,
+    },
+    WithdrawalDestinationProposed {
+        destination: Addr,
+        /// Split of each sweep, empty if everything goes to `destination`
+        shares: Vec<DestinationShare>,
+        unlock_timestamp: u64,
+        /// Time from which the new destination takes effect
+        effective_at: u64,
+    },
+    PendingDestinationCancelled {
+        destination: Addr,
+    },
+    DistributionBracketsSet {
+        thresholds: Vec<Uint128>,
+    },
+    IndexRepaired {
+        complete: bool,
+        corrections: u32,
+    },
+    RoleGranted {
+        role: Role,
+        address: Addr,
+    },
+    RoleRevoked {
+        role: Role,
+        address: Addr,
+    },
+    PausedSet {
+        paused: bool,
+    },
+    SweepLimitSet {
+        /// None if the limit was removed
+        limit: Option<SweepLimit>,
+    },
+    SweepLimitProposed {
+        /// None if the limit is to be removed
+        limit: Option<SweepLimit>,
+        /// Time from which the new limit takes effect
+        effective_at: u64,
+    },
+    ReserveRatioSet {
+        ratio: Decimal,
+    },
+    ReserveRatioProposed {
+        ratio: Decimal,
+        /// Time from which the new ratio takes effect
+        effective_at: u64,
+    },
+    WithdrawalQueued {
+        id: u64,
+        user: Addr,
+        amount: Uint128,
+    },
+    QueuedWithdrawalPaid {
+        id: u64,
+        user: Addr,
+        amount: Uint128,
+    },
+    FundsReturned {
+        sender: Addr,
+        amount: Uint128,
+    },
+    CampaignCancelled {
+        user_count: u32,
+        total_deposits: Uint128,
+    },
+    Refund {
+        user: Addr,
+        amount: Uint128,
+    },
+    CampaignSettled {
+        succeeded: bool,
+        total_deposits: Uint128,
+    },
+    TokensRecovered {
+        /// Native denom or CW20 contract address
+        token: String,
+        recipient: Addr,
+        amount: Uint128,
+    },
+    DenomSet {
+        denom: String,
+        decimals: u8,
+    },
+    HookAdded {
+        hook: Addr,
+    },
+    HookRemoved {
+        hook: Addr,
+    },
+    HookFailed {
+        hook: Addr,
+        error: String,
+    },
+}
+
+impl ContractEvent {
+    /// Event name without the contract prefix, e.g. `deposit`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContractEvent::Instantiate { .. } => "instantiate",
+            ContractEvent::Deposit { .. } => "deposit",
+            ContractEvent::Withdraw { .. } => "withdraw",
+            ContractEvent::OwnerWithdraw { .. } => "owner_withdraw",
+            ContractEvent::ConfigUpdate { .. } => "config_update",
+            ContractEvent::WithdrawalDestinationSet { .. } => "withdrawal_destination_set",
+            ContractEvent::WithdrawalDestinationProposed { .. } => "withdrawal_destination_proposed",
+            ContractEvent::PendingDestinationCancelled { .. } => "pending_destination_cancelled",
+            ContractEvent::DistributionBracketsSet { .. } => "distribution_brackets_set",
+            ContractEvent::IndexRepaired { .. } => "index_repaired",
+            ContractEvent::RoleGranted { .. } => "role_granted",
+            ContractEvent::RoleRevoked { .. } => "role_revoked",
+            ContractEvent::PausedSet { .. } => "paused_set",
+            ContractEvent::SweepLimitSet { .. } => "sweep_limit_set",
+            ContractEvent::SweepLimitProposed { .. } => "sweep_limit_proposed",
+            ContractEvent::ReserveRatioSet { .. } => "reserve_ratio_set",
+            ContractEvent::ReserveRatioProposed { .. } => "reserve_ratio_proposed",
+            ContractEvent::WithdrawalQueued { .. } => "withdrawal_queued",
+            ContractEvent::QueuedWithdrawalPaid { .. } => "queued_withdrawal_paid",
+            ContractEvent::FundsReturned { .. } => "funds_returned",
+            ContractEvent::CampaignCancelled { .. } => "campaign_cancelled",
+            ContractEvent::Refund { .. } => "refund",
+            ContractEvent::CampaignSettled { .. } => "campaign_settled",
+            ContractEvent::TokensRecovered { .. } => "tokens_recovered",
+            ContractEvent::DenomSet { .. } => "denom_set",
+            ContractEvent::HookAdded { .. } => "hook_added",
+            ContractEvent::HookRemoved { .. } => "hook_removed",
+            ContractEvent::HookFailed { .. } => "hook_failed",
+        }
+    }
+    
+    /// Full event type as emitted by the contract, e.g. `ustc_preregister.deposit`
+    pub fn event_type(&self) -> String {
+        format!("{}.{}", EVENT_PREFIX, self.name())
+    }
+    
+    /// Builds the custom event to attach to a response
+    pub fn to_event(&self) -> Event {
+        let event = Event::new(self.event_type())
+            .add_attribute("schema_version", EVENT_SCHEMA_VERSION.to_string());
+        
+        match self {
+            // Target attributes are empty when the campaign has no target
+            ContractEvent::Instantiate { owner, ustc_denom, soft_cap, deadline } => event
+                .add_attribute("owner", owner.to_string())
+                .add_attribute("ustc_denom", ustc_denom)
+                .add_attribute("soft_cap", soft_cap.map(|cap| cap.to_string()).unwrap_or_default())
+                .add_attribute("deadline", deadline.map(|deadline| deadline.to_string()).unwrap_or_default()),
+            ContractEvent::Deposit { user, amount, new_balance, total_deposits, new_user } => event
+                .add_attribute("user", user.to_string())
+                .add_attribute("amount", amount.to_string())
+                .add_attribute("new_balance", new_balance.to_string())
+                .add_attribute("total_deposits", total_deposits.to_string())
+                .add_attribute("new_user", new_user.to_string()),
+            ContractEvent::Withdraw { user, amount, new_balance, total_deposits, user_removed } => event
+                .add_attribute("user", user.to_string())
+                .add_attribute("amount", amount.to_string())
+                .add_attribute("new_balance", new_balance.to_string())
+                .add_attribute("total_deposits", total_deposits.to_string())
+                .add_attribute("user_removed", user_removed.to_string()),
+            ContractEvent::OwnerWithdraw { destination, amount } => event
+                .add_attribute("destination", destination.to_string())
+                .add_attribute("amount", amount.to_string()),
+            ContractEvent::ConfigUpdate { owner } => event
+                .add_attribute("owner", owner.to_string()),
+            ContractEvent::WithdrawalDestinationSet { destination, shares, unlock_timestamp } => event
+                .add_attribute("destination", destination.to_string())
+                .add_attribute("shares", encode_shares(shares))
+                .add_attribute("unlock_timestamp", unlock_timestamp.to_string()),
+            ContractEvent::WithdrawalDestinationProposed { destination, shares, unlock_timestamp, effective_at } => event
+                .add_attribute("destination", destination.to_string())
+                .add_attribute("shares", encode_shares(shares))
+                .add_attribute("unlock_timestamp", unlock_timestamp.to_string())
+                .add_attribute("effective_at", effective_at.to_string()),
+            ContractEvent::PendingDestinationCancelled { destination } => event
+                .add_attribute("destination", destination.to_string()),
+            ContractEvent::DistributionBracketsSet { thresholds } => event
+                .add_attribute(
+                    "thresholds",
+                    thresholds.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(","),
+                ),
+            ContractEvent::IndexRepaired { complete, corrections } => event
+                .add_attribute("complete", complete.to_string())
+                .add_attribute("corrections", corrections.to_string()),
+            ContractEvent::RoleGranted { role, address } | ContractEvent::RoleRevoked { role, address } => event
+                .add_attribute("role", role.as_str())
+                .add_attribute("address", address.to_string()),
+            ContractEvent::PausedSet { paused } => event
+                .add_attribute("paused", paused.to_string()),
+            // Both attributes are empty when the limit is removed
+            ContractEvent::SweepLimitSet { limit } => event
+                .add_attribute("max_amount", limit.as_ref().map(|l| l.max_amount.to_string()).unwrap_or_default())
+                .add_attribute("window", limit.as_ref().map(|l| l.window.to_string()).unwrap_or_default()),
+            ContractEvent::SweepLimitProposed { limit, effective_at } => event
+                .add_attribute("max_amount", limit.as_ref().map(|l| l.max_amount.to_string()).unwrap_or_default())
+                .add_attribute("window", limit.as_ref().map(|l| l.window.to_string()).unwrap_or_default())
+                .add_attribute("effective_at", effective_at.to_string()),
+            ContractEvent::ReserveRatioSet { ratio } => event
+                .add_attribute("ratio", ratio.to_string()),
+            ContractEvent::ReserveRatioProposed { ratio, effective_at } => event
+                .add_attribute("ratio", ratio.to_string())
+                .add_attribute("effective_at", effective_at.to_string()),
+            ContractEvent::WithdrawalQueued { id, user, amount }
+            | ContractEvent::QueuedWithdrawalPaid { id, user, amount } => event
+                .add_attribute("id", id.to_string())
+                .add_attribute("user", user.to_string())
+                .add_attribute("amount", amount.to_string()),
+            ContractEvent::FundsReturned { sender, amount } => event
+                .add_attribute("sender", sender.to_string())
+                .add_attribute("amount", amount.to_string()),
+            ContractEvent::CampaignCancelled { user_count, total_deposits } => event
+                .add_attribute("user_count", user_count.to_string())
+                .add_attribute("total_deposits", total_deposits.to_string()),
+            ContractEvent::Refund { user, amount } => event
+                .add_attribute("user", user.to_string())
+                .add_attribute("amount", amount.to_string()),
+            ContractEvent::CampaignSettled { succeeded, total_deposits } => event
+                .add_attribute("succeeded", succeeded.to_string())
+                .add_attribute("total_deposits", total_deposits.to_string()),
+            ContractEvent::TokensRecovered { token, recipient, amount } => event
+                .add_attribute("token", token)
+                .add_attribute("recipient", recipient.to_string())
+                .add_attribute("amount", amount.to_string()),
+            ContractEvent::DenomSet { denom, decimals } => event
+                .add_attribute("denom", denom)
+                .add_attribute("decimals", decimals.to_string()),
+            ContractEvent::HookAdded { hook } => event.add_attribute("hook", hook.to_string()),
+            ContractEvent::HookRemoved { hook } => event.add_attribute("hook", hook.to_string()),
+            ContractEvent::HookFailed { hook, error } => event
+                .add_attribute("hook", hook.to_string())
+                .add_attribute("error", error),
+        }
+    }
+    
+    /// Decodes a contract event, with or without the `wasm-` prefix added by the chain
+    ///
+    /// Attributes added by the chain (`_contract_address`, `msg_index`) are ignored.
+    ///
+    /// # Returns
+    /// * `Ok(None)` if the event was not emitted by this contract
+    /// * `Ok(Some(event))` with the decoded event
+    /// * `Err` if the event is from this contract but malformed or of an unsupported version
+    #[cfg(any(test, feature = "decoder"))]
+    pub fn from_event(event: &Event) -> Result<Option<Self>, EventDecodeError> {
+        let attributes: Vec<(&str, &str)> = event
+            .attributes
+            .iter()
+            .map(|attr| (attr.key.as_str(), attr.value.as_str()))
+            .collect();
+        Self::decode(&event.ty, &attributes)
+    }
+    
+    #[cfg(any(test, feature = "decoder"))]
+    fn decode(ty: &str, attributes: &[(&str, &str)]) -> Result<Option<Self>, EventDecodeError> {
+        let ty = ty.strip_prefix(WASM_EVENT_PREFIX).unwrap_or(ty);
+        let name = match ty.strip_prefix(EVENT_PREFIX).and_then(|rest| rest.strip_prefix('.')) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        
+        let attrs = Attributes { ty, attributes };
+        let version: u32 = attrs.parse("schema_version")?;
+        if version != EVENT_SCHEMA_VERSION {
+            return Err(EventDecodeError::UnsupportedSchemaVersion {
+                event_type: ty.to_string(),
+                version,
+            });
+        }
+        
+        let event = match name {
+            "instantiate" => ContractEvent::Instantiate {
+                owner: attrs.addr("owner")?,
+                ustc_denom: attrs.get("ustc_denom")?.to_string(),
+                soft_cap: attrs.optional("soft_cap")?,
+                deadline: attrs.optional("deadline")?,
+            },
+            "deposit" => ContractEvent::Deposit {
+                user: attrs.addr("user")?,
+                amount: attrs.parse("amount")?,
+                new_balance: attrs.parse("new_balance")?,
+                total_deposits: attrs.parse("total_deposits")?,
+                new_user: attrs.parse("new_user")?,
+            },
+            "withdraw" => ContractEvent::Withdraw {
+                user: attrs.addr("user")?,
+                amount: attrs.parse("amount")?,
+                new_balance: attrs.parse("new_balance")?,
+                total_deposits: attrs.parse("total_deposits")?,
+                user_removed: attrs.parse("user_removed")?,
+            },
+            "owner_withdraw" => ContractEvent::OwnerWithdraw {
+                destination: attrs.addr("destination")?,
+                amount: attrs.parse("amount")?,
+            },
+            "config_update" => ContractEvent::ConfigUpdate {
+                owner: attrs.addr("owner")?,
+            },
+            "withdrawal_destination_set" => ContractEvent::WithdrawalDestinationSet {
+                destination: attrs.addr("destination")?,
+                shares: attrs.shares("shares")?,
+                unlock_timestamp: attrs.parse("unlock_timestamp")?,
+            },
+            "withdrawal_destination_proposed" => ContractEvent::WithdrawalDestinationProposed {
+                destination: attrs.addr("destination")?,
+                shares: attrs.shares("shares")?,
+                unlock_timestamp: attrs.parse("unlock_timestamp")?,
+                effective_at: attrs.parse("effective_at")?,
+            },
+            "pending_destination_cancelled" => ContractEvent::PendingDestinationCancelled {
+                destination: attrs.addr("destination")?,
+            },
+            "distribution_brackets_set" => {
+                let value = attrs.get("thresholds")?;
+                let thresholds = if value.is_empty() {
+                    vec![]
+                } else {
+                    value
+                        .split(',')
+                        .map(|t| t.parse().map_err(|_| attrs.invalid("thresholds", value)))
+                        .collect::<Result<Vec<Uint128>, _>>()?
+                };
+                ContractEvent::DistributionBracketsSet { thresholds }
+            }
+            "index_repaired" => ContractEvent::IndexRepaired {
+                complete: attrs.parse("complete")?,
+                corrections: attrs.parse("corrections")?,
+            },
+            "role_granted" => ContractEvent::RoleGranted {
+                role: attrs.parse("role")?,
+                address: attrs.addr("address")?,
+            },
+            "role_revoked" => ContractEvent::RoleRevoked {
+                role: attrs.parse("role")?,
+                address: attrs.addr("address")?,
+            },
+            "paused_set" => ContractEvent::PausedSet {
+                paused: attrs.parse("paused")?,
+            },
+            "sweep_limit_set" => ContractEvent::SweepLimitSet {
+                limit: if attrs.get("max_amount")?.is_empty() {
+                    None
+                } else {
+                    Some(SweepLimit {
+                        max_amount: attrs.parse("max_amount")?,
+                        window: attrs.parse("window")?,
+                    })
+                },
+            },
+            "sweep_limit_proposed" => ContractEvent::SweepLimitProposed {
+                limit: if attrs.get("max_amount")?.is_empty() {
+                    None
+                } else {
+                    Some(SweepLimit {
+                        max_amount: attrs.parse("max_amount")?,
+                        window: attrs.parse("window")?,
+                    })
+                },
+                effective_at: attrs.parse("effective_at")?,
+            },
+            "reserve_ratio_set" => ContractEvent::ReserveRatioSet {
+                ratio: attrs.parse("ratio")?,
+            },
+            "reserve_ratio_proposed" => ContractEvent::ReserveRatioProposed {
+                ratio: attrs.parse("ratio")?,
+                effective_at: attrs.parse("effective_at")?,
+            },
+            "withdrawal_queued" => ContractEvent::WithdrawalQueued {
+                id: attrs.parse("id")?,
+                user: attrs.addr("user")?,
+                amount: attrs.parse("amount")?,
+            },
+            "queued_withdrawal_paid" => ContractEvent::QueuedWithdrawalPaid {
+                id: attrs.parse("id")?,
+                user: attrs.addr("user")?,
+                amount: attrs.parse("amount")?,
+            },
+            "funds_returned" => ContractEvent::FundsReturned {
+                sender: attrs.addr("sender")?,
+                amount: attrs.parse("amount")?,
+            },
+            "campaign_cancelled" => ContractEvent::CampaignCancelled {
+                user_count: attrs.parse("user_count")?,
+                total_deposits: attrs.parse("total_deposits")?,
+            },
+            "refund" => ContractEvent::Refund {
+                user: attrs.addr("user")?,
+                amount: attrs.parse("amount")?,
+            },
+            "campaign_settled" => ContractEvent::CampaignSettled {
+                succeeded: attrs.parse("succeeded")?,
+                total_deposits: attrs.parse("total_deposits")?,
+            },
+            "tokens_recovered" => ContractEvent::TokensRecovered {
+                token: attrs.get("token")?.to_string(),
+                recipient: attrs.addr("recipient")?,
+                amount: attrs.parse("amount")?,
+            },
+            "denom_set" => ContractEvent::DenomSet {
+                denom: attrs.get("denom")?.to_string(),
+                decimals: attrs.parse("decimals")?,
+            },
+            "hook_added" => ContractEvent::HookAdded {
+                hook: attrs.addr("hook")?,
+            },
+            "hook_removed" => ContractEvent::HookRemoved {
+                hook: attrs.addr("hook")?,
+            },
+            "hook_failed" => ContractEvent::HookFailed {
+                hook: attrs.addr("hook")?,
+                error: attrs.get("error")?.to_string(),
+            },
+            _ => {
+                return Err(EventDecodeError::UnknownEvent {
+                    event_type: ty.to_string(),
+                })
+            }
+        };
+        
+        Ok(Some(event))
+    }
+}
+
+/// State change recorded by the contract before typed events were added
+///
+/// Decoded from the flat attributes of the `wasm` event, which only carried the
+/// `action` and its arguments. The contract still emits these attributes.
+#[cfg(any(test, feature = "decoder"))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LegacyEvent {
+    Instantiate {
+        owner: Addr,
+        ustc_denom: String,
+    },
+    Deposit {
+        user: Addr,
+        amount: Uint128,
+    },
+    Withdraw {
+        user: Addr,
+        amount: Uint128,
+    },
+    OwnerWithdraw {
+        destination: Addr,
+        amount: Uint128,
+    },
+    WithdrawalDestinationSet {
+        destination: Addr,
+        unlock_timestamp: u64,
+    },
+    ConfigUpdate {
+        owner: Addr,
+    },
+}
+
+#[cfg(any(test, feature = "decoder"))]
+impl LegacyEvent {
+    /// Name of the action, e.g. `deposit`
+    pub fn name(&self) -> &'static str {
+        match self {
+            LegacyEvent::Instantiate { .. } => "instantiate",
+            LegacyEvent::Deposit { .. } => "deposit",
+            LegacyEvent::Withdraw { .. } => "withdraw",
+            LegacyEvent::OwnerWithdraw { .. } => "owner_withdraw",
+            LegacyEvent::WithdrawalDestinationSet { .. } => "set_withdrawal_destination",
+            LegacyEvent::ConfigUpdate { .. } => "update_config",
+        }
+    }
+    
+    /// Decodes the flat attributes of one contract call, e.g. `Response::attributes`
+    ///
+    /// # Returns
+    /// * `Ok(None)` if there is no `action` or it did not exist before typed events
+    /// * `Ok(Some(event))` with the decoded event
+    /// * `Err` if a known action is missing an attribute or has a malformed one
+    pub fn from_attributes(attributes: &[Attribute]) -> Result<Option<Self>, EventDecodeError> {
+        let attributes: Vec<(&str, &str)> = attributes
+            .iter()
+            .map(|attr| (attr.key.as_str(), attr.value.as_str()))
+            .collect();
+        Self::decode(&attributes)
+    }
+    
+    fn decode(attributes: &[(&str, &str)]) -> Result<Option<Self>, EventDecodeError> {
+        let attrs = Attributes { ty: LEGACY_EVENT_TYPE, attributes };
+        let action = match attributes.iter().find(|(k, _)| *k == "action") {
+            Some((_, action)) => *action,
+            None => return Ok(None),
+        };
+        
+        let event = match action {
+            "instantiate" => LegacyEvent::Instantiate {
+                owner: attrs.addr("owner")?,
+                ustc_denom: attrs.get("ustc_denom")?.to_string(),
+            },
+            "deposit" => LegacyEvent::Deposit {
+                user: attrs.addr("user")?,
+                amount: attrs.parse("amount")?,
+            },
+            "withdraw" => LegacyEvent::Withdraw {
+                user: attrs.addr("user")?,
+                amount: attrs.parse("amount")?,
+            },
+            "owner_withdraw" => LegacyEvent::OwnerWithdraw {
+                destination: attrs.addr("destination")?,
+                amount: attrs.parse("amount")?,
+            },
+            "set_withdrawal_destination" => LegacyEvent::WithdrawalDestinationSet {
+                destination: attrs.addr("destination")?,
+                unlock_timestamp: attrs.parse("unlock_timestamp")?,
+            },
+            "update_config" => LegacyEvent::ConfigUpdate {
+                owner: attrs.addr("owner")?,
+            },
+            _ => return Ok(None),
+        };
+        
+        Ok(Some(event))
+    }
+}
+
+/// Encodes shares as comma-separated `address:bps` pairs, empty for a single destination
+fn encode_shares(shares: &[DestinationShare]) -> String {
+    shares
+        .iter()
+        .map(|share| format!("{}:{}", share.address, share.bps))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Attribute lookup helper for decoding a single event
+#[cfg(any(test, feature = "decoder"))]
+struct Attributes<'a> {
+    ty: &'a str,
+    attributes: &'a [(&'a str, &'a str)],
+}
+
+#[cfg(any(test, feature = "decoder"))]
+impl<'a> Attributes<'a> {
+    fn get(&self, key: &str) -> Result<&'a str, EventDecodeError> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+            .ok_or_else(|| EventDecodeError::MissingAttribute {
+                event_type: self.ty.to_string(),
+                key: key.to_string(),
+            })
+    }
+    
+    fn parse<T: std::str::FromStr>(&self, key: &str) -> Result<T, EventDecodeError> {
+        let value = self.get(key)?;
+        value.parse().map_err(|_| self.invalid(key, value))
+    }
+    
+    fn addr(&self, key: &str) -> Result<Addr, EventDecodeError> {
+        Ok(Addr::unchecked(self.get(key)?))
+    }
+    
+    /// Parses an attribute that is empty for None
+    ///
+    /// Also added without a schema bump, so a missing attribute is None too.
+    fn optional<T: std::str::FromStr>(&self, key: &str) -> Result<Option<T>, EventDecodeError> {
+        match self.attributes.iter().find(|(k, _)| *k == key) {
+            Some((_, value)) if !value.is_empty() => value.parse().map(Some).map_err(|_| self.invalid(key, value)),
+            _ => Ok(None),
+        }
+    }
+    
+    /// Decodes shares written by `encode_shares`
+    ///
+    /// The attribute was added without a schema bump, so a missing one means no split.
+    fn shares(&self, key: &str) -> Result<Vec<DestinationShare>, EventDecodeError> {
+        let value = match self.attributes.iter().find(|(k, _)| *k == key) {
+            Some((_, value)) if !value.is_empty() => *value,
+            _ => return Ok(vec![]),
+        };
+        value
+            .split(',')
+            .map(|share| {
+                let (address, bps) = share.split_once(':').ok_or_else(|| self.invalid(key, value))?;
+                Ok(DestinationShare {
+                    address: Addr::unchecked(address),
+                    bps: bps.parse().map_err(|_| self.invalid(key, value))?,
+                })
+            })
+            .collect()
+    }
+    
+    fn invalid(&self, key: &str, value: &str) -> EventDecodeError {
+        EventDecodeError::InvalidAttribute {
+            event_type: self.ty.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// Event attribute as found in tx JSON
+#[cfg(any(test, feature = "decoder"))]
+#[derive(Deserialize)]
+struct TxAttribute {
+    key: String,
+    value: String,
+}
+
+/// Event as found in tx JSON
+#[cfg(any(test, feature = "decoder"))]
+#[derive(Deserialize)]
+struct TxEvent {
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(default)]
+    attributes: Vec<TxAttribute>,
+}
+
+/// Per-message log as found in tx JSON
+#[cfg(any(test, feature = "decoder"))]
+#[derive(Deserialize)]
+struct TxLog {
+    #[serde(default)]
+    events: Vec<TxEvent>,
+}
+
+/// Subset of a tx response (`terrad query tx <hash> --output json`) holding the events
+#[cfg(any(test, feature = "decoder"))]
+#[derive(Deserialize)]
+struct TxResponse {
+    #[serde(default)]
+    logs: Vec<TxLog>,
+    #[serde(default)]
+    events: Vec<TxEvent>,
+}
+
+/// Decodes all events emitted by this contract from a tx response in JSON
+///
+/// Events are read from `logs[].events` when present, falling back to the top-level
+/// `events` list otherwise (newer nodes return empty logs). Events from other modules
+/// and other contracts' custom events are skipped.
+///
+/// # Arguments
+/// * `tx_json` - Tx response as returned by `terrad query tx <hash> --output json`
+/// * `contract` - Only decode events emitted by this contract address, if provided
+///
+/// # Returns
+/// * Decoded events in emission order
+#[cfg(any(test, feature = "decoder"))]
+pub fn decode_tx_events(
+    tx_json: &str,
+    contract: Option<&str>,
+) -> Result<Vec<ContractEvent>, EventDecodeError> {
+    let events = parse_tx_events(tx_json)?;
+    
+    let mut decoded = Vec::new();
+    for event in &events {
+        if !event.ty.starts_with(WASM_EVENT_PREFIX) {
+            continue;
+        }
+        
+        let attributes: Vec<(&str, &str)> = event
+            .attributes
+            .iter()
+            .map(|attr| (attr.key.as_str(), attr.value.as_str()))
+            .collect();
+        
+        if let Some(contract) = contract {
+            let emitter = attributes.iter().find(|(k, _)| *k == "_contract_address");
+            if emitter.map(|(_, v)| *v) != Some(contract) {
+                continue;
+            }
+        }
+        
+        if let Some(event) = ContractEvent::decode(&event.ty, &attributes)? {
+            decoded.push(event);
+        }
+    }
+    
+    Ok(decoded)
+}
+
+/// Decodes the events of this contract from a tx response in JSON that predates typed events
+///
+/// Such transactions only carry the flat `action` attributes of the `wasm` event. Nodes
+/// may merge the calls of several contracts into one `wasm` event, each call starting
+/// with its `_contract_address`. A transaction with typed events of the contract is left
+/// to `decode_tx_events`, so no call is decoded twice.
+///
+/// # Arguments
+/// * `tx_json` - Tx response as returned by `terrad query tx <hash> --output json`
+/// * `contract` - Only decode calls of this contract address, if provided
+///
+/// # Returns
+/// * Decoded events in emission order, empty if the tx has typed events of the contract
+/// * `Err` if a legacy action is found but no contract address was given, since it
+///   cannot be told apart from another contract's action of the same name
+#[cfg(any(test, feature = "decoder"))]
+pub fn decode_legacy_tx_events(
+    tx_json: &str,
+    contract: Option<&str>,
+) -> Result<Vec<LegacyEvent>, EventDecodeError> {
+    if !decode_tx_events(tx_json, contract)?.is_empty() {
+        return Ok(vec![]);
+    }
+    
+    let mut decoded = Vec::new();
+    for event in parse_tx_events(tx_json)? {
+        if event.ty != LEGACY_EVENT_TYPE {
+            continue;
+        }
+        
+        let attributes: Vec<(&str, &str)> = event
+            .attributes
+            .iter()
+            .map(|attr| (attr.key.as_str(), attr.value.as_str()))
+            .collect();
+        
+        // Each call starts with the address of the contract that made it
+        let mut start = 0;
+        while start < attributes.len() {
+            let end = attributes[start + 1..]
+                .iter()
+                .position(|(k, _)| *k == "_contract_address")
+                .map_or(attributes.len(), |offset| start + 1 + offset);
+            let call = &attributes[start..end];
+            start = end;
+            
+            if let Some(contract) = contract {
+                let emitter = call.iter().find(|(k, _)| *k == "_contract_address");
+                if emitter.map(|(_, v)| *v) != Some(contract) {
+                    continue;
+                }
+            }
+            
+            if let Some(event) = LegacyEvent::decode(call)? {
+                if contract.is_none() {
+                    return Err(EventDecodeError::UnattributedLegacyEvent {
+                        action: event.name().to_string(),
+                    });
+                }
+                decoded.push(event);
+            }
+        }
+    }
+    
+    Ok(decoded)
+}
+
+/// Events of a tx response in JSON, from `logs[].events` or the top-level `events` list
+#[cfg(any(test, feature = "decoder"))]
+fn parse_tx_events(tx_json: &str) -> Result<Vec<TxEvent>, EventDecodeError> {
+    let tx: TxResponse = from_json(tx_json.as_bytes())
+        .map_err(|e| EventDecodeError::InvalidJson { msg: e.to_string() })?;
+    
+    Ok(if tx.logs.iter().any(|log| !log.events.is_empty()) {
+        tx.logs.into_iter().flat_map(|log| log.events).collect()
+    } else {
+        tx.events
+    })
+}
@@ -1,6 +1,21 @@
-use cosmwasm_std::{Coin, MessageInfo, StdError, Uint128};
+use cosmwasm_std::{Addr, Api, Coin, Decimal, Empty, Env, MessageInfo, Order, StdError, StdResult, Storage, SubMsg, Uint128};
+use cw_storage_plus::{Bound, Map};
 use crate::error::ContractError;
-use crate::state::Config;
+use crate::state::{
+    Activity, ActivityKind, HistoryEntry, HistoryKind, Role, ACTIVITY, DAILY_STATS, DISTRIBUTION,
+    DISTRIBUTION_THRESHOLDS, ROLES, SEQUENCE, USER_HISTORY, USER_HISTORY_SEQ, PendingDestination,
+    PENDING_DESTINATION, WITHDRAWAL_DESTINATION, WITHDRAWAL_UNLOCK_TIMESTAMP, DestinationShare,
+    TOTAL_BPS, WITHDRAWAL_SHARES, SweepLimit, SWEEP_LOG, RESERVE_RATIO, TOTAL_DEPOSITS,
+    WITHDRAW_QUEUE_STATE, WITHDRAW_QUEUE, QueuedWithdrawal, CampaignStatus, CAMPAIGN_CANCELLATION, CAMPAIGN_RESULT, CONFIG,
+    CampaignCancellation, PAUSED, USER_COUNT, HOOKS, HOOK_GAS_LIMIT, BracketStats, DISTRIBUTION_REBUILD,
+    REPAIR_PROGRESS, PendingSweepLimit, PENDING_SWEEP_LIMIT, SWEEP_LIMIT, PendingReserveRatio,
+    PENDING_RESERVE_RATIO,
+};
+use crate::events::ContractEvent;
+use crate::msg::{BalanceChangedHookMsg, UncheckedDestinationShare};
+
+/// Length of a daily statistics bucket in seconds
+pub const SECONDS_PER_DAY: u64 = 86400;
 
 /// Validate that funds contain only the expected denomination
 pub fn validate_denom(funds: &[Coin], expected_denom: &str) -> Result<Uint128, ContractError> {
@@ -29,14 +44,185 @@ pub fn validate_denom(funds: &[Coin], expected_denom: &str) -> Result<Uint128, C
     Ok(coin.amount)
 }
 
-/// Verify that the caller is the owner
-pub fn verify_owner(info: &MessageInfo, config: &Config) -> Result<(), ContractError> {
-    if info.sender != config.owner {
-        return Err(ContractError::Unauthorized {});
+/// Split deposit funds into the expected denomination and everything else
+/// 
+/// Other denominations are returned rather than rejected so the caller can refund them.
+/// 
+/// # Returns
+/// * The amount of the expected denomination and the other non-zero coins
+pub fn split_deposit_funds(funds: &[Coin], expected_denom: &str) -> Result<(Uint128, Vec<Coin>), ContractError> {
+    let (expected, others): (Vec<&Coin>, Vec<&Coin>) = funds.iter().partition(|coin| coin.denom == expected_denom);
+    
+    let amount = match (expected.first(), others.first()) {
+        (Some(coin), _) => coin.amount,
+        (None, Some(coin)) => {
+            return Err(ContractError::InvalidDenom {
+                expected: expected_denom.to_string(),
+                got: coin.denom.clone(),
+            })
+        },
+        (None, None) => Uint128::zero(),
+    };
+    if amount.is_zero() {
+        return Err(ContractError::InvalidAmount {});
+    }
+    
+    let others = others.into_iter().filter(|coin| !coin.amount.is_zero()).cloned().collect();
+    Ok((amount, others))
+}
+
+/// Validate a native denomination against the Cosmos SDK denom rules
+/// 
+/// A letter followed by 2 to 127 letters, digits or any of `/:._-`, which covers
+/// base denoms (`uusd`), IBC denoms (`ibc/...`) and token factory denoms (`factory/...`).
+pub fn validate_denom_name(denom: &str) -> Result<(), ContractError> {
+    let valid = (3..=128).contains(&denom.len())
+        && denom.starts_with(|c: char| c.is_ascii_alphabetic())
+        && denom.chars().all(|c| c.is_ascii_alphanumeric() || "/:._-".contains(c));
+    if !valid {
+        return Err(ContractError::InvalidDenomName { denom: denom.to_string() });
+    }
+    Ok(())
+}
+
+/// Validate a user-supplied address with `addr_validate`
+/// 
+/// `error` builds the error naming the rejected field, so callers can tell an invalid
+/// owner from an invalid destination.
+pub fn validate_address(api: &dyn Api, address: &str, error: fn(String) -> ContractError) -> Result<Addr, ContractError> {
+    api.addr_validate(address).map_err(|_| error(address.to_string()))
+}
+
+/// Reject funds attached to a message that does not take any
+pub fn nonpayable(info: &MessageInfo) -> Result<(), ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::NonPayable {});
     }
     Ok(())
 }
 
+/// Verify that the caller holds the given role
+pub fn verify_role(storage: &dyn Storage, info: &MessageInfo, role: Role) -> Result<(), ContractError> {
+    if !ROLES.has(storage, (role.as_str(), &info.sender)) {
+        return Err(ContractError::MissingRole { role });
+    }
+    Ok(())
+}
+
+/// Verify that the caller holds at least one of the given roles
+/// 
+/// Reports the last role of the list as missing if the caller holds none of them.
+pub fn verify_any_role(storage: &dyn Storage, info: &MessageInfo, roles: &[Role]) -> Result<(), ContractError> {
+    if roles.iter().any(|role| ROLES.has(storage, (role.as_str(), &info.sender))) {
+        return Ok(());
+    }
+    match roles.last() {
+        Some(role) => Err(ContractError::MissingRole { role: *role }),
+        None => Err(ContractError::Unauthorized {}),
+    }
+}
+
+/// Grant every role to an address (used for the owner on instantiate and migrate)
+pub fn grant_all_roles(storage: &mut dyn Storage, address: &Addr) -> StdResult<()> {
+    for role in Role::ALL {
+        ROLES.save(storage, (role.as_str(), address), &Empty {})?;
+    }
+    Ok(())
+}
+
+/// Make `owner` the only holder of every role
+/// 
+/// Every other holder loses its roles, so whichever role key was lost or compromised no
+/// longer has any control. Each change is recorded in the activity ledger.
+/// 
+/// # Returns
+/// * One role revoked or role granted event per change
+pub fn reset_roles(storage: &mut dyn Storage, env: &Env, owner: &Addr) -> StdResult<Vec<ContractEvent>> {
+    let mut events = vec![];
+    for role in Role::ALL {
+        // Granted first, so the events replay as a sequence that never leaves the contract without an admin
+        if !ROLES.has(storage, (role.as_str(), owner)) {
+            ROLES.save(storage, (role.as_str(), owner), &Empty {})?;
+            record_activity(storage, env, ActivityKind::RoleGranted { role, address: owner.clone() })?;
+            events.push(ContractEvent::RoleGranted { role, address: owner.clone() });
+        }
+        
+        let holders = ROLES
+            .prefix(role.as_str())
+            .keys(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for holder in holders.into_iter().filter(|holder| holder != owner) {
+            ROLES.remove(storage, (role.as_str(), &holder));
+            record_activity(storage, env, ActivityKind::RoleRevoked { role, address: holder.clone() })?;
+            events.push(ContractEvent::RoleRevoked { role, address: holder });
+        }
+    }
+    Ok(events)
+}
+
+/// Make an address the owner, moving every role held by the current owner to it
+/// 
+/// Roles held by other addresses are not affected.
+/// 
+/// # Returns
+/// * The config update event to emit
+pub fn transfer_ownership(storage: &mut dyn Storage, env: &Env, new_owner: Addr) -> StdResult<ContractEvent> {
+    let mut config = CONFIG.load(storage)?;
+    for role in Role::ALL {
+        if ROLES.has(storage, (role.as_str(), &config.owner)) {
+            ROLES.remove(storage, (role.as_str(), &config.owner));
+            ROLES.save(storage, (role.as_str(), &new_owner), &Empty {})?;
+        }
+    }
+    config.owner = new_owner;
+    CONFIG.save(storage, &config)?;
+    
+    record_activity(storage, env, ActivityKind::ConfigUpdate { owner: config.owner.clone() })?;
+    
+    Ok(ContractEvent::ConfigUpdate { owner: config.owner })
+}
+
+/// Pause or unpause deposits
+/// 
+/// # Returns
+/// * The event to emit if the paused flag changed
+pub fn set_paused(storage: &mut dyn Storage, env: &Env, paused: bool) -> StdResult<Option<ContractEvent>> {
+    if PAUSED.load(storage)? == paused {
+        return Ok(None);
+    }
+    PAUSED.save(storage, &paused)?;
+    record_activity(storage, env, ActivityKind::PausedSet { paused })?;
+    
+    Ok(Some(ContractEvent::PausedSet { paused }))
+}
+
+/// Cancel the campaign, snapshotting the depositors and total deposits for refund progress
+/// 
+/// Records the cancellation in the activity ledger.
+/// 
+/// # Returns
+/// * The cancellation snapshot, or `CampaignCancelled` if already cancelled
+pub fn cancel_campaign(storage: &mut dyn Storage, env: &Env) -> Result<CampaignCancellation, ContractError> {
+    if CAMPAIGN_CANCELLATION.exists(storage) {
+        return Err(ContractError::CampaignCancelled {});
+    }
+    
+    let user_count = USER_COUNT.load(storage)?;
+    let total_deposits = TOTAL_DEPOSITS.load(storage)?;
+    let cancellation = CampaignCancellation {
+        cancelled_at: env.block.time.seconds(),
+        users_at_cancel: user_count,
+        deposits_at_cancel: total_deposits,
+        refunded_users: 0,
+        refunded_amount: Uint128::zero(),
+    };
+    CAMPAIGN_CANCELLATION.save(storage, &cancellation)?;
+    
+    record_activity(storage, env, ActivityKind::CampaignCancelled { user_count, total_deposits })?;
+    
+    Ok(cancellation)
+}
+
 /// Remove a user from the index-based storage system.
 /// 
 /// This function implements the swap-and-remove pattern to maintain compact indices:
@@ -44,6 +230,10 @@ pub fn verify_owner(info: &MessageInfo, config: &Config) -> Result<(), ContractE
 /// - Then remove the last index entry
 /// - This ensures indices remain compact and contiguous (0..user_count-1)
 /// 
+/// While an index repair is in progress, only the part the repair has already compacted
+/// (below its `next_index`) is swapped within; entries it has not reached yet are left
+/// for it to drop once the balance is gone.
+/// 
 /// # Arguments
 /// * `storage` - Mutable storage reference
 /// * `user` - Address of the user to remove from index
@@ -65,6 +255,31 @@ pub fn remove_user_from_index(
         return Ok(());
     };
     
+    // USER_COUNT is only restored when the repair completes, the repair's own count is used instead
+    if let Some(mut progress) = REPAIR_PROGRESS.may_load(storage)? {
+        let indexed = progress.has_indexed(user_index)
+            && user_index < progress.next_index
+            && USER_INDEX.may_load(storage, user_index)?.as_ref() == Some(user);
+        if indexed {
+            let last_index = progress.next_index - 1;
+            if user_index != last_index {
+                let last_user = USER_INDEX
+                    .may_load(storage, last_index)?
+                    .ok_or(crate::error::ContractError::IndexInconsistency {})?;
+                USER_INDEX.save(storage, user_index, &last_user)?;
+                USER_INDEX_REVERSE.save(storage, &last_user, &user_index)?;
+            }
+            USER_INDEX.remove(storage, last_index);
+            USER_INDEX_REVERSE.remove(storage, user);
+            progress.next_index = last_index;
+            REPAIR_PROGRESS.save(storage, &progress)?;
+        }
+        
+        let user_count = USER_COUNT.load(storage)?;
+        USER_COUNT.save(storage, &user_count.saturating_sub(1))?;
+        return Ok(());
+    }
+    
     // Get current user count
     let user_count = USER_COUNT.load(storage)?;
     
@@ -102,6 +317,647 @@ pub fn remove_user_from_index(
     Ok(())
 }
 
+/// Append an entry to a user's deposit/withdrawal history
+/// 
+/// Entries are keyed by a per-user sequence number that only ever increases, so the
+/// history survives the user withdrawing everything and depositing again.
+/// 
+/// # Arguments
+/// * `storage` - Mutable storage reference
+/// * `env` - Contract environment, used for block height and time
+/// * `user` - Address of the user whose balance changed
+/// * `kind` - Whether this was a deposit or a withdrawal
+/// * `amount` - Amount deposited or withdrawn
+/// * `resulting_balance` - User balance after the change
+/// 
+/// # Returns
+/// * The sequence number assigned to the new entry
+pub fn append_user_history(
+    storage: &mut dyn Storage,
+    env: &Env,
+    user: &Addr,
+    kind: HistoryKind,
+    amount: Uint128,
+    resulting_balance: Uint128,
+) -> StdResult<u64> {
+    let sequence = USER_HISTORY_SEQ.may_load(storage, user)?.unwrap_or_default();
+    
+    USER_HISTORY.save(
+        storage,
+        (user, sequence),
+        &HistoryEntry {
+            sequence,
+            height: env.block.height,
+            time: env.block.time.seconds(),
+            kind,
+            amount,
+            resulting_balance,
+        },
+    )?;
+    USER_HISTORY_SEQ.save(storage, user, &(sequence + 1))?;
+    
+    Ok(sequence)
+}
+
+/// Record a state change in the global activity ledger
+/// 
+/// Assigns the next global sequence number, so entries are contiguous (1, 2, 3, ...)
+/// and consumers can detect gaps.
+/// 
+/// # Arguments
+/// * `storage` - Mutable storage reference
+/// * `env` - Contract environment, used for block height and time
+/// * `kind` - The state change to record
+/// 
+/// # Returns
+/// * The sequence number assigned to the new entry
+pub fn record_activity(
+    storage: &mut dyn Storage,
+    env: &Env,
+    kind: ActivityKind,
+) -> StdResult<u64> {
+    let sequence = SEQUENCE.load(storage)? + 1;
+    
+    ACTIVITY.save(
+        storage,
+        sequence,
+        &Activity {
+            sequence,
+            height: env.block.height,
+            time: env.block.time.seconds(),
+            kind,
+        },
+    )?;
+    SEQUENCE.save(storage, &sequence)?;
+    
+    Ok(sequence)
+}
+
+/// Build the notification of a balance change for every registered hook
+/// 
+/// Hooks are called with a gas limit and reply on error, so a failing hook is reported by
+/// `reply` instead of reverting the balance change. The reply id is the one assigned to the
+/// hook when it was added, see HOOK_REPLY_IDS.
+/// 
+/// # Returns
+/// * One `SubMsg` per hook, in address order
+pub fn balance_changed_hooks(
+    storage: &dyn Storage,
+    user: &Addr,
+    old_balance: Uint128,
+    new_balance: Uint128,
+) -> StdResult<Vec<SubMsg>> {
+    HOOKS
+        .range(storage, None, None, Order::Ascending)
+        .map(|entry| {
+            let (hook, reply_id) = entry?;
+            let msg = BalanceChangedHookMsg {
+                user: user.to_string(),
+                old_balance,
+                new_balance,
+            };
+            let msg = msg.into_cosmos_msg(hook.to_string())?;
+            Ok(SubMsg::reply_on_error(msg, reply_id).with_gas_limit(HOOK_GAS_LIMIT))
+        })
+        .collect()
+}
+
+/// Day number of the current block, used as the daily statistics bucket key
+pub fn day_of(env: &Env) -> u64 {
+    env.block.time.seconds() / SECONDS_PER_DAY
+}
+
+/// Add a deposit to the current day's statistics bucket
+/// 
+/// # Arguments
+/// * `storage` - Mutable storage reference
+/// * `env` - Contract environment, used to determine the day
+/// * `amount` - Amount deposited
+/// * `is_new_user` - Whether the user's balance was zero before the deposit
+/// * `closing_total` - Total deposits after the deposit
+pub fn record_daily_deposit(
+    storage: &mut dyn Storage,
+    env: &Env,
+    amount: Uint128,
+    is_new_user: bool,
+    closing_total: Uint128,
+) -> StdResult<()> {
+    DAILY_STATS.update(storage, day_of(env), |stats| -> StdResult<_> {
+        let mut stats = stats.unwrap_or_default();
+        stats.deposit_volume += amount;
+        if is_new_user {
+            stats.new_users += 1;
+        }
+        stats.closing_total = closing_total;
+        Ok(stats)
+    })?;
+    Ok(())
+}
+
+/// Add a user withdrawal to the current day's statistics bucket
+/// 
+/// # Arguments
+/// * `storage` - Mutable storage reference
+/// * `env` - Contract environment, used to determine the day
+/// * `amount` - Amount withdrawn
+/// * `user_exited` - Whether the user's balance became zero
+/// * `closing_total` - Total deposits after the withdrawal
+pub fn record_daily_withdrawal(
+    storage: &mut dyn Storage,
+    env: &Env,
+    amount: Uint128,
+    user_exited: bool,
+    closing_total: Uint128,
+) -> StdResult<()> {
+    DAILY_STATS.update(storage, day_of(env), |stats| -> StdResult<_> {
+        let mut stats = stats.unwrap_or_default();
+        stats.withdrawal_volume += amount;
+        if user_exited {
+            stats.exited_users += 1;
+        }
+        stats.closing_total = closing_total;
+        Ok(stats)
+    })?;
+    Ok(())
+}
+
+/// Index of the distribution bracket containing `amount`
+/// 
+/// Equals the number of thresholds less than or equal to `amount`.
+pub fn bracket_of(thresholds: &[Uint128], amount: Uint128) -> u32 {
+    thresholds.iter().filter(|threshold| **threshold <= amount).count() as u32
+}
+
+/// Move a user between distribution brackets after a balance change
+/// 
+/// Removes the old balance from its bracket (if non-zero) and adds the new balance to
+/// its bracket (if non-zero), keeping bracket counts and volumes in sync with USERS.
+/// While an index repair is in progress the brackets may be missing balances; they are
+/// replaced once the repair completes, so they are not checked for underflow until then.
+/// 
+/// # Arguments
+/// * `storage` - Mutable storage reference
+/// * `old_balance` - User balance before the change
+/// * `new_balance` - User balance after the change
+pub fn update_distribution(
+    storage: &mut dyn Storage,
+    old_balance: Uint128,
+    new_balance: Uint128,
+) -> StdResult<()> {
+    let thresholds = DISTRIBUTION_THRESHOLDS.load(storage)?;
+    let checked = !REPAIR_PROGRESS.exists(storage);
+    move_between_brackets(storage, &DISTRIBUTION, &thresholds, old_balance, new_balance, checked)
+}
+
+/// Keep an index repair in progress in step with a balance change
+/// 
+/// The repair sums the balances it has walked into its total and rebuilt distribution.
+/// A change to a user it already counted is applied to them too, so the figures it
+/// writes on completion match USERS. Does nothing while no repair is in progress.
+/// 
+/// # Arguments
+/// * `storage` - Mutable storage reference
+/// * `user` - Address of the user whose balance changed
+/// * `old_balance` - User balance before the change
+/// * `new_balance` - User balance after the change
+pub fn sync_repair_progress(
+    storage: &mut dyn Storage,
+    user: &Addr,
+    old_balance: Uint128,
+    new_balance: Uint128,
+) -> StdResult<()> {
+    let Some(mut progress) = REPAIR_PROGRESS.may_load(storage)? else {
+        return Ok(());
+    };
+    if !progress.has_counted(user) {
+        return Ok(());
+    }
+    
+    let thresholds = DISTRIBUTION_THRESHOLDS.load(storage)?;
+    move_between_brackets(storage, &DISTRIBUTION_REBUILD, &thresholds, old_balance, new_balance, true)?;
+    progress.total = progress.total.checked_sub(old_balance)?.checked_add(new_balance)?;
+    REPAIR_PROGRESS.save(storage, &progress)
+}
+
+/// Move a balance between the brackets of `brackets`
+/// 
+/// With `checked`, a bracket that does not hold the old balance is an error; otherwise
+/// its count and volume stop at zero.
+fn move_between_brackets(
+    storage: &mut dyn Storage,
+    brackets: &Map<u32, BracketStats>,
+    thresholds: &[Uint128],
+    old_balance: Uint128,
+    new_balance: Uint128,
+    checked: bool,
+) -> StdResult<()> {
+    if !old_balance.is_zero() {
+        brackets.update(storage, bracket_of(thresholds, old_balance), |stats| -> StdResult<_> {
+            let mut stats = stats.unwrap_or_default();
+            if checked {
+                // Every balance is in its bracket; an underflow means the distribution is out of
+                // sync with USERS and needs a RepairIndex run
+                stats.count = stats.count.checked_sub(1).ok_or_else(|| {
+                    StdError::generic_err("Distribution bracket count underflow, run RepairIndex")
+                })?;
+                stats.volume = stats.volume.checked_sub(old_balance)?;
+            } else {
+                stats.count = stats.count.saturating_sub(1);
+                stats.volume = stats.volume.saturating_sub(old_balance);
+            }
+            Ok(stats)
+        })?;
+    }
+    
+    if !new_balance.is_zero() {
+        brackets.update(storage, bracket_of(thresholds, new_balance), |stats| -> StdResult<_> {
+            let mut stats = stats.unwrap_or_default();
+            stats.count += 1;
+            stats.volume += new_balance;
+            Ok(stats)
+        })?;
+    }
+    
+    Ok(())
+}
+
+/// Validate the split of a sweep between several addresses
+/// 
+/// An empty split sends everything to `destination`. Otherwise every share must be
+/// non-zero, addresses must be unique, `destination` must be one of them (it receives
+/// the rounding remainder) and the shares must sum to TOTAL_BPS.
+pub fn validate_shares(destination: &Addr, shares: &[DestinationShare]) -> Result<(), ContractError> {
+    if shares.is_empty() {
+        return Ok(());
+    }
+    
+    let mut total: u32 = 0;
+    for (i, share) in shares.iter().enumerate() {
+        if share.bps == 0 || shares[..i].iter().any(|other| other.address == share.address) {
+            return Err(ContractError::InvalidDestinationShares { total: TOTAL_BPS });
+        }
+        total += u32::from(share.bps);
+    }
+    
+    if total != u32::from(TOTAL_BPS) || !shares.iter().any(|share| share.address == *destination) {
+        return Err(ContractError::InvalidDestinationShares { total: TOTAL_BPS });
+    }
+    
+    Ok(())
+}
+
+/// Split a sweep of `amount` into one leg per share
+/// 
+/// Each share is rounded down; `destination` also receives what rounding leaves over,
+/// so the legs always add up to `amount`. Legs that round to zero are dropped, since a
+/// bank send of nothing fails.
+pub fn split_sweep(destination: &Addr, shares: &[DestinationShare], amount: Uint128) -> Vec<(Addr, Uint128)> {
+    if shares.is_empty() {
+        return vec![(destination.clone(), amount)];
+    }
+    
+    let mut legs: Vec<(Addr, Uint128)> = shares
+        .iter()
+        .map(|share| (share.address.clone(), amount.multiply_ratio(share.bps, TOTAL_BPS)))
+        .collect();
+    let remainder = amount - legs.iter().map(|(_, leg)| *leg).sum::<Uint128>();
+    if let Some((_, leg)) = legs.iter_mut().find(|(address, _)| address == destination) {
+        *leg += remainder;
+    }
+    
+    legs.retain(|(_, leg)| !leg.is_zero());
+    legs
+}
+
+/// Reserve a sweep at `now` must leave behind, and how much of `balance` lies above it
+/// 
+/// The reserve is rounded up so it never falls short of the ratio in effect. Amounts
+/// owed to queued withdrawals are not part of TOTAL_DEPOSITS any more and are kept
+/// on top of the reserve.
+/// 
+/// # Returns
+/// * `(required_reserve, sweepable)`
+pub fn sweepable_balance(storage: &dyn Storage, now: u64, balance: Uint128) -> StdResult<(Uint128, Uint128)> {
+    let (ratio, _) = effective_reserve_ratio(storage, now)?;
+    let required_reserve = TOTAL_DEPOSITS.load(storage)?.mul_ceil(ratio);
+    let queued = WITHDRAW_QUEUE_STATE.load(storage)?.total;
+    Ok((required_reserve, balance.saturating_sub(required_reserve).saturating_sub(queued)))
+}
+
+/// Append a payout to the back of the withdraw queue
+/// 
+/// The amount must already be deducted from the user's balance; ReturnFunds pays it once
+/// the contract balance covers it and every earlier entry.
+/// 
+/// # Returns
+/// * The queued entry and its position in the queue
+pub fn queue_withdrawal(storage: &mut dyn Storage, env: &Env, user: &Addr, amount: Uint128) -> StdResult<(QueuedWithdrawal, u64)> {
+    let mut queue = WITHDRAW_QUEUE_STATE.load(storage)?;
+    let entry = QueuedWithdrawal {
+        id: queue.next,
+        user: user.clone(),
+        amount,
+        queued_at: env.block.time.seconds(),
+    };
+    WITHDRAW_QUEUE.save(storage, entry.id, &entry)?;
+    queue.next += 1;
+    queue.total += amount;
+    WITHDRAW_QUEUE_STATE.save(storage, &queue)?;
+    
+    record_activity(storage, env, ActivityKind::WithdrawalQueued { id: entry.id, user: user.clone(), amount })?;
+    
+    let position = entry.id - queue.head;
+    Ok((entry, position))
+}
+
+/// Sweeps within the trailing sweep limit window
+pub struct SweepWindow {
+    /// Amount swept in the last `SweepLimit::window` seconds
+    pub swept: Uint128,
+    /// Time the oldest sweep in the window drops out of it, or None if nothing was swept
+    pub resets_at: Option<u64>,
+}
+
+/// First block time still inside the window ending at `now`
+fn sweep_window_start(limit: &SweepLimit, now: u64) -> u64 {
+    (now + 1).saturating_sub(limit.window)
+}
+
+/// Sweeps a sweep at `now` counts against
+/// 
+/// A sweep counts for `SweepLimit::window` seconds after it happened, so the limit holds for any
+/// span of that length rather than per calendar period.
+pub fn current_sweep_window(storage: &dyn Storage, limit: &SweepLimit, now: u64) -> StdResult<SweepWindow> {
+    let start = sweep_window_start(limit, now);
+    let mut window = SweepWindow {
+        swept: Uint128::zero(),
+        resets_at: None,
+    };
+    for entry in SWEEP_LOG.range(storage, Some(Bound::inclusive(start)), None, Order::Ascending) {
+        let (swept_at, amount) = entry?;
+        window.resets_at.get_or_insert(swept_at + limit.window);
+        window.swept += amount;
+    }
+    Ok(window)
+}
+
+/// Record a throttled sweep and prune sweeps that left the window
+pub fn record_sweep(storage: &mut dyn Storage, limit: &SweepLimit, now: u64, amount: Uint128) -> StdResult<()> {
+    let start = sweep_window_start(limit, now);
+    let expired: Vec<u64> = SWEEP_LOG
+        .keys(storage, None, Some(Bound::exclusive(start)), Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for swept_at in expired {
+        SWEEP_LOG.remove(storage, swept_at);
+    }
+    SWEEP_LOG.update(storage, now, |swept| -> StdResult<_> { Ok(swept.unwrap_or_default() + amount) })?;
+    Ok(())
+}
+
+/// Withdrawal configuration as seen by a sweep at a given time
+pub struct EffectiveDestination {
+    pub destination: Option<Addr>,
+    pub shares: Vec<DestinationShare>,
+    pub unlock_timestamp: u64,
+    /// Staged change not yet in effect
+    pub pending: Option<PendingDestination>,
+}
+
+/// Withdrawal destination in effect at `now`
+/// 
+/// A pending change whose delay has elapsed is treated as already applied, so queries
+/// see the same destination a sweep at `now` would use.
+pub fn effective_destination(storage: &dyn Storage, now: u64) -> StdResult<EffectiveDestination> {
+    match PENDING_DESTINATION.load(storage)? {
+        Some(pending) if pending.effective_at <= now => Ok(EffectiveDestination {
+            destination: Some(pending.destination),
+            shares: pending.shares,
+            unlock_timestamp: pending.unlock_timestamp,
+            pending: None,
+        }),
+        pending => Ok(EffectiveDestination {
+            destination: WITHDRAWAL_DESTINATION.load(storage)?,
+            shares: WITHDRAWAL_SHARES.load(storage)?,
+            unlock_timestamp: WITHDRAWAL_UNLOCK_TIMESTAMP.load(storage)?,
+            pending,
+        }),
+    }
+}
+
+/// Campaign status at `now`
+/// 
+/// Uses the recorded outcome once one exists, so withdrawals after the deadline do not
+/// turn a succeeded campaign into a failed one.
+pub fn campaign_status(storage: &dyn Storage, now: u64) -> StdResult<CampaignStatus> {
+    if CAMPAIGN_CANCELLATION.exists(storage) {
+        return Ok(CampaignStatus::Cancelled);
+    }
+    if let Some(result) = CAMPAIGN_RESULT.may_load(storage)? {
+        return Ok(result);
+    }
+    
+    let config = CONFIG.load(storage)?;
+    match (config.soft_cap, config.deadline) {
+        (Some(soft_cap), Some(deadline)) if now >= deadline => {
+            if TOTAL_DEPOSITS.load(storage)? >= soft_cap {
+                Ok(CampaignStatus::Succeeded)
+            } else {
+                Ok(CampaignStatus::Failed)
+            }
+        }
+        _ => Ok(CampaignStatus::Active),
+    }
+}
+
+/// Record the campaign outcome if the deadline has passed and none is recorded yet
+/// 
+/// Must run before anything that changes TOTAL_DEPOSITS after the deadline.
+/// 
+/// # Returns
+/// * The event to emit if the outcome was recorded
+pub fn settle_campaign(storage: &mut dyn Storage, env: &Env) -> StdResult<Option<ContractEvent>> {
+    if CAMPAIGN_RESULT.exists(storage) {
+        return Ok(None);
+    }
+    let status = campaign_status(storage, env.block.time.seconds())?;
+    if !matches!(status, CampaignStatus::Succeeded | CampaignStatus::Failed) {
+        return Ok(None);
+    }
+    
+    CAMPAIGN_RESULT.save(storage, &status)?;
+    let succeeded = status == CampaignStatus::Succeeded;
+    let total_deposits = TOTAL_DEPOSITS.load(storage)?;
+    record_activity(storage, env, ActivityKind::CampaignSettled { succeeded, total_deposits })?;
+    
+    Ok(Some(ContractEvent::CampaignSettled { succeeded, total_deposits }))
+}
+
+/// Validate a requested withdrawal destination
+/// 
+/// The destination and every share address must be valid addresses, the shares must
+/// split the sweep exactly and the unlock timestamp must be at least 7 days in the future.
+/// 
+/// # Returns
+/// * The validated destination address and shares
+pub fn validate_destination_change(
+    api: &dyn Api,
+    env: &Env,
+    destination: &str,
+    shares: &[UncheckedDestinationShare],
+    unlock_timestamp: u64,
+) -> Result<(Addr, Vec<DestinationShare>), ContractError> {
+    let destination = validate_address(api, destination, |address| ContractError::InvalidDestinationAddress { address })?;
+    let shares = shares
+        .iter()
+        .map(|share| {
+            Ok(DestinationShare {
+                address: validate_address(api, &share.address, |address| ContractError::InvalidDestinationAddress { address })?,
+                bps: share.bps,
+            })
+        })
+        .collect::<Result<Vec<_>, ContractError>>()?;
+    validate_shares(&destination, &shares)?;
+    
+    // Validate timestamp is at least 7 days in the future
+    let min_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60; // 7 days in seconds
+    if unlock_timestamp < min_timestamp {
+        return Err(ContractError::InvalidTimestamp {});
+    }
+    Ok((destination, shares))
+}
+
+/// Make a withdrawal destination current immediately
+/// 
+/// # Returns
+/// * The destination set event to emit
+pub fn save_withdrawal_destination(
+    storage: &mut dyn Storage,
+    env: &Env,
+    destination: Addr,
+    shares: Vec<DestinationShare>,
+    unlock_timestamp: u64,
+) -> StdResult<ContractEvent> {
+    WITHDRAWAL_DESTINATION.save(storage, &Some(destination.clone()))?;
+    WITHDRAWAL_SHARES.save(storage, &shares)?;
+    WITHDRAWAL_UNLOCK_TIMESTAMP.save(storage, &unlock_timestamp)?;
+    
+    record_activity(
+        storage,
+        env,
+        ActivityKind::WithdrawalDestinationSet {
+            destination: destination.clone(),
+            shares: shares.clone(),
+            unlock_timestamp,
+        },
+    )?;
+    
+    Ok(ContractEvent::WithdrawalDestinationSet {
+        destination,
+        shares,
+        unlock_timestamp,
+    })
+}
+
+/// Apply a pending withdrawal destination change whose delay has elapsed
+/// 
+/// Records the change in the activity ledger like a direct destination change.
+/// 
+/// # Returns
+/// * The event to emit if a change was applied
+pub fn apply_pending_destination(storage: &mut dyn Storage, env: &Env) -> StdResult<Option<ContractEvent>> {
+    let pending = match PENDING_DESTINATION.load(storage)? {
+        Some(pending) if pending.effective_at <= env.block.time.seconds() => pending,
+        _ => return Ok(None),
+    };
+    
+    PENDING_DESTINATION.save(storage, &None)?;
+    
+    save_withdrawal_destination(storage, env, pending.destination, pending.shares, pending.unlock_timestamp).map(Some)
+}
+
+/// Whether `new` throttles sweeps at least as much as `current`
+/// 
+/// A limit allowing no more over a window no shorter never lets through a sweep the current
+/// limit would refuse. Having no limit is the loosest setting.
+pub fn tightens_sweep_limit(current: &Option<SweepLimit>, new: &Option<SweepLimit>) -> bool {
+    match (current, new) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(current), Some(new)) => new.max_amount <= current.max_amount && new.window >= current.window,
+    }
+}
+
+/// Make a sweep limit current immediately
+/// 
+/// # Returns
+/// * The sweep limit set event to emit
+pub fn save_sweep_limit(storage: &mut dyn Storage, env: &Env, limit: Option<SweepLimit>) -> StdResult<ContractEvent> {
+    SWEEP_LIMIT.save(storage, &limit)?;
+    record_activity(storage, env, ActivityKind::SweepLimitSet { limit: limit.clone() })?;
+    
+    Ok(ContractEvent::SweepLimitSet { limit })
+}
+
+/// Apply a pending sweep limit change whose delay has elapsed
+/// 
+/// # Returns
+/// * The event to emit if a change was applied
+pub fn apply_pending_sweep_limit(storage: &mut dyn Storage, env: &Env) -> StdResult<Option<ContractEvent>> {
+    let pending = match PENDING_SWEEP_LIMIT.load(storage)? {
+        Some(pending) if pending.effective_at <= env.block.time.seconds() => pending,
+        _ => return Ok(None),
+    };
+    
+    PENDING_SWEEP_LIMIT.save(storage, &None)?;
+    
+    save_sweep_limit(storage, env, pending.limit).map(Some)
+}
+
+/// Make a reserve ratio current immediately
+/// 
+/// # Returns
+/// * The reserve ratio set event to emit
+pub fn save_reserve_ratio(storage: &mut dyn Storage, env: &Env, ratio: Decimal) -> StdResult<ContractEvent> {
+    RESERVE_RATIO.save(storage, &ratio)?;
+    record_activity(storage, env, ActivityKind::ReserveRatioSet { ratio })?;
+    
+    Ok(ContractEvent::ReserveRatioSet { ratio })
+}
+
+/// Apply a pending reserve ratio change whose delay has elapsed
+/// 
+/// # Returns
+/// * The event to emit if a change was applied
+pub fn apply_pending_reserve_ratio(storage: &mut dyn Storage, env: &Env) -> StdResult<Option<ContractEvent>> {
+    let pending = match PENDING_RESERVE_RATIO.load(storage)? {
+        Some(pending) if pending.effective_at <= env.block.time.seconds() => pending,
+        _ => return Ok(None),
+    };
+    
+    PENDING_RESERVE_RATIO.save(storage, &None)?;
+    
+    save_reserve_ratio(storage, env, pending.ratio).map(Some)
+}
+
+/// Reserve ratio in effect at `now`, and the staged change not yet in effect
+pub fn effective_reserve_ratio(storage: &dyn Storage, now: u64) -> StdResult<(Decimal, Option<PendingReserveRatio>)> {
+    match PENDING_RESERVE_RATIO.load(storage)? {
+        Some(pending) if pending.effective_at <= now => Ok((pending.ratio, None)),
+        pending => Ok((RESERVE_RATIO.load(storage)?, pending)),
+    }
+}
+
+/// Sweep limit in effect at `now`, and the staged change not yet in effect
+/// 
+/// A pending change whose delay has elapsed is treated as already applied, like
+/// `effective_destination` does for destinations.
+pub fn effective_sweep_limit(storage: &dyn Storage, now: u64) -> StdResult<(Option<SweepLimit>, Option<PendingSweepLimit>)> {
+    match PENDING_SWEEP_LIMIT.load(storage)? {
+        Some(pending) if pending.effective_at <= now => Ok((pending.limit, None)),
+        pending => Ok((SWEEP_LIMIT.load(storage)?, pending)),
+    }
+}
+
 /// Lightweight consistency check for index storage
 /// 
 /// Verifies basic consistency between forward and reverse index.
@@ -1,5 +1,6 @@
 pub mod contract;
 pub mod error;
+pub mod events;
 pub mod helpers;
 pub mod msg;
 pub mod state;
@@ -8,9 +9,9 @@ pub mod state;
 mod tests;
 
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult};
 
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg};
 
 #[entry_point]
 pub fn instantiate(
@@ -37,3 +38,17 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     crate::contract::query(deps, env, msg)
 }
 
+#[entry_point]
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, crate::error::ContractError> {
+    crate::contract::migrate(deps, env, msg)
+}
+
+#[entry_point]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, crate::error::ContractError> {
+    crate::contract::sudo(deps, env, msg)
+}
+
+#[entry_point]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, crate::error::ContractError> {
+    crate::contract::reply(deps, env, msg)
+}
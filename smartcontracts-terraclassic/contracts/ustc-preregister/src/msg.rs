@@ -1,47 +1,254 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
 use cosmwasm_std::Addr;
+use cosmwasm_std::Decimal;
+use cosmwasm_std::Order;
 use cosmwasm_std::Uint128;
+use cosmwasm_std::{to_json_binary, Binary, CosmosMsg, StdResult, WasmMsg};
+
+use crate::state::{Activity, DailyStats, DestinationShare, HistoryEntry, CampaignCancellation, CampaignStatus, PendingDestination, PendingReserveRatio, PendingSweepLimit, QueuedWithdrawal, RepairCursor, RepairPhase, Role, SweepLimit};
 
 #[cw_serde]
 pub struct InstantiateMsg {
-    pub owner: Addr,
+    pub owner: String,
+    /// Minimum total deposits for the campaign to succeed (optional, set with `deadline`)
+    #[serde(default)]
+    pub soft_cap: Option<Uint128>,
+    /// Time the soft cap is checked, Unix timestamp in seconds (optional, set with `soft_cap`)
+    #[serde(default)]
+    pub deadline: Option<u64>,
+    /// Deposit denomination (optional, default: uusd)
+    #[serde(default)]
+    pub denom: Option<String>,
+    /// Display decimals of the deposit denom (optional, default: 6, max: 18)
+    #[serde(default)]
+    pub decimals: Option<u8>,
+}
+
+#[cw_serde]
+pub struct MigrateMsg {}
+
+/// Governance-only actions, dispatched by the chain through the `sudo` entry point
+/// 
+/// Lets Terra Classic governance step in if the owner key is lost. Sudo messages bypass
+/// every role check.
+#[cw_serde]
+pub enum SudoMsg {
+    /// Make `owner` the owner and the only holder of every role, revoking all other holders
+    ReplaceOwner { owner: String },
+    /// Cancel the campaign so depositors can be refunded
+    ForceCancelCampaign {},
+    /// Pause or unpause deposits
+    ForcePause { paused: bool },
+    /// Set the withdrawal destination immediately, skipping the destination change delay
+    /// 
+    /// A pending destination change is discarded. The unlock timestamp must still be at
+    /// least 7 days in the future.
+    SetWithdrawalDestination {
+        destination: String,
+        #[serde(default)]
+        shares: Vec<UncheckedDestinationShare>,
+        unlock_timestamp: u64,
+    },
 }
 
 #[cw_serde]
 pub enum ExecuteMsg {
     Deposit {},
     Withdraw { amount: Uint128 },
-    /// Owner-only function to withdraw all accumulated USTC tokens
+    /// Treasurer function to withdraw accumulated USTC tokens
     /// 
-    /// Transfers all USTC tokens from the contract to the withdrawal destination
-    /// (set via SetWithdrawalDestination). Requires a 7-day timelock to have passed.
+    /// Transfers USTC tokens from the contract to the withdrawal destination
+    /// (set via SetWithdrawalDestination), split between its shares if it has any.
+    /// Requires a 7-day timelock to have passed.
     /// 
     /// **Important**: This withdraws the contract balance but does NOT modify user
     /// deposit records. User balances remain tracked for future token conversion.
     /// This function can be called multiple times - after withdrawal, if users deposit
     /// additional USTC, the owner can withdraw again (subject to timelock).
-    OwnerWithdraw {},
-    UpdateConfig { owner: Option<Addr> },
-    /// Owner function to set withdrawal destination and unlock timestamp
+    OwnerWithdraw {
+        /// Amount to sweep, or None for the whole contract balance
+        amount: Option<Uint128>,
+    },
+    /// Admin function to transfer ownership
+    /// 
+    /// Every role held by the current owner is moved to the new owner.
+    UpdateConfig { owner: Option<String> },
+    /// Config manager function to set withdrawal destination and unlock timestamp
     /// 
     /// Sets the destination address for timelocked withdrawals and the timestamp
     /// when withdrawal becomes available. The timestamp must be at least 7 days
-    /// (604800 seconds) in the future. Can be called multiple times to update
-    /// the destination, but timestamp always resets to the new value.
+    /// (604800 seconds) in the future. The first destination applies immediately;
+    /// later changes are staged as a pending change that takes effect after a 7-day
+    /// delay and can be cancelled until then. Staging a new change replaces any
+    /// pending one.
     SetWithdrawalDestination {
         /// Address to receive USTC withdrawals
-        destination: Addr,
+        destination: String,
+        /// Optional split of each sweep in basis points, summing to 10,000
+        /// Must include `destination`, which receives the rounding remainder.
+        /// Empty (the default) sends everything to `destination`.
+        #[serde(default)]
+        shares: Vec<UncheckedDestinationShare>,
         /// Unix timestamp (in seconds) when withdrawal becomes available
         /// Must be at least 7 days in the future
         unlock_timestamp: u64,
     },
+    /// Config manager function to configure the deposit distribution brackets
+    /// 
+    /// Thresholds (in uusd) must be non-zero and strictly ascending; N thresholds define
+    /// N+1 brackets. Only allowed while there are no depositors.
+    SetDistributionBrackets { thresholds: Vec<Uint128> },
+    /// Admin function to repair the user enumeration index in resumable batches
+    /// 
+    /// Rebuilds USER_INDEX, USER_INDEX_REVERSE and USER_COUNT from USERS and recomputes
+    /// total deposits. Each call processes up to `limit` entries (default: 50, max: 200)
+    /// and returns a `RepairIndexResponse` in the response data listing every correction
+    /// made. Pass the returned `next_start` as `start` to continue; `start: None` begins
+    /// a new repair. Deposits are rejected until the repair completes.
+    RepairIndex {
+        /// Cursor returned by the previous batch, or None to start a new repair
+        start: Option<RepairIndexCursor>,
+        /// Maximum number of entries to process in this batch (default: 50, max: 200)
+        limit: Option<u32>,
+    },
+    /// Admin function to grant a role to an address
+    /// 
+    /// Granting a role the address already holds is a no-op.
+    GrantRole { role: Role, address: String },
+    /// Admin function to revoke a role from an address
+    /// 
+    /// The last admin cannot be revoked, so the contract always has an admin.
+    RevokeRole { role: Role, address: String },
+    /// Guardian function to pause or unpause deposits
+    /// 
+    /// Withdrawals stay available while paused so users can always exit.
+    SetPaused { paused: bool },
+    /// Config manager or guardian function to cancel a pending destination change
+    CancelPendingDestination {},
+    /// Config manager function to throttle treasurer sweeps
+    /// 
+    /// At most `max_amount` can be swept in any span of `window` seconds: a sweep counts against
+    /// the limit until `window` seconds after it happened. None removes the limit.
+    /// 
+    /// A limit allowing no more over a window no shorter applies immediately and discards any
+    /// staged change. Raising or removing the limit is staged and takes effect 7 days later.
+    SetSweepLimit { limit: Option<SweepLimit> },
+    /// Config manager function to set the share of total deposits sweeps must leave behind
+    /// 
+    /// E.g. 0.1 keeps 10% of TOTAL_DEPOSITS in the contract so users can still withdraw
+    /// after a sweep. Must be at most 1.
+    /// 
+    /// Raising the ratio applies immediately and discards any staged change. Lowering it is
+    /// staged and takes effect 7 days later.
+    SetReserveRatio { ratio: Decimal },
+    /// Admin or treasurer function to return swept USTC and settle queued withdrawals
+    /// 
+    /// Accepts USTC (or no funds, to settle from the current balance) and pays queued
+    /// withdrawals in request order, stopping at the first one the balance cannot cover.
+    ReturnFunds {},
+    /// Admin function to cancel the campaign and refund every depositor
+    /// 
+    /// Deposits and sweeps are rejected from then on. Users can still withdraw themselves,
+    /// and anyone can push the remaining refunds with ProcessRefunds. Cannot be undone.
+    CancelCampaign {},
+    /// Refund up to `limit` depositors of a cancelled or failed campaign (default: 50, max: 100,
+    /// lowered to 50 divided by the number of registered hooks)
+    /// 
+    /// Permissionless. Walks USER_INDEX from the end, sending each user their full balance
+    /// and removing them from the index. Users the balance cannot cover yet are moved into the
+    /// withdraw queue instead. Call repeatedly until no users remain.
+    ProcessRefunds { limit: Option<u32> },
+    /// Admin function to send the contract's whole balance of a stray token to `recipient`
+    /// 
+    /// For native coins or CW20 tokens sent to the contract by mistake. The deposit denom
+    /// cannot be recovered; its balance belongs to depositors and leaves through sweeps.
+    RecoverTokens {
+        denom_or_cw20: RecoverableToken,
+        recipient: String,
+    },
+    /// Config manager function to change the deposit denomination and its display decimals
+    /// 
+    /// Only allowed while TOTAL_DEPOSITS is zero and no withdrawal is queued, since
+    /// existing balances are denominated in the old denom.
+    SetDenom { denom: String, decimals: u8 },
+    /// Admin function to register a contract notified of every balance change
+    /// 
+    /// Each deposit, withdrawal and refund sends the hook a `BalanceChangedExecuteMsg`.
+    /// A failing hook is reported with a `hook_failed` event and does not revert the
+    /// balance change. At most 10 hooks can be registered.
+    AddHook { addr: String },
+    /// Admin function to unregister a balance change hook
+    RemoveHook { addr: String },
+}
+
+/// Share of each sweep sent to one address, as passed in messages
+/// 
+/// Validated into the stored `DestinationShare`.
+#[cw_serde]
+pub struct UncheckedDestinationShare {
+    pub address: String,
+    /// Share in basis points (1/10,000 of the swept amount)
+    pub bps: u16,
+}
+
+/// Balance change of one user, sent to every registered hook
+/// 
+/// Modeled on the cw4 `MemberChangedHookMsg`.
+#[cw_serde]
+pub struct BalanceChangedHookMsg {
+    pub user: String,
+    pub old_balance: Uint128,
+    pub new_balance: Uint128,
+}
+
+impl BalanceChangedHookMsg {
+    /// Serializes the message wrapped in `BalanceChangedExecuteMsg`
+    pub fn into_json_binary(self) -> StdResult<Binary> {
+        to_json_binary(&BalanceChangedExecuteMsg::BalanceChangedHook(self))
+    }
+    
+    /// Creates the message to send to a hook contract
+    pub fn into_cosmos_msg(self, contract_addr: String) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr,
+            msg: self.into_json_binary()?,
+            funds: vec![],
+        }
+        .into())
+    }
+}
+
+/// Execute message hook contracts must accept
+#[cw_serde]
+pub enum BalanceChangedExecuteMsg {
+    BalanceChangedHook(BalanceChangedHookMsg),
+}
+
+/// Token held by the contract outside of the deposit accounting
+#[cw_serde]
+pub enum RecoverableToken {
+    /// Native coin, by denom
+    Native { denom: String },
+    /// CW20 token, by contract address
+    Cw20 { address: String },
 }
 
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
     #[returns(GetUserDepositResponse)]
-    GetUserDeposit { user: Addr },
+    GetUserDeposit { user: String },
+    
+    /// Get deposits for a list of addresses in one call
+    /// 
+    /// Returns one entry per requested address, in request order, with the deposit,
+    /// position in the user index and time of the first deposit. Addresses are validated
+    /// individually; invalid entries carry an `error` instead of failing the query.
+    /// 
+    /// # Parameters
+    /// * `users` - Addresses to look up (at most 100)
+    #[returns(GetUserDepositsResponse)]
+    GetUserDeposits { users: Vec<String> },
     
     /// Get all users with pagination support
     /// 
@@ -63,11 +270,32 @@ pub enum QueryMsg {
     #[returns(GetAllUsersResponse)]
     GetAllUsers { 
         /// User address to start pagination after (cursor for next page)
-        start_after: Option<Addr>, 
+        start_after: Option<String>, 
         /// Maximum number of results to return (default: 30, max: 100)
         limit: Option<u32> 
     },
     
+    /// Get all users ordered by address, with pagination support
+    /// 
+    /// Ranges over user storage by address instead of walking the enumeration index,
+    /// so pages are stable while users deposit or withdraw:
+    /// - `start_after` may be any address, including one that has since withdrawn everything
+    /// - every user holding a deposit for the whole scan appears exactly once
+    /// 
+    /// Prefer this over GetAllUsers for full exports. GetAllUsers is kept for backward
+    /// compatibility.
+    /// 
+    /// # Parameters
+    /// * `start_after` - Optional address to start pagination after (need not be a current user)
+    /// * `limit` - Optional limit on number of results (default: 30, max: 100)
+    #[returns(GetAllUsersResponse)]
+    GetAllUsersByAddress {
+        /// Address to start pagination after (cursor for next page)
+        start_after: Option<String>,
+        /// Maximum number of results to return (default: 30, max: 100)
+        limit: Option<u32>,
+    },
+    
     #[returns(GetUserCountResponse)]
     GetUserCount {},
     
@@ -82,11 +310,22 @@ pub enum QueryMsg {
     /// Checks that the index storage is consistent:
     /// - user_count matches actual number of users in index
     /// - All indices are valid and point to users with non-zero balances
-    /// - Reverse index matches forward index
+    /// - Reverse index matches forward index, with no orphaned reverse entries
+    /// - Every user in USERS is present in the index
+    /// - Sum of all user balances matches total deposits
+    /// 
+    /// Validation is paginated: each query checks up to `limit` entries (default: 100,
+    /// max: 500) and returns the issues found in that page plus a cursor. Pass the
+    /// returned `next_start` as `start` until `complete` is true.
     /// 
     /// This is useful for debugging and ensuring data integrity.
     #[returns(ValidateIndexResponse)]
-    ValidateIndex {},
+    ValidateIndex {
+        /// Cursor returned by the previous page, or None to start from the beginning
+        start: Option<ValidateIndexCursor>,
+        /// Maximum number of entries to check in this page (default: 100, max: 500)
+        limit: Option<u32>,
+    },
     
     /// Get withdrawal information
     /// 
@@ -94,6 +333,220 @@ pub enum QueryMsg {
     /// withdrawal is configured (both destination and timestamp are set).
     #[returns(GetWithdrawalInfoResponse)]
     GetWithdrawalInfo {},
+    
+    /// Get the current and pending withdrawal destination
+    /// 
+    /// Shows the destination in effect with its unlock timestamp, and any staged change
+    /// with the time it takes effect.
+    #[returns(GetDestinationScheduleResponse)]
+    GetDestinationSchedule {},
+    
+    /// Get depositors ordered by balance, largest first
+    /// 
+    /// Ranges over the `(deposit, addr)` secondary index, so pages are cheap regardless
+    /// of the number of users. Depositors with equal balances are ordered by address
+    /// (descending) so the ordering is total and stable between pages.
+    /// 
+    /// # Parameters
+    /// * `start_after` - Optional user address to start pagination after (the `next` cursor)
+    /// * `limit` - Optional limit on number of results (default: 30, max: 100)
+    #[returns(GetTopDepositorsResponse)]
+    GetTopDepositors {
+        /// User address to start pagination after (cursor for next page)
+        start_after: Option<String>,
+        /// Maximum number of results to return (default: 30, max: 100)
+        limit: Option<u32>,
+    },
+    
+    /// Get a user's position in the balance ranking
+    /// 
+    /// Rank 1 is the largest depositor, using the same ordering as GetTopDepositors.
+    /// Returns `rank: None` for users without a deposit. Ranks are computed down to
+    /// 1000; users ranked lower get `rank: None` and `beyond_max_rank: true`.
+    #[returns(GetUserRankResponse)]
+    GetUserRank { user: String },
+    
+    /// Get a user's deposit and withdrawal history
+    /// 
+    /// Every deposit and withdrawal appends an entry with the block height, block time,
+    /// amount and resulting balance. History is kept after the user withdraws everything.
+    /// 
+    /// # Parameters
+    /// * `user` - User address to query
+    /// * `start_after` - Optional sequence number to start pagination after
+    /// * `limit` - Optional limit on number of results (default: 30, max: 100)
+    /// * `order` - Optional ordering by sequence (default: `asc`, oldest first)
+    #[returns(GetUserHistoryResponse)]
+    GetUserHistory {
+        user: String,
+        /// Sequence number to start pagination after (cursor for next page)
+        start_after: Option<u64>,
+        /// Maximum number of results to return (default: 30, max: 100)
+        limit: Option<u32>,
+        /// Sort order (default: asc)
+        order: Option<OrderBy>,
+    },
+    
+    /// Get entries from the global activity ledger
+    /// 
+    /// Every deposit, withdrawal, owner withdrawal and configuration change is recorded
+    /// with a global sequence number that increases by exactly one per entry. Indexers
+    /// can sync incrementally by passing the last sequence they processed as
+    /// `start_after_seq`, and detect gaps by checking sequences are contiguous.
+    /// 
+    /// # Parameters
+    /// * `start_after_seq` - Optional sequence number to start after
+    /// * `limit` - Optional limit on number of results (default: 30, max: 100)
+    #[returns(GetActivityResponse)]
+    GetActivity {
+        /// Sequence number to start after (last sequence already processed)
+        start_after_seq: Option<u64>,
+        /// Maximum number of results to return (default: 30, max: 100)
+        limit: Option<u32>,
+    },
+    
+    /// Get the sequence number of the latest activity entry
+    #[returns(GetLatestSequenceResponse)]
+    GetLatestSequence {},
+    
+    /// Get daily aggregate statistics
+    /// 
+    /// Returns deposit volume, withdrawal volume, new users, exited users and closing
+    /// total deposits per day, for days between `from_day` and `to_day` (inclusive) that
+    /// had activity. Days are numbered as `block time / 86400`. At most 100 buckets are
+    /// returned; if the range holds more, `next_from_day` is set and the rest is fetched by
+    /// querying again with it as `from_day`.
+    #[returns(GetDailyStatsResponse)]
+    GetDailyStats {
+        /// First day to include (Unix timestamp / 86400)
+        from_day: u64,
+        /// Last day to include (Unix timestamp / 86400)
+        to_day: u64,
+    },
+    
+    /// Get the deposit size distribution
+    /// 
+    /// Returns user count and volume per balance bracket, along with concentration
+    /// figures (per-bracket shares and a Gini coefficient) computed from the brackets.
+    #[returns(GetDistributionResponse)]
+    GetDistribution {},
+    
+    /// Get the holders of every role
+    #[returns(GetRolesResponse)]
+    GetRoles {},
+    
+    /// Get how much the treasurer can sweep now and when the sweep window resets
+    #[returns(GetSweepAllowanceResponse)]
+    GetSweepAllowance {},
+    
+    /// Get the contract balance available for user withdrawals and the reserve sweeps must leave
+    #[returns(GetLiquidityResponse)]
+    GetLiquidity {},
+    
+    /// Get a user's queued withdrawals and their positions in the queue
+    #[returns(GetQueuePositionResponse)]
+    GetQueuePosition { user: String },
+    
+    /// Get the withdrawal queue in request order
+    /// 
+    /// # Parameters
+    /// * `start_after` - Optional queue id to start pagination after
+    /// * `limit` - Optional limit on number of results (default: 30, max: 100)
+    #[returns(GetWithdrawQueueResponse)]
+    GetWithdrawQueue {
+        /// Queue id to start pagination after (cursor for next page)
+        start_after: Option<u64>,
+        /// Maximum number of results to return (default: 30, max: 100)
+        limit: Option<u32>,
+    },
+    
+    /// Get the cancellation snapshot and how many users are still to be refunded
+    #[returns(GetRefundProgressResponse)]
+    GetRefundProgress {},
+    
+    /// Get the campaign status against its soft cap and deadline
+    #[returns(GetCampaignStatusResponse)]
+    GetCampaignStatus {},
+    
+    /// Get the contracts notified of balance changes
+    #[returns(ListHooksResponse)]
+    ListHooks {},
+}
+
+/// Sort order for paginated queries
+#[cw_serde]
+pub enum OrderBy {
+    Asc,
+    Desc,
+}
+
+impl From<OrderBy> for Order {
+    fn from(order: OrderBy) -> Self {
+        match order {
+            OrderBy::Asc => Order::Ascending,
+            OrderBy::Desc => Order::Descending,
+        }
+    }
+}
+
+/// Pass of a paginated index validation
+#[cw_serde]
+pub enum ValidatePhase {
+    /// Walk USER_INDEX by position, checking balances, reverse entries and gaps
+    Index,
+    /// Walk USER_INDEX_REVERSE by address, checking for orphaned entries
+    Reverse,
+    /// Walk USERS by address, checking every depositor is indexed and summing balances
+    Users,
+}
+
+/// Position of a paginated index validation
+/// 
+/// Carries the running tallies needed for the final count and total checks,
+/// so it must be passed back unchanged.
+#[cw_serde]
+pub struct ValidateIndexCursor {
+    pub phase: ValidatePhase,
+    /// Last USER_INDEX position checked (Index phase)
+    pub after_position: Option<u32>,
+    /// Last address checked (Reverse and Users phases)
+    pub after_address: Option<String>,
+    /// Index entries seen so far
+    pub total_users_in_index: u32,
+    /// Index entries pointing to users with non-zero balance seen so far
+    pub user_count_actual: u32,
+    /// Sum of user balances seen so far (Users phase)
+    pub total_deposits_actual: Uint128,
+}
+
+/// Position of an in-progress index repair, as passed to and returned by RepairIndex
+#[cw_serde]
+pub struct RepairIndexCursor {
+    pub phase: RepairPhase,
+    /// Last USER_INDEX position processed (Index phase)
+    pub after_position: Option<u32>,
+    /// Last address processed (Users and Reverse phases)
+    pub after_address: Option<String>,
+}
+
+impl From<RepairCursor> for RepairIndexCursor {
+    fn from(cursor: RepairCursor) -> Self {
+        RepairIndexCursor {
+            phase: cursor.phase,
+            after_position: cursor.after_position,
+            after_address: cursor.after_address.map(String::from),
+        }
+    }
+}
+
+/// Response data of a RepairIndex batch
+#[cw_serde]
+pub struct RepairIndexResponse {
+    /// Human-readable description of every correction made in this batch
+    pub corrections: Vec<String>,
+    /// Cursor to pass as `start` for the next batch, None once the repair is complete
+    pub next_start: Option<RepairIndexCursor>,
+    pub complete: bool,
 }
 
 // Response types
@@ -103,6 +556,24 @@ pub struct GetUserDepositResponse {
     pub deposit: Uint128,
 }
 
+#[cw_serde]
+pub struct UserDepositEntry {
+    /// Address as submitted in the query
+    pub user: String,
+    /// Current deposit (zero if none), None if the address is invalid
+    pub deposit: Option<Uint128>,
+    /// Position in the user index, None if the user has no deposit
+    pub index: Option<u32>,
+    /// Time of the user's first deposit (Unix timestamp in seconds), None if never deposited
+    pub first_deposit_time: Option<u64>,
+    /// Validation error for this address, if any
+    pub error: Option<String>,
+}
+
+#[cw_serde]
+pub struct GetUserDepositsResponse {
+    pub deposits: Vec<UserDepositEntry>,
+}
 
 #[cw_serde]
 pub struct GetUserCountResponse {
@@ -124,21 +595,217 @@ pub struct GetTotalDepositsResponse {
 pub struct GetConfigResponse {
     pub owner: Addr,
     pub ustc_denom: String,
+    /// Display decimals of the deposit denom
+    pub decimals: u8,
+    /// True while deposits are paused
+    pub paused: bool,
+}
+
+#[cw_serde]
+pub struct RoleHolders {
+    pub role: Role,
+    pub holders: Vec<Addr>,
+}
+
+#[cw_serde]
+pub struct GetRolesResponse {
+    /// One entry per role, including roles without holders
+    pub roles: Vec<RoleHolders>,
+}
+
+#[cw_serde]
+pub struct GetLiquidityResponse {
+    /// Contract balance, the most users can withdraw right now
+    pub balance: Uint128,
+    /// Sum of all user balances
+    pub total_deposits: Uint128,
+    /// Share of total deposits sweeps must leave behind
+    pub reserve_ratio: Decimal,
+    /// Staged lowering of the ratio not yet in effect
+    pub pending_reserve_ratio: Option<PendingReserveRatio>,
+    /// Amount sweeps must leave behind (total deposits times the ratio, rounded up)
+    pub required_reserve: Uint128,
+    /// Amount owed to queued withdrawals
+    pub queued: Uint128,
+    /// Balance above the reserve and the queued withdrawals, the most a sweep can take
+    pub sweepable: Uint128,
+}
+
+#[cw_serde]
+pub struct QueuePosition {
+    pub entry: QueuedWithdrawal,
+    /// Number of entries ahead in the queue (0 is paid first)
+    pub position: u64,
+}
+
+#[cw_serde]
+pub struct GetQueuePositionResponse {
+    pub user: Addr,
+    /// The user's queued withdrawals, oldest first
+    pub entries: Vec<QueuePosition>,
+    /// Amount owed to all queued withdrawals
+    pub total_queued: Uint128,
+}
+
+#[cw_serde]
+pub struct GetWithdrawQueueResponse {
+    pub entries: Vec<QueuePosition>,
+    /// Number of entries in the queue
+    pub length: u64,
+    /// Amount owed to all queued withdrawals
+    pub total_queued: Uint128,
+    /// Queue id to pass as `start_after` for the next page
+    pub next: Option<u64>,
+}
+
+#[cw_serde]
+pub struct GetRefundProgressResponse {
+    /// Cancellation snapshot and refund totals, or None if the campaign is not cancelled
+    pub cancellation: Option<CampaignCancellation>,
+    /// Users still holding a balance
+    pub remaining_users: u32,
+    /// Sum of the balances still to be refunded
+    pub remaining_deposits: Uint128,
+}
+
+#[cw_serde]
+pub struct ListHooksResponse {
+    pub hooks: Vec<Addr>,
+}
+
+#[cw_serde]
+pub struct GetCampaignStatusResponse {
+    pub status: CampaignStatus,
+    pub soft_cap: Option<Uint128>,
+    pub deadline: Option<u64>,
+    pub total_deposits: Uint128,
+    /// Amount still missing to reach the soft cap (0 once reached or without a target)
+    pub shortfall: Uint128,
+}
+
+#[cw_serde]
+pub struct GetSweepAllowanceResponse {
+    /// Sweep limit, or None if sweeps are not throttled
+    pub limit: Option<SweepLimit>,
+    /// Amount swept in the last `window` seconds
+    pub swept_in_window: Uint128,
+    /// Amount that can be swept now: the contract balance, capped by what is left in the window
+    pub available: Uint128,
+    /// Time the oldest sweep in the window stops counting, or None if nothing was swept in it
+    pub resets_at: Option<u64>,
+    /// Staged raise or removal of the limit not yet in effect
+    pub pending: Option<PendingSweepLimit>,
 }
 
 #[cw_serde]
 pub struct ValidateIndexResponse {
+    /// True if no issues were found in this page
     pub is_consistent: bool,
+    /// Issues found in this page
     pub issues: Vec<String>,
     pub user_count_stored: u32,
+    /// Running tallies, final once `complete` is true
     pub user_count_actual: u32,
     pub total_users_in_index: u32,
+    pub total_deposits_stored: Uint128,
+    pub total_deposits_actual: Uint128,
+    /// Cursor to pass as `start` for the next page, None once validation is complete
+    pub next_start: Option<ValidateIndexCursor>,
+    /// True once every pass has finished
+    pub complete: bool,
+}
+
+#[cw_serde]
+pub struct GetTopDepositorsResponse {
+    /// `(address, deposit)` tuples, largest deposit first
+    pub depositors: Vec<(Addr, Uint128)>,
+    pub next: Option<Addr>, // For pagination
+}
+
+#[cw_serde]
+pub struct GetUserRankResponse {
+    pub user: Addr,
+    pub deposit: Uint128,
+    /// 1-based rank by deposit, or None if the user has no deposit or ranks below 1000
+    pub rank: Option<u32>,
+    /// True if the user has a deposit but more than 999 depositors ahead, so the rank
+    /// was not computed
+    pub beyond_max_rank: bool,
+    /// Total number of ranked users
+    pub user_count: u32,
+}
+
+#[cw_serde]
+pub struct GetUserHistoryResponse {
+    pub user: Addr,
+    pub entries: Vec<HistoryEntry>,
+    /// Sequence number to pass as `start_after` for the next page
+    pub next: Option<u64>,
+}
+
+#[cw_serde]
+pub struct GetActivityResponse {
+    pub activities: Vec<Activity>,
+    /// Latest sequence number at query time; more entries exist if greater than the last returned
+    pub latest_sequence: u64,
+}
+
+#[cw_serde]
+pub struct GetLatestSequenceResponse {
+    pub sequence: u64,
+}
+
+#[cw_serde]
+pub struct GetDailyStatsResponse {
+    /// `(day, stats)` tuples in day order
+    pub days: Vec<(u64, DailyStats)>,
+    /// `from_day` to continue with if the range holds more buckets, None if all were returned
+    pub next_from_day: Option<u64>,
+}
+
+#[cw_serde]
+pub struct DistributionBracket {
+    /// Lower bound of the bracket (inclusive)
+    pub min: Uint128,
+    /// Upper bound of the bracket (exclusive), None for the last bracket
+    pub max: Option<Uint128>,
+    pub count: u32,
+    pub volume: Uint128,
+    /// Fraction of all depositors in this bracket
+    pub user_share: Decimal,
+    /// Fraction of total deposits held by this bracket
+    pub volume_share: Decimal,
+}
+
+#[cw_serde]
+pub struct GetDistributionResponse {
+    pub brackets: Vec<DistributionBracket>,
+    pub total_users: u32,
+    pub total_volume: Uint128,
+    /// Gini coefficient between brackets (0 = equal, towards 1 = concentrated)
+    pub gini: Decimal,
+}
+
+#[cw_serde]
+pub struct GetDestinationScheduleResponse {
+    /// Destination sweeps currently go to, or None if not set
+    pub current: Option<Addr>,
+    /// Split of each sweep, empty if everything goes to the current destination
+    pub current_shares: Vec<DestinationShare>,
+    /// Time from which sweeps to the current destination are allowed
+    pub current_unlock_timestamp: u64,
+    /// Staged change not yet in effect
+    pub pending: Option<PendingDestination>,
+    /// Delay in seconds between staging a change and it taking effect
+    pub change_delay: u64,
 }
 
 #[cw_serde]
 pub struct GetWithdrawalInfoResponse {
     /// Withdrawal destination address, or None if not set
     pub destination: Option<Addr>,
+    /// Split of each sweep, empty if everything goes to the destination
+    pub shares: Vec<DestinationShare>,
     /// Unlock timestamp (Unix timestamp in seconds), or 0 if not set
     pub unlock_timestamp: u64,
     /// Whether withdrawal is configured (both destination and timestamp are set)
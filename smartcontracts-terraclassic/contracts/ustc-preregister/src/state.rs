@@ -1,17 +1,61 @@
-use cosmwasm_std::Addr;
-use cw_storage_plus::{Item, Map};
+use std::fmt;
+use std::str::FromStr;
+
+use cosmwasm_std::{Addr, Empty};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Decimal, Uint128};
+
+/// Display decimals of the deposit denom when none are configured (uusd has 6)
+pub const DEFAULT_DECIMALS: u8 = 6;
+
+fn default_decimals() -> u8 {
+    DEFAULT_DECIMALS
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Config {
     pub owner: Addr,
+    /// Deposit denomination, `uusd` unless configured otherwise
     pub ustc_denom: String,
+    /// Display decimals of the deposit denom, for frontends only
+    #[serde(default = "default_decimals")]
+    pub decimals: u8,
+    /// Minimum total deposits for the campaign to succeed, None if there is no target
+    #[serde(default)]
+    pub soft_cap: Option<Uint128>,
+    /// Time the soft cap is checked (Unix timestamp in seconds), set together with `soft_cap`
+    #[serde(default)]
+    pub deadline: Option<u64>,
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
-pub const USERS: Map<&Addr, Uint128> = Map::new("users");
+
+/// Secondary indexes over USERS
+///
+/// `deposit` is keyed by `(deposit, addr)` so depositors can be ranged in balance order
+/// without exporting the full user list. It is maintained automatically by IndexedMap
+/// on every save/remove of a user balance.
+pub struct UserIndexes<'a> {
+    pub deposit: MultiIndex<'a, u128, Uint128, Addr>,
+}
+
+impl<'a> IndexList<Uint128> for UserIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Uint128>> + '_> {
+        let v: Vec<&dyn Index<Uint128>> = vec![&self.deposit];
+        Box::new(v.into_iter())
+    }
+}
+
+/// User deposits, indexed by balance for leaderboard queries
+pub const USERS: IndexedMap<&Addr, Uint128, UserIndexes> = IndexedMap::new(
+    "users",
+    UserIndexes {
+        deposit: MultiIndex::new(|_pk, deposit| deposit.u128(), "users", "users__deposit"),
+    },
+);
+
 pub const TOTAL_DEPOSITS: Item<Uint128> = Item::new("total_deposits");
 pub const USER_COUNT: Item<u32> = Item::new("user_count");
 
@@ -21,6 +65,53 @@ pub const USER_INDEX: Map<u32, Addr> = Map::new("user_idx");
 // Reverse mapping: user_address => index
 pub const USER_INDEX_REVERSE: Map<&Addr, u32> = Map::new("user_idx_rev");
 
+/// User withdrawal waiting for the contract to hold enough funds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct QueuedWithdrawal {
+    /// Queue id, assigned in request order
+    pub id: u64,
+    pub user: Addr,
+    pub amount: Uint128,
+    /// Time of the request (Unix timestamp in seconds)
+    pub queued_at: u64,
+}
+
+/// Secondary indexes over WITHDRAW_QUEUE
+pub struct QueueIndexes<'a> {
+    pub user: MultiIndex<'a, Addr, QueuedWithdrawal, u64>,
+}
+
+impl<'a> IndexList<QueuedWithdrawal> for QueueIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<QueuedWithdrawal>> + '_> {
+        let v: Vec<&dyn Index<QueuedWithdrawal>> = vec![&self.user];
+        Box::new(v.into_iter())
+    }
+}
+
+/// FIFO queue of user withdrawals the contract could not pay when requested, keyed by id
+/// The user balance is deducted when the request is queued; the entry is the amount owed.
+/// Entries are only removed from the head, so ids from `WithdrawQueueState::head` to
+/// `next` are contiguous and an entry's position is its id minus the head.
+pub const WITHDRAW_QUEUE: IndexedMap<u64, QueuedWithdrawal, QueueIndexes> = IndexedMap::new(
+    "withdraw_queue",
+    QueueIndexes {
+        user: MultiIndex::new(|_pk, entry| entry.user.clone(), "withdraw_queue", "withdraw_queue__user"),
+    },
+);
+
+/// Bounds and total of WITHDRAW_QUEUE
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct WithdrawQueueState {
+    /// Id of the oldest entry still queued (equals `next` when the queue is empty)
+    pub head: u64,
+    /// Id assigned to the next queued entry
+    pub next: u64,
+    /// Sum of all queued amounts, owed to users before anything can be swept
+    pub total: Uint128,
+}
+
+pub const WITHDRAW_QUEUE_STATE: Item<WithdrawQueueState> = Item::new("withdraw_queue_state");
+
 /// Withdrawal destination address for timelocked withdrawals
 /// Initialized to None, must be set by owner before withdrawal is possible
 pub const WITHDRAWAL_DESTINATION: Item<Option<Addr>> = Item::new("withdrawal_dest");
@@ -30,3 +121,395 @@ pub const WITHDRAWAL_DESTINATION: Item<Option<Addr>> = Item::new("withdrawal_des
 /// Must be at least 7 days (604800 seconds) in the future when set
 pub const WITHDRAWAL_UNLOCK_TIMESTAMP: Item<u64> = Item::new("withdrawal_unlock");
 
+/// Basis points making up a whole sweep
+pub const TOTAL_BPS: u16 = 10_000;
+
+/// Share of each sweep sent to one address
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DestinationShare {
+    pub address: Addr,
+    /// Share in basis points (1/10,000 of the swept amount)
+    pub bps: u16,
+}
+
+/// Split of each sweep between several addresses, shares summing to TOTAL_BPS
+/// Empty means the whole sweep goes to WITHDRAWAL_DESTINATION, which is always one of
+/// the shares otherwise and receives the rounding remainder.
+pub const WITHDRAWAL_SHARES: Item<Vec<DestinationShare>> = Item::new("withdrawal_shares");
+
+/// Staged change of the withdrawal destination
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingDestination {
+    pub destination: Addr,
+    /// Split that applies together with the destination, empty for a single destination
+    #[serde(default)]
+    pub shares: Vec<DestinationShare>,
+    /// Unlock timestamp that applies once the change takes effect
+    pub unlock_timestamp: u64,
+    /// Time (Unix timestamp in seconds) from which the change takes effect
+    pub effective_at: u64,
+}
+
+/// Pending withdrawal destination change, or None if no change is staged
+/// Replacing an existing destination is delayed so a compromised key cannot redirect
+/// sweeps without a public warning period during which the change can be cancelled.
+/// Promoted to WITHDRAWAL_DESTINATION lazily, the first time it is used after `effective_at`.
+pub const PENDING_DESTINATION: Item<Option<PendingDestination>> = Item::new("pending_withdrawal_dest");
+
+/// Maximum amount the treasurer can sweep per window
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct SweepLimit {
+    pub max_amount: Uint128,
+    /// Window length in seconds
+    pub window: u64,
+}
+
+/// Share of TOTAL_DEPOSITS that sweeps must leave in the contract for user withdrawals
+/// Zero (the default) lets the treasurer sweep the whole balance.
+pub const RESERVE_RATIO: Item<Decimal> = Item::new("reserve_ratio");
+
+/// Staged lowering of the reserve ratio
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingReserveRatio {
+    pub ratio: Decimal,
+    /// Time (Unix timestamp in seconds) from which the change takes effect
+    pub effective_at: u64,
+}
+
+/// Pending reserve ratio change, or None if no change is staged
+/// Lowering the ratio is delayed so depositors see it coming before more can be swept.
+/// Promoted to RESERVE_RATIO lazily, the first time it is used after `effective_at`.
+pub const PENDING_RESERVE_RATIO: Item<Option<PendingReserveRatio>> = Item::new("pending_reserve_ratio");
+
+/// Sweep limit, or None if sweeps are not throttled
+pub const SWEEP_LIMIT: Item<Option<SweepLimit>> = Item::new("sweep_limit");
+
+/// Staged loosening of the sweep limit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingSweepLimit {
+    /// Limit that applies once the change takes effect, None if the limit is removed
+    pub limit: Option<SweepLimit>,
+    /// Time (Unix timestamp in seconds) from which the change takes effect
+    pub effective_at: u64,
+}
+
+/// Pending sweep limit change, or None if no change is staged
+/// Raising or removing the limit is delayed so depositors see it coming before sweeps
+/// speed up. Promoted to SWEEP_LIMIT lazily, the first time it is used after `effective_at`.
+pub const PENDING_SWEEP_LIMIT: Item<Option<PendingSweepLimit>> = Item::new("pending_sweep_limit");
+
+/// Amount swept per block time (Unix timestamp in seconds)
+/// The limit applies to the trailing `SweepLimit::window` seconds: a sweep counts against it until
+/// `window` seconds after it happened. Older entries are pruned on the next sweep.
+pub const SWEEP_LOG: Map<u64, Uint128> = Map::new("sweep_log");
+
+
+/// Access control role
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Grants and revokes roles, transfers ownership and repairs the index
+    Admin,
+    /// Sweeps funds to the withdrawal destination (OwnerWithdraw)
+    Treasurer,
+    /// Sets the withdrawal destination and distribution brackets
+    ConfigManager,
+    /// Pauses and unpauses deposits
+    Guardian,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Admin, Role::Treasurer, Role::ConfigManager, Role::Guardian];
+    
+    /// Storage key and display name of the role
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Treasurer => "treasurer",
+            Role::ConfigManager => "config_manager",
+            Role::Guardian => "guardian",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("unknown role {}", s))
+    }
+}
+
+/// Role holders: (role, address) => Empty
+/// A role can have any number of holders; the owner holds all roles after instantiation
+pub const ROLES: Map<(&str, &Addr), Empty> = Map::new("roles");
+
+/// Maximum number of registered balance change hooks, each one is called on every
+/// balance change
+pub const MAX_HOOKS: u32 = 10;
+
+/// Gas each hook call may use; a hook running out of it fails like any other hook error
+pub const HOOK_GAS_LIMIT: u64 = 500_000;
+
+/// Contracts notified of every balance change: address => reply id
+pub const HOOKS: Map<&Addr, u64> = Map::new("hooks");
+
+/// Hook of every reply id ever assigned: reply id => address
+/// Entries are kept when a hook is removed, so a reply always names the hook that failed;
+/// ids are never reused
+pub const HOOK_REPLY_IDS: Map<u64, Addr> = Map::new("hook_reply_ids");
+
+/// Deposits are rejected while paused, withdrawals are always allowed
+pub const PAUSED: Item<bool> = Item::new("paused");
+
+/// Kind of balance change recorded in a user's history
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    Deposit,
+    Withdraw,
+}
+
+/// Single entry in a user's deposit/withdrawal history
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct HistoryEntry {
+    /// Per-user sequence number, starting at 0 for the user's first deposit
+    pub sequence: u64,
+    /// Block height of the transaction
+    pub height: u64,
+    /// Block time of the transaction (Unix timestamp in seconds)
+    pub time: u64,
+    pub kind: HistoryKind,
+    pub amount: Uint128,
+    /// User balance after this entry was applied
+    pub resulting_balance: Uint128,
+}
+
+/// Append-only history of balance changes: (user, sequence) => entry
+pub const USER_HISTORY: Map<(&Addr, u64), HistoryEntry> = Map::new("user_history");
+
+/// Next history sequence number per user (equals the number of entries recorded)
+/// Kept after the user withdraws everything so sequences are never reused
+pub const USER_HISTORY_SEQ: Map<&Addr, u64> = Map::new("user_history_seq");
+
+/// State change recorded in the global activity ledger
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Deposit { user: Addr, amount: Uint128 },
+    Withdraw { user: Addr, amount: Uint128 },
+    OwnerWithdraw { destination: Addr, amount: Uint128 },
+    ConfigUpdate { owner: Addr },
+    WithdrawalDestinationSet {
+        destination: Addr,
+        #[serde(default)]
+        shares: Vec<DestinationShare>,
+        unlock_timestamp: u64,
+    },
+    WithdrawalDestinationProposed {
+        destination: Addr,
+        #[serde(default)]
+        shares: Vec<DestinationShare>,
+        unlock_timestamp: u64,
+        effective_at: u64,
+    },
+    PendingDestinationCancelled { destination: Addr },
+    RoleGranted { role: Role, address: Addr },
+    RoleRevoked { role: Role, address: Addr },
+    PausedSet { paused: bool },
+    SweepLimitSet { limit: Option<SweepLimit> },
+    SweepLimitProposed { limit: Option<SweepLimit>, effective_at: u64 },
+    ReserveRatioSet { ratio: Decimal },
+    ReserveRatioProposed { ratio: Decimal, effective_at: u64 },
+    WithdrawalQueued { id: u64, user: Addr, amount: Uint128 },
+    QueuedWithdrawalPaid { id: u64, user: Addr, amount: Uint128 },
+    FundsReturned { sender: Addr, amount: Uint128 },
+    CampaignCancelled { user_count: u32, total_deposits: Uint128 },
+    Refund { user: Addr, amount: Uint128 },
+    CampaignSettled { succeeded: bool, total_deposits: Uint128 },
+    TokensRecovered { token: String, recipient: Addr, amount: Uint128 },
+    DenomSet { denom: String, decimals: u8 },
+    HookAdded { hook: Addr },
+    HookRemoved { hook: Addr },
+    HookFailed { hook: Addr },
+    DistributionBracketsSet { thresholds: Vec<Uint128> },
+    /// One RepairIndex batch, with total deposits after it; a completed repair may have
+    /// rewritten total deposits, the user count and the distribution
+    IndexRepaired { complete: bool, corrections: u32, total: Uint128 },
+}
+
+/// Single entry in the global activity ledger
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Activity {
+    /// Global sequence number, starting at 1 and increasing by exactly 1 per entry
+    pub sequence: u64,
+    /// Block height of the transaction
+    pub height: u64,
+    /// Block time of the transaction (Unix timestamp in seconds)
+    pub time: u64,
+    pub kind: ActivityKind,
+}
+
+/// Sequence number of the latest activity entry (0 if nothing has happened yet)
+pub const SEQUENCE: Item<u64> = Item::new("sequence");
+
+/// Global activity ledger: sequence => activity
+/// Off-chain consumers can sync incrementally and detect gaps by sequence number
+pub const ACTIVITY: Map<u64, Activity> = Map::new("activity");
+
+/// Aggregate statistics for one UTC day
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct DailyStats {
+    /// Total USTC deposited during the day
+    pub deposit_volume: Uint128,
+    /// Total USTC withdrawn by users during the day
+    pub withdrawal_volume: Uint128,
+    /// Users whose balance went from zero to non-zero
+    pub new_users: u32,
+    /// Users whose balance went from non-zero to zero
+    pub exited_users: u32,
+    /// Total deposits after the last change of the day
+    pub closing_total: Uint128,
+}
+
+/// Daily statistics buckets: day number (block time / 86400) => stats
+/// Days without any deposit or withdrawal have no bucket
+pub const DAILY_STATS: Map<u64, DailyStats> = Map::new("daily_stats");
+
+/// Number of depositors and total deposits within one distribution bracket
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct BracketStats {
+    pub count: u32,
+    pub volume: Uint128,
+}
+
+/// Owner-configured bracket boundaries for the deposit distribution, strictly ascending
+/// N thresholds define N+1 brackets: [0, t0), [t0, t1), ..., [tN-1, infinity)
+pub const DISTRIBUTION_THRESHOLDS: Item<Vec<Uint128>> = Item::new("dist_thresholds");
+
+/// Deposit distribution: bracket index => stats
+/// Users are moved between brackets whenever their balance changes
+pub const DISTRIBUTION: Map<u32, BracketStats> = Map::new("distribution");
+
+/// Distribution recomputed from USERS by an in-progress index repair, replacing
+/// DISTRIBUTION once the repair completes
+pub const DISTRIBUTION_REBUILD: Map<u32, BracketStats> = Map::new("distribution_rebuild");
+
+/// Phase of an in-progress index repair
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairPhase {
+    /// Compact USER_INDEX, dropping entries that are not backed by USERS and the reverse index
+    Index,
+    /// Append users from USERS that are missing from the index or the deposit index,
+    /// recomputing total deposits and the distribution
+    Users,
+    /// Drop USER_INDEX_REVERSE entries that do not match USER_INDEX
+    Reverse,
+}
+
+/// Position of an in-progress index repair, returned to the caller after every batch
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RepairCursor {
+    pub phase: RepairPhase,
+    /// Last USER_INDEX position processed (Index phase)
+    pub after_position: Option<u32>,
+    /// Last address processed (Users and Reverse phases)
+    pub after_address: Option<Addr>,
+}
+
+/// Progress of an in-progress index repair
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RepairProgress {
+    pub cursor: RepairCursor,
+    /// Next free index position, i.e. the number of users indexed so far
+    pub next_index: u32,
+    /// Sum of user balances seen so far in the Users phase
+    pub total: Uint128,
+}
+
+impl RepairProgress {
+    /// Progress of a repair that has not processed anything yet
+    pub fn initial() -> Self {
+        RepairProgress {
+            cursor: RepairCursor {
+                phase: RepairPhase::Index,
+                after_position: None,
+                after_address: None,
+            },
+            next_index: 0,
+            total: Uint128::zero(),
+        }
+    }
+    
+    /// Whether the Index phase has already checked `position`
+    /// 
+    /// Checked entries were compacted below `next_index`, entries after the cursor are
+    /// still at their old position.
+    pub fn has_indexed(&self, position: u32) -> bool {
+        match self.cursor.phase {
+            RepairPhase::Index => self.cursor.after_position.is_some_and(|after| position <= after),
+            RepairPhase::Users | RepairPhase::Reverse => true,
+        }
+    }
+    
+    /// Whether the Users phase has already counted `user` into `total` and the rebuilt distribution
+    pub fn has_counted(&self, user: &Addr) -> bool {
+        match self.cursor.phase {
+            RepairPhase::Index => false,
+            RepairPhase::Users => self.cursor.after_address.as_ref().is_some_and(|after| user <= after),
+            RepairPhase::Reverse => true,
+        }
+    }
+}
+
+/// Snapshot taken when the campaign is cancelled, with refund progress
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CampaignCancellation {
+    /// Block time of the cancellation (Unix timestamp in seconds)
+    pub cancelled_at: u64,
+    /// Number of depositors when the campaign was cancelled
+    pub users_at_cancel: u32,
+    /// Total deposits when the campaign was cancelled
+    pub deposits_at_cancel: Uint128,
+    /// Number of users refunded by ProcessRefunds so far, including queued refunds
+    pub refunded_users: u32,
+    /// Amount refunded by ProcessRefunds so far
+    pub refunded_amount: Uint128,
+}
+
+/// Campaign cancellation, present only once the campaign is cancelled
+/// Deposits and sweeps are rejected while this is set
+pub const CAMPAIGN_CANCELLATION: Item<CampaignCancellation> = Item::new("campaign_cancellation");
+
+/// Lifecycle stage of the campaign
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignStatus {
+    /// Before the deadline, or no soft cap is configured
+    Active,
+    /// Total deposits reached the soft cap by the deadline, sweeps are allowed
+    Succeeded,
+    /// Total deposits missed the soft cap by the deadline, refund-only
+    Failed,
+    /// Cancelled by an admin, refund-only
+    Cancelled,
+}
+
+/// Outcome recorded by the first transaction after the deadline
+/// 
+/// Fixes the result before withdrawals can lower TOTAL_DEPOSITS below the soft cap.
+pub const CAMPAIGN_RESULT: Item<CampaignStatus> = Item::new("campaign_result");
+
+/// Index repair progress, present only while a repair is running
+/// Deposits and withdrawals are rejected while this is set
+pub const REPAIR_PROGRESS: Item<RepairProgress> = Item::new("repair_progress");
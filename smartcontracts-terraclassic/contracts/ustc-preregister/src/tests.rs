@@ -1,9 +1,11 @@
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, Addr, Uint128, BankMsg};
+    use cosmwasm_std::{coin, coins, Addr, Decimal, Uint128, BankMsg};
     use crate::contract::{execute, instantiate, query};
-    use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+    use crate::error::ContractError;
+    use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, OrderBy, RecoverableToken, UncheckedDestinationShare};
+    use crate::state::{ActivityKind, CampaignStatus, DailyStats, DestinationShare, HistoryKind, Role, SweepLimit};
 
     const USTC_DENOM: &str = "uusd";
     const OWNER: &str = "terra1owner";
@@ -12,18 +14,34 @@ mod tests {
 
     fn setup_contract(deps: &mut cosmwasm_std::OwnedDeps<cosmwasm_std::MemoryStorage, cosmwasm_std::testing::MockApi, cosmwasm_std::testing::MockQuerier>) {
         let msg = InstantiateMsg {
-            owner: Addr::unchecked(OWNER),
+            owner: OWNER.to_string(),
+            soft_cap: None,
+            deadline: None,
+            denom: None,
+            decimals: None,
         };
         let info = mock_info(OWNER, &[]);
         let env = mock_env();
         instantiate(deps.as_mut(), env, info, msg).unwrap();
     }
 
+    /// Gives the contract a balance covering user withdrawals
+    /// 
+    /// The mock querier does not track deposits, and withdrawals check the contract can pay.
+    fn fund_contract(deps: &mut cosmwasm_std::OwnedDeps<cosmwasm_std::MemoryStorage, cosmwasm_std::testing::MockApi, cosmwasm_std::testing::MockQuerier>) {
+        let contract = mock_env().contract.address;
+        deps.querier.update_balance(contract, coins(1_000_000_000u128, USTC_DENOM));
+    }
+
     #[test]
     fn test_instantiate() {
         let mut deps = mock_dependencies();
         let msg = InstantiateMsg {
-            owner: Addr::unchecked(OWNER),
+            owner: OWNER.to_string(),
+            soft_cap: None,
+            deadline: None,
+            denom: None,
+            decimals: None,
         };
         let info = mock_info(OWNER, &[]);
         let env = mock_env();
@@ -43,18 +61,18 @@ mod tests {
     #[test]
     fn test_instantiate_invalid_owner() {
         let mut deps = mock_dependencies();
-        // Using an address that would fail validation if we were validating
-        // Since Addr::unchecked accepts any string, this test verifies that
-        // we're not doing redundant validation (which is correct - Addr is validated during deserialization)
+        // The owner arrives as a plain string and is checked with addr_validate
         let msg = InstantiateMsg {
-            owner: Addr::unchecked("invalid_address"),
+            owner: "Terra1Owner".to_string(),
+            soft_cap: None,
+            deadline: None,
+            denom: None,
+            decimals: None,
         };
         let info = mock_info(OWNER, &[]);
         let env = mock_env();
-        // This should succeed since we removed redundant validation
-        // Addr validation happens during JSON deserialization, not in instantiate
-        let res = instantiate(deps.as_mut(), env, info, msg);
-        assert!(res.is_ok());
+        let err = instantiate(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidOwnerAddress { address } if address == "Terra1Owner"));
     }
 
     #[test]
@@ -74,7 +92,7 @@ mod tests {
         
         // Query user deposit
         let query_msg = QueryMsg::GetUserDeposit {
-            user: Addr::unchecked(USER1),
+            user: USER1.to_string(),
         };
         let res = query(deps.as_ref(), env, query_msg).unwrap();
         let deposit: crate::msg::GetUserDepositResponse = cosmwasm_std::from_json(&res).unwrap();
@@ -112,6 +130,7 @@ mod tests {
     fn test_withdraw() {
         let mut deps = mock_dependencies();
         setup_contract(&mut deps);
+        fund_contract(&mut deps);
         
         // First deposit
         let deposit_amount = Uint128::from(1000u128);
@@ -130,7 +149,7 @@ mod tests {
         
         // Query user deposit - should be 500
         let query_msg = QueryMsg::GetUserDeposit {
-            user: Addr::unchecked(USER1),
+            user: USER1.to_string(),
         };
         let res = query(deps.as_ref(), env, query_msg).unwrap();
         let deposit: crate::msg::GetUserDepositResponse = cosmwasm_std::from_json(&res).unwrap();
@@ -173,7 +192,8 @@ mod tests {
         let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60 + 1; // 7 days + 1 second
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination: destination.clone(),
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         execute(deps.as_mut(), env.clone(), info, msg).unwrap();
@@ -183,7 +203,7 @@ mod tests {
         
         // Owner withdraw - should succeed
         let info = mock_info(OWNER, &[]);
-        let msg = ExecuteMsg::OwnerWithdraw {};
+        let msg = ExecuteMsg::OwnerWithdraw { amount: None };
         let res = execute(deps.as_mut(), env, info, msg);
         assert!(res.is_ok());
         
@@ -202,7 +222,7 @@ mod tests {
         
         let info = mock_info(USER1, &[]);
         let env = mock_env();
-        let msg = ExecuteMsg::OwnerWithdraw {};
+        let msg = ExecuteMsg::OwnerWithdraw { amount: None };
         let res = execute(deps.as_mut(), env, info, msg);
         assert!(res.is_err());
     }
@@ -233,6 +253,7 @@ mod tests {
     fn test_withdraw_all_and_get_all_users() {
         let mut deps = mock_dependencies();
         setup_contract(&mut deps);
+        fund_contract(&mut deps);
         
         // User1 deposits
         let deposit_amount = Uint128::from(1000u128);
@@ -275,7 +296,7 @@ mod tests {
         let info = mock_info(OWNER, &[]);
         let env = mock_env();
         let msg = ExecuteMsg::UpdateConfig {
-            owner: Some(new_owner.clone()),
+            owner: Some(new_owner.to_string()),
         };
         
         // Should succeed
@@ -319,7 +340,7 @@ mod tests {
         let info = mock_info(USER1, &[]); // Non-owner
         let env = mock_env();
         let msg = ExecuteMsg::UpdateConfig {
-            owner: Some(Addr::unchecked("terra1newowner")),
+            owner: Some("terra1newowner".to_string()),
         };
         
         // Should fail - unauthorized
@@ -405,7 +426,7 @@ mod tests {
         
         // Query user deposit - should be sum
         let query_msg = QueryMsg::GetUserDeposit {
-            user: Addr::unchecked(USER1),
+            user: USER1.to_string(),
         };
         let res = query(deps.as_ref(), env, query_msg).unwrap();
         let deposit: crate::msg::GetUserDepositResponse = cosmwasm_std::from_json(&res).unwrap();
@@ -442,7 +463,7 @@ mod tests {
         
         // Query next page using start_after
         let next_cursor = users.next.unwrap();
-        let query_msg = QueryMsg::GetAllUsers { start_after: Some(next_cursor), limit: Some(2) };
+        let query_msg = QueryMsg::GetAllUsers { start_after: Some(next_cursor.to_string()), limit: Some(2) };
         let res = query(deps.as_ref(), env, query_msg).unwrap();
         let users: crate::msg::GetAllUsersResponse = cosmwasm_std::from_json(&res).unwrap();
         assert_eq!(users.users.len(), 2);
@@ -475,6 +496,7 @@ mod tests {
     fn test_withdraw_partial() {
         let mut deps = mock_dependencies();
         setup_contract(&mut deps);
+        fund_contract(&mut deps);
         
         let deposit_amount = Uint128::from(1000u128);
         let withdraw_amount = Uint128::from(300u128);
@@ -490,7 +512,7 @@ mod tests {
         
         // Query user deposit - should still have balance
         let query_msg = QueryMsg::GetUserDeposit {
-            user: Addr::unchecked(USER1),
+            user: USER1.to_string(),
         };
         let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
         let deposit: crate::msg::GetUserDepositResponse = cosmwasm_std::from_json(&res).unwrap();
@@ -517,7 +539,7 @@ mod tests {
         
         // Query with start_after that doesn't exist - should error
         let query_msg = QueryMsg::GetAllUsers { 
-            start_after: Some(Addr::unchecked("terra1nonexistent")), 
+            start_after: Some("terra1nonexistent".to_string()), 
             limit: None 
         };
         let res = query(deps.as_ref(), env, query_msg);
@@ -529,6 +551,7 @@ mod tests {
     fn test_withdraw_index_inconsistency() {
         let mut deps = mock_dependencies();
         setup_contract(&mut deps);
+        fund_contract(&mut deps);
         
         // This test verifies that index inconsistency is handled correctly
         // In normal operation, this shouldn't happen, but we test the error path
@@ -557,7 +580,8 @@ mod tests {
         let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60 + 1;
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination,
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -566,7 +590,7 @@ mod tests {
         env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60 + 1);
         
         // Owner withdraw - should error because contract has no balance
-        let msg = ExecuteMsg::OwnerWithdraw {};
+        let msg = ExecuteMsg::OwnerWithdraw { amount: None };
         let res = execute(deps.as_mut(), env, info, msg);
         assert!(res.is_err());
     }
@@ -592,6 +616,7 @@ mod tests {
     fn test_multiple_users_index_consistency() {
         let mut deps = mock_dependencies();
         setup_contract(&mut deps);
+        fund_contract(&mut deps);
         
         let env = mock_env();
         
@@ -664,7 +689,7 @@ mod tests {
         
         // Test pagination with start_after using the cursor from previous query
         let next_cursor = users.next.unwrap();
-        let query_msg = QueryMsg::GetAllUsers { start_after: Some(next_cursor.clone()), limit: Some(2) };
+        let query_msg = QueryMsg::GetAllUsers { start_after: Some(next_cursor.to_string()), limit: Some(2) };
         let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
         let users2: crate::msg::GetAllUsersResponse = cosmwasm_std::from_json(&res).unwrap();
         assert_eq!(users2.users.len(), 2);
@@ -696,7 +721,7 @@ mod tests {
         
         // Use cursor to get next page
         let cursor = users.next.unwrap();
-        let query_msg = QueryMsg::GetAllUsers { start_after: Some(cursor.clone()), limit: Some(1) };
+        let query_msg = QueryMsg::GetAllUsers { start_after: Some(cursor.to_string()), limit: Some(1) };
         let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
         let users2: crate::msg::GetAllUsersResponse = cosmwasm_std::from_json(&res).unwrap();
         // Should get users after the cursor
@@ -746,6 +771,7 @@ mod tests {
     fn test_sum_of_deposits_after_withdrawals() {
         let mut deps = mock_dependencies();
         setup_contract(&mut deps);
+        fund_contract(&mut deps);
         
         let env = mock_env();
         
@@ -801,7 +827,7 @@ mod tests {
         execute(deps.as_mut(), env.clone(), info2, ExecuteMsg::Deposit {}).unwrap();
         
         // Validate index - should be consistent
-        let query_msg = QueryMsg::ValidateIndex {};
+        let query_msg = QueryMsg::ValidateIndex { start: None, limit: None };
         let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
         let validation: crate::msg::ValidateIndexResponse = cosmwasm_std::from_json(&res).unwrap();
         
@@ -816,6 +842,7 @@ mod tests {
     fn test_validate_index_after_withdrawal() {
         let mut deps = mock_dependencies();
         setup_contract(&mut deps);
+        fund_contract(&mut deps);
         
         let env = mock_env();
         
@@ -838,7 +865,7 @@ mod tests {
         execute(deps.as_mut(), env.clone(), info, msg).unwrap();
         
         // Validate index - should still be consistent after swap-and-remove
-        let query_msg = QueryMsg::ValidateIndex {};
+        let query_msg = QueryMsg::ValidateIndex { start: None, limit: None };
         let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
         let validation: crate::msg::ValidateIndexResponse = cosmwasm_std::from_json(&res).unwrap();
         
@@ -853,6 +880,7 @@ mod tests {
     fn test_user_count_zero_edge_case() {
         let mut deps = mock_dependencies();
         setup_contract(&mut deps);
+        fund_contract(&mut deps);
         
         let env = mock_env();
         
@@ -892,7 +920,7 @@ mod tests {
         assert!(users.next.is_none());
         
         // Verify ValidateIndex shows consistent state with 0 users
-        let query_msg = QueryMsg::ValidateIndex {};
+        let query_msg = QueryMsg::ValidateIndex { start: None, limit: None };
         let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
         let validation: crate::msg::ValidateIndexResponse = cosmwasm_std::from_json(&res).unwrap();
         
@@ -920,7 +948,8 @@ mod tests {
         
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination: destination.clone(),
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         
@@ -957,7 +986,8 @@ mod tests {
         
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination,
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         
@@ -978,7 +1008,8 @@ mod tests {
         
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination,
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         
@@ -998,7 +1029,8 @@ mod tests {
         // Non-owner tries to set withdrawal destination
         let info = mock_info(USER1, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination,
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         
@@ -1019,7 +1051,7 @@ mod tests {
         
         // Try to withdraw without setting destination
         let info = mock_info(OWNER, &[]);
-        let msg = ExecuteMsg::OwnerWithdraw {};
+        let msg = ExecuteMsg::OwnerWithdraw { amount: None };
         let res = execute(deps.as_mut(), env, info, msg);
         assert!(res.is_err());
     }
@@ -1053,7 +1085,8 @@ mod tests {
         let info = mock_info(OWNER, &[]);
         let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60 + 1;
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination: destination.clone(),
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -1064,7 +1097,7 @@ mod tests {
         WITHDRAWAL_UNLOCK_TIMESTAMP.save(deps.as_mut().storage, &0u64).unwrap();
         
         // Try to withdraw - should fail because timestamp is 0
-        let msg = ExecuteMsg::OwnerWithdraw {};
+        let msg = ExecuteMsg::OwnerWithdraw { amount: None };
         let res = execute(deps.as_mut(), env, info, msg);
         assert!(res.is_err());
     }
@@ -1085,13 +1118,14 @@ mod tests {
         let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60 + 1;
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination,
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
         
         // Try to withdraw immediately (before timestamp passes) - should fail
-        let msg = ExecuteMsg::OwnerWithdraw {};
+        let msg = ExecuteMsg::OwnerWithdraw { amount: None };
         let res = execute(deps.as_mut(), env, info, msg);
         assert!(res.is_err());
     }
@@ -1118,7 +1152,8 @@ mod tests {
         let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60 + 1;
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination: destination.clone(),
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -1127,7 +1162,7 @@ mod tests {
         env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60 + 1);
         
         // Owner withdraw
-        let msg = ExecuteMsg::OwnerWithdraw {};
+        let msg = ExecuteMsg::OwnerWithdraw { amount: None };
         let res = execute(deps.as_mut(), env, info, msg).unwrap();
         
         // Verify it transfers to destination, not owner
@@ -1159,7 +1194,8 @@ mod tests {
         // Set first withdrawal destination
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination: destination1.clone(),
+            destination: destination1.to_string(),
+            shares: vec![],
             unlock_timestamp: unlock_timestamp1,
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -1168,7 +1204,7 @@ mod tests {
         let query_msg = QueryMsg::GetWithdrawalInfo {};
         let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
         let info_resp: crate::msg::GetWithdrawalInfoResponse = cosmwasm_std::from_json(&res).unwrap();
-        assert_eq!(info_resp.destination, Some(destination1));
+        assert_eq!(info_resp.destination, Some(destination1.clone()));
         assert_eq!(info_resp.unlock_timestamp, unlock_timestamp1);
         
         // Update to new destination with new timestamp
@@ -1176,12 +1212,21 @@ mod tests {
         let unlock_timestamp2 = env.block.time.seconds() + 14 * 24 * 60 * 60 + 1; // 14 days in future
         
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination: destination2.clone(),
+            destination: destination2.to_string(),
+            shares: vec![],
             unlock_timestamp: unlock_timestamp2,
         };
         execute(deps.as_mut(), env.clone(), info, msg).unwrap();
         
-        // Verify destination and timestamp were updated
+        // The change is staged: the first destination stays in effect during the delay
+        let query_msg = QueryMsg::GetWithdrawalInfo {};
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let info_resp: crate::msg::GetWithdrawalInfoResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(info_resp.destination, Some(destination1));
+        assert_eq!(info_resp.unlock_timestamp, unlock_timestamp1);
+        
+        // Verify destination and timestamp were updated once the delay elapsed
+        env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60);
         let query_msg = QueryMsg::GetWithdrawalInfo {};
         let res = query(deps.as_ref(), env, query_msg).unwrap();
         let info_resp: crate::msg::GetWithdrawalInfoResponse = cosmwasm_std::from_json(&res).unwrap();
@@ -1218,7 +1263,8 @@ mod tests {
         // Set withdrawal destination
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination: destination.clone(),
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         execute(deps.as_mut(), env.clone(), info, msg).unwrap();
@@ -1255,7 +1301,8 @@ mod tests {
         let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60 + 1;
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination: destination.clone(),
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -1264,7 +1311,7 @@ mod tests {
         env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60 + 2);
         
         // Owner withdraw should succeed
-        let msg = ExecuteMsg::OwnerWithdraw {};
+        let msg = ExecuteMsg::OwnerWithdraw { amount: None };
         let res = execute(deps.as_mut(), env, info, msg);
         assert!(res.is_ok());
         
@@ -1289,7 +1336,8 @@ mod tests {
         // Set withdrawal destination
         let info = mock_info(OWNER, &[]);
         let msg = ExecuteMsg::SetWithdrawalDestination {
-            destination: destination.clone(),
+            destination: destination.to_string(),
+            shares: vec![],
             unlock_timestamp,
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -1309,14 +1357,14 @@ mod tests {
         env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60 + 2);
         
         // First owner withdraw
-        let msg = ExecuteMsg::OwnerWithdraw {};
+        let msg = ExecuteMsg::OwnerWithdraw { amount: None };
         let res1 = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
         assert_eq!(res1.attributes[2].key, "amount");
         assert_eq!(res1.attributes[2].value, "3000");
         
         // Verify user balance is still tracked (not modified by owner withdrawal)
         let query_msg = QueryMsg::GetUserDeposit {
-            user: Addr::unchecked(USER1),
+            user: USER1.to_string(),
         };
         let res: crate::msg::GetUserDepositResponse = cosmwasm_std::from_json(
             &query(deps.as_ref(), env.clone(), query_msg).unwrap()
@@ -1342,7 +1390,7 @@ mod tests {
         
         // Verify user balance increased
         let query_msg = QueryMsg::GetUserDeposit {
-            user: Addr::unchecked(USER1),
+            user: USER1.to_string(),
         };
         let res: crate::msg::GetUserDepositResponse = cosmwasm_std::from_json(
             &query(deps.as_ref(), env.clone(), query_msg).unwrap()
@@ -1350,19 +1398,2612 @@ mod tests {
         assert_eq!(res.deposit, Uint128::from(5000u128));
         
         // Second owner withdraw should succeed
-        let msg = ExecuteMsg::OwnerWithdraw {};
+        let msg = ExecuteMsg::OwnerWithdraw { amount: None };
         let res2 = execute(deps.as_mut(), env, info, msg).unwrap();
         assert_eq!(res2.attributes[2].key, "amount");
         assert_eq!(res2.attributes[2].value, "2000");
         
         // Verify user balance is still tracked after second withdrawal
         let query_msg = QueryMsg::GetUserDeposit {
-            user: Addr::unchecked(USER1),
+            user: USER1.to_string(),
         };
         let res: crate::msg::GetUserDepositResponse = cosmwasm_std::from_json(
             &query(deps.as_ref(), mock_env(), query_msg).unwrap()
         ).unwrap();
         assert_eq!(res.deposit, Uint128::from(5000u128));
     }
+    
+    #[test]
+    fn test_get_top_depositors() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        
+        // Deposit in an order unrelated to the balances
+        for (user, amount) in [("terra1user1", 3000u128), ("terra1user2", 5000u128), ("terra1user3", 1000u128), ("terra1user4", 4000u128)] {
+            let info = mock_info(user, &coins(amount, USTC_DENOM));
+            execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        }
+        
+        // First page
+        let query_msg = QueryMsg::GetTopDepositors { start_after: None, limit: Some(2) };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let page: crate::msg::GetTopDepositorsResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(page.depositors, vec![
+            (Addr::unchecked("terra1user2"), Uint128::from(5000u128)),
+            (Addr::unchecked("terra1user4"), Uint128::from(4000u128)),
+        ]);
+        assert_eq!(page.next, Some(Addr::unchecked("terra1user4")));
+        
+        // Second page
+        let query_msg = QueryMsg::GetTopDepositors { start_after: page.next.as_ref().map(Addr::to_string), limit: Some(2) };
+        let res = query(deps.as_ref(), env, query_msg).unwrap();
+        let page: crate::msg::GetTopDepositorsResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(page.depositors, vec![
+            (Addr::unchecked("terra1user1"), Uint128::from(3000u128)),
+            (Addr::unchecked("terra1user3"), Uint128::from(1000u128)),
+        ]);
+        assert!(page.next.is_none());
+    }
+    
+    #[test]
+    fn test_top_depositors_follow_balance_changes() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        
+        let env = mock_env();
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER2, &coins(2000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        // USER1 tops up past USER2
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1500u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        let query_msg = QueryMsg::GetTopDepositors { start_after: None, limit: None };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let page: crate::msg::GetTopDepositorsResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(page.depositors, vec![
+            (Addr::unchecked(USER1), Uint128::from(2500u128)),
+            (Addr::unchecked(USER2), Uint128::from(2000u128)),
+        ]);
+        
+        // USER1 withdraws everything and drops out of the ranking
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(2500u128) };
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg).unwrap();
+        let query_msg = QueryMsg::GetTopDepositors { start_after: None, limit: None };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let page: crate::msg::GetTopDepositorsResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(page.depositors, vec![(Addr::unchecked(USER2), Uint128::from(2000u128))]);
+        
+        // Removed user can no longer be used as a cursor
+        let query_msg = QueryMsg::GetTopDepositors { start_after: Some(USER1.to_string()), limit: None };
+        assert!(query(deps.as_ref(), env, query_msg).is_err());
+    }
+    
+    #[test]
+    fn test_get_user_rank() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        for (user, amount) in [("terra1user1", 3000u128), ("terra1user2", 5000u128), ("terra1user3", 1000u128)] {
+            let info = mock_info(user, &coins(amount, USTC_DENOM));
+            execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        }
+        
+        for (user, expected_rank) in [("terra1user2", 1u32), ("terra1user1", 2), ("terra1user3", 3)] {
+            let query_msg = QueryMsg::GetUserRank { user: user.to_string() };
+            let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+            let rank: crate::msg::GetUserRankResponse = cosmwasm_std::from_json(&res).unwrap();
+            assert_eq!(rank.rank, Some(expected_rank));
+            assert_eq!(rank.user_count, 3);
+        }
+        
+        // User without deposit is unranked
+        let query_msg = QueryMsg::GetUserRank { user: "terra1nobody".to_string() };
+        let res = query(deps.as_ref(), env, query_msg).unwrap();
+        let rank: crate::msg::GetUserRankResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(rank.rank, None);
+        assert_eq!(rank.deposit, Uint128::zero());
+    }
+    
+    #[test]
+    fn test_get_all_users_by_address_cursor_withdrawn() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        
+        let env = mock_env();
+        for i in 1..=4 {
+            let user = format!("terra1user{}", i);
+            let info = mock_info(&user, &coins(1000u128 * i, USTC_DENOM));
+            execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        }
+        
+        let query_msg = QueryMsg::GetAllUsersByAddress { start_after: None, limit: Some(2) };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let page: crate::msg::GetAllUsersResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(page.users.len(), 2);
+        assert_eq!(page.users[0].0, Addr::unchecked("terra1user1"));
+        assert_eq!(page.next, Some(Addr::unchecked("terra1user2")));
+        
+        // Cursor user withdraws everything between pages
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(2000u128) };
+        execute(deps.as_mut(), env.clone(), mock_info("terra1user2", &[]), msg).unwrap();
+        
+        // The index-based query rejects the cursor, the address-based one does not
+        let query_msg = QueryMsg::GetAllUsers { start_after: page.next.as_ref().map(Addr::to_string), limit: Some(2) };
+        assert!(query(deps.as_ref(), env.clone(), query_msg).is_err());
+        
+        let query_msg = QueryMsg::GetAllUsersByAddress { start_after: page.next.as_ref().map(Addr::to_string), limit: Some(2) };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let page: crate::msg::GetAllUsersResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(page.users, vec![
+            (Addr::unchecked("terra1user3"), Uint128::from(3000u128)),
+            (Addr::unchecked("terra1user4"), Uint128::from(4000u128)),
+        ]);
+        assert!(page.next.is_none());
+        
+        // Any address works as a cursor, even one that never deposited
+        let query_msg = QueryMsg::GetAllUsersByAddress { start_after: Some("terra1user30".to_string()), limit: None };
+        let res = query(deps.as_ref(), env, query_msg).unwrap();
+        let page: crate::msg::GetAllUsersResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(page.users, vec![(Addr::unchecked("terra1user4"), Uint128::from(4000u128))]);
+    }
+    
+    #[test]
+    fn test_get_all_users_by_address_full_scan_with_removals() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        
+        let env = mock_env();
+        for i in 1..=9 {
+            let user = format!("terra1user{}", i);
+            let info = mock_info(&user, &coins(1000u128, USTC_DENOM));
+            execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        }
+        
+        // Scan two users at a time while a user from each side of the cursor leaves
+        let mut seen: Vec<Addr> = Vec::new();
+        let mut cursor = None;
+        let mut leavers = vec!["terra1user1", "terra1user9"].into_iter();
+        loop {
+            let query_msg = QueryMsg::GetAllUsersByAddress { start_after: cursor, limit: Some(2) };
+            let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+            let page: crate::msg::GetAllUsersResponse = cosmwasm_std::from_json(&res).unwrap();
+            seen.extend(page.users.into_iter().map(|(user, _)| user));
+            
+            if let Some(leaver) = leavers.next() {
+                let msg = ExecuteMsg::Withdraw { amount: Uint128::from(1000u128) };
+                execute(deps.as_mut(), env.clone(), mock_info(leaver, &[]), msg).unwrap();
+            }
+            
+            cursor = page.next.map(String::from);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        
+        // terra1user1 was returned before leaving, terra1user9 left before being reached
+        let expected: Vec<Addr> = (1..=8).map(|i| Addr::unchecked(format!("terra1user{}", i))).collect();
+        assert_eq!(seen, expected);
+    }
+    
+    #[test]
+    fn test_get_user_history() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        
+        let mut env = mock_env();
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        env.block.height += 10;
+        env.block.time = env.block.time.plus_seconds(60);
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(1000u128) };
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg).unwrap();
+        
+        // Re-deposit after withdrawing everything continues the same history
+        env.block.height += 10;
+        env.block.time = env.block.time.plus_seconds(60);
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(500u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        let query_msg = QueryMsg::GetUserHistory { user: USER1.to_string(), start_after: None, limit: None, order: None };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let history: crate::msg::GetUserHistoryResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(history.entries.len(), 3);
+        assert!(history.next.is_none());
+        
+        let first = &history.entries[0];
+        assert_eq!(first.sequence, 0);
+        assert_eq!(first.kind, HistoryKind::Deposit);
+        assert_eq!(first.height, mock_env().block.height);
+        assert_eq!(first.time, mock_env().block.time.seconds());
+        assert_eq!(first.resulting_balance, Uint128::from(1000u128));
+        
+        let second = &history.entries[1];
+        assert_eq!(second.kind, HistoryKind::Withdraw);
+        assert_eq!(second.amount, Uint128::from(1000u128));
+        assert_eq!(second.resulting_balance, Uint128::zero());
+        
+        let third = &history.entries[2];
+        assert_eq!(third.sequence, 2);
+        assert_eq!(third.time, env.block.time.seconds());
+        assert_eq!(third.resulting_balance, Uint128::from(500u128));
+        
+        // Other users have an empty history
+        let query_msg = QueryMsg::GetUserHistory { user: USER2.to_string(), start_after: None, limit: None, order: None };
+        let res = query(deps.as_ref(), env, query_msg).unwrap();
+        let history: crate::msg::GetUserHistoryResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert!(history.entries.is_empty());
+    }
+    
+    #[test]
+    fn test_get_user_history_pagination_and_order() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        for amount in 1..=5u128 {
+            execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(amount, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        }
+        
+        // Newest first, two at a time
+        let query_msg = QueryMsg::GetUserHistory { user: USER1.to_string(), start_after: None, limit: Some(2), order: Some(OrderBy::Desc) };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let page: crate::msg::GetUserHistoryResponse = cosmwasm_std::from_json(&res).unwrap();
+        let sequences: Vec<u64> = page.entries.iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, vec![4, 3]);
+        assert_eq!(page.next, Some(3));
+        
+        let query_msg = QueryMsg::GetUserHistory { user: USER1.to_string(), start_after: page.next, limit: Some(2), order: Some(OrderBy::Desc) };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let page: crate::msg::GetUserHistoryResponse = cosmwasm_std::from_json(&res).unwrap();
+        let sequences: Vec<u64> = page.entries.iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, vec![2, 1]);
+        
+        // Oldest first from a cursor
+        let query_msg = QueryMsg::GetUserHistory { user: USER1.to_string(), start_after: Some(2), limit: None, order: Some(OrderBy::Asc) };
+        let res = query(deps.as_ref(), env, query_msg).unwrap();
+        let page: crate::msg::GetUserHistoryResponse = cosmwasm_std::from_json(&res).unwrap();
+        let sequences: Vec<u64> = page.entries.iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, vec![3, 4]);
+        assert!(page.next.is_none());
+        assert_eq!(page.entries[1].resulting_balance, Uint128::from(15u128));
+    }
+    
+    #[test]
+    fn test_activity_ledger() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        
+        let mut env = mock_env();
+        
+        // Nothing recorded yet
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLatestSequence {}).unwrap();
+        let latest: crate::msg::GetLatestSequenceResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(latest.sequence, 0);
+        
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(400u128) };
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg).unwrap();
+        
+        let destination = Addr::unchecked("terra1destination");
+        let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60;
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: destination.to_string(), shares: vec![], unlock_timestamp };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        deps.querier.update_balance(&env.contract.address, coins(600u128, USTC_DENOM));
+        env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60);
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap();
+        
+        let msg = ExecuteMsg::UpdateConfig { owner: Some("terra1newowner".to_string()) };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        // Failed and no-op calls are not recorded
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(10_000u128) };
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg).unwrap_err();
+        let msg = ExecuteMsg::UpdateConfig { owner: None };
+        execute(deps.as_mut(), env.clone(), mock_info("terra1newowner", &[]), msg).unwrap();
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLatestSequence {}).unwrap();
+        let latest: crate::msg::GetLatestSequenceResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(latest.sequence, 5);
+        
+        let query_msg = QueryMsg::GetActivity { start_after_seq: None, limit: None };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let ledger: crate::msg::GetActivityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(ledger.latest_sequence, 5);
+        let sequences: Vec<u64> = ledger.activities.iter().map(|a| a.sequence).collect();
+        assert_eq!(sequences, vec![1, 2, 3, 4, 5]);
+        assert_eq!(ledger.activities[0].kind, ActivityKind::Deposit { user: Addr::unchecked(USER1), amount: Uint128::from(1000u128) });
+        assert_eq!(ledger.activities[1].kind, ActivityKind::Withdraw { user: Addr::unchecked(USER1), amount: Uint128::from(400u128) });
+        assert_eq!(ledger.activities[2].kind, ActivityKind::WithdrawalDestinationSet { destination: destination.clone(), shares: vec![], unlock_timestamp });
+        assert_eq!(ledger.activities[3].kind, ActivityKind::OwnerWithdraw { destination, amount: Uint128::from(600u128) });
+        assert_eq!(ledger.activities[3].time, env.block.time.seconds());
+        assert_eq!(ledger.activities[4].kind, ActivityKind::ConfigUpdate { owner: Addr::unchecked("terra1newowner") });
+        
+        // Incremental sync from the last processed sequence
+        let query_msg = QueryMsg::GetActivity { start_after_seq: Some(3), limit: Some(1) };
+        let res = query(deps.as_ref(), env, query_msg).unwrap();
+        let ledger: crate::msg::GetActivityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(ledger.activities.len(), 1);
+        assert_eq!(ledger.activities[0].sequence, 4);
+        assert_eq!(ledger.latest_sequence, 5);
+    }
+    
+    #[test]
+    fn test_get_daily_stats() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        
+        let mut env = mock_env();
+        let day0 = env.block.time.seconds() / 86400;
+        
+        // Day 0: two new users, one partial withdrawal
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER2, &coins(2000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(500u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(300u128) };
+        execute(deps.as_mut(), env.clone(), mock_info(USER2, &[]), msg).unwrap();
+        
+        // Day 2: USER1 exits (nothing happens on day 1)
+        env.block.time = env.block.time.plus_seconds(2 * 86400);
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(1500u128) };
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg).unwrap();
+        
+        let query_msg = QueryMsg::GetDailyStats { from_day: day0, to_day: day0 + 2 };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let stats: crate::msg::GetDailyStatsResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(stats.days, vec![
+            (day0, DailyStats {
+                deposit_volume: Uint128::from(3500u128),
+                withdrawal_volume: Uint128::from(300u128),
+                new_users: 2,
+                exited_users: 0,
+                closing_total: Uint128::from(3200u128),
+            }),
+            (day0 + 2, DailyStats {
+                deposit_volume: Uint128::zero(),
+                withdrawal_volume: Uint128::from(1500u128),
+                new_users: 0,
+                exited_users: 1,
+                closing_total: Uint128::from(1700u128),
+            }),
+        ]);
+        assert_eq!(stats.next_from_day, None);
+        
+        // Range bounds are inclusive
+        let query_msg = QueryMsg::GetDailyStats { from_day: day0 + 1, to_day: day0 + 1 };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let stats: crate::msg::GetDailyStatsResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert!(stats.days.is_empty());
+        
+        // Inverted range is rejected
+        let query_msg = QueryMsg::GetDailyStats { from_day: day0 + 2, to_day: day0 };
+        assert!(query(deps.as_ref(), env.clone(), query_msg).is_err());
+        
+        // Longer ranges are returned in pages of 100 buckets
+        for _ in 0..100 {
+            env.block.time = env.block.time.plus_seconds(86400);
+            execute(deps.as_mut(), env.clone(), mock_info(USER2, &coins(1u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        }
+        let last_day = day0 + 102;
+        let query_msg = QueryMsg::GetDailyStats { from_day: day0, to_day: last_day };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let stats: crate::msg::GetDailyStatsResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(stats.days.len(), 100);
+        assert_eq!(stats.days.last().unwrap().0, last_day - 2);
+        assert_eq!(stats.next_from_day, Some(last_day - 1));
+        
+        let query_msg = QueryMsg::GetDailyStats { from_day: last_day - 1, to_day: last_day };
+        let res = query(deps.as_ref(), env, query_msg).unwrap();
+        let stats: crate::msg::GetDailyStatsResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(stats.days.iter().map(|(day, _)| *day).collect::<Vec<_>>(), vec![last_day - 1, last_day]);
+        assert_eq!(stats.next_from_day, None);
+    }
+    
+    #[test]
+    fn test_get_distribution() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        
+        let env = mock_env();
+        let msg = ExecuteMsg::SetDistributionBrackets { thresholds: vec![Uint128::from(1000u128), Uint128::from(10_000u128)] };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetActivity { start_after_seq: None, limit: None }).unwrap();
+        let ledger: crate::msg::GetActivityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(
+            ledger.activities.last().unwrap().kind,
+            ActivityKind::DistributionBracketsSet { thresholds: vec![Uint128::from(1000u128), Uint128::from(10_000u128)] }
+        );
+        
+        execute(deps.as_mut(), env.clone(), mock_info("terra1user1", &coins(100u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("terra1user2", &coins(100u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("terra1user3", &coins(900u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        // USER3 tops up into the middle bracket
+        execute(deps.as_mut(), env.clone(), mock_info("terra1user3", &coins(4100u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetDistribution {}).unwrap();
+        let dist: crate::msg::GetDistributionResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(dist.brackets.len(), 3);
+        assert_eq!(dist.total_users, 3);
+        assert_eq!(dist.total_volume, Uint128::from(5200u128));
+        
+        assert_eq!(dist.brackets[0].min, Uint128::zero());
+        assert_eq!(dist.brackets[0].max, Some(Uint128::from(1000u128)));
+        assert_eq!(dist.brackets[0].count, 2);
+        assert_eq!(dist.brackets[0].volume, Uint128::from(200u128));
+        assert_eq!(dist.brackets[1].count, 1);
+        assert_eq!(dist.brackets[1].volume, Uint128::from(5000u128));
+        assert_eq!(dist.brackets[2].max, None);
+        assert_eq!(dist.brackets[2].count, 0);
+        assert_eq!(dist.brackets[1].user_share, Decimal::from_ratio(1u128, 3u128));
+        assert_eq!(dist.brackets[1].volume_share, Decimal::from_ratio(5000u128, 5200u128));
+        
+        // Users within a bracket hold equal amounts here, so this is the exact Gini of (100, 100, 5000)
+        assert_eq!(dist.gini, Decimal::from_ratio(9800u128, 3u128 * 5200u128));
+        
+        // Withdrawals move users back down or out of the distribution
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(4500u128) };
+        execute(deps.as_mut(), env.clone(), mock_info("terra1user3", &[]), msg).unwrap();
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(100u128) };
+        execute(deps.as_mut(), env.clone(), mock_info("terra1user1", &[]), msg).unwrap();
+        
+        let res = query(deps.as_ref(), env, QueryMsg::GetDistribution {}).unwrap();
+        let dist: crate::msg::GetDistributionResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(dist.brackets[0].count, 2);
+        assert_eq!(dist.brackets[0].volume, Uint128::from(600u128));
+        assert_eq!(dist.brackets[1].count, 0);
+        assert_eq!(dist.brackets[1].volume, Uint128::zero());
+        assert_eq!(dist.gini, Decimal::zero());
+    }
+    
+    #[test]
+    fn test_set_distribution_brackets_validation() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        
+        // Default brackets are in place after instantiation
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetDistribution {}).unwrap();
+        let dist: crate::msg::GetDistributionResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(dist.brackets.len(), 5);
+        assert_eq!(dist.gini, Decimal::zero());
+        
+        // Only a config manager can change brackets
+        let msg = ExecuteMsg::SetDistributionBrackets { thresholds: vec![Uint128::from(10u128)] };
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::ConfigManager }));
+        
+        // Thresholds must be strictly ascending and non-zero
+        for thresholds in [vec![Uint128::from(10u128), Uint128::from(10u128)], vec![Uint128::zero()]] {
+            let msg = ExecuteMsg::SetDistributionBrackets { thresholds };
+            let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidDistributionThresholds { .. }));
+        }
+        
+        // Locked once there are depositors
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(100u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        let msg = ExecuteMsg::SetDistributionBrackets { thresholds: vec![Uint128::from(10u128)] };
+        let err = execute(deps.as_mut(), env, mock_info(OWNER, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::DistributionBracketsLocked {}));
+    }
+    
+    #[test]
+    fn test_get_user_deposits_batch() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let mut env = mock_env();
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        let first_time = env.block.time.seconds();
+        env.block.time = env.block.time.plus_seconds(100);
+        execute(deps.as_mut(), env.clone(), mock_info(USER2, &coins(2000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(500u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        let users = vec![USER2.to_string(), "Terra1MixedCase".to_string(), USER1.to_string(), "terra1nobody".to_string()];
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetUserDeposits { users }).unwrap();
+        let batch: crate::msg::GetUserDepositsResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(batch.deposits.len(), 4);
+        
+        assert_eq!(batch.deposits[0].user, USER2);
+        assert_eq!(batch.deposits[0].deposit, Some(Uint128::from(2000u128)));
+        assert_eq!(batch.deposits[0].index, Some(1));
+        assert_eq!(batch.deposits[0].first_deposit_time, Some(first_time + 100));
+        
+        // Invalid entry is reported without failing the query
+        assert_eq!(batch.deposits[1].deposit, None);
+        assert!(batch.deposits[1].error.is_some());
+        
+        // First deposit time is unaffected by later deposits
+        assert_eq!(batch.deposits[2].deposit, Some(Uint128::from(1500u128)));
+        assert_eq!(batch.deposits[2].index, Some(0));
+        assert_eq!(batch.deposits[2].first_deposit_time, Some(first_time));
+        assert!(batch.deposits[2].error.is_none());
+        
+        // Valid address without a deposit
+        assert_eq!(batch.deposits[3].deposit, Some(Uint128::zero()));
+        assert_eq!(batch.deposits[3].index, None);
+        assert_eq!(batch.deposits[3].first_deposit_time, None);
+        
+        // Batch size is bounded
+        let users = (0..101).map(|i| format!("terra1user{}", i)).collect();
+        assert!(query(deps.as_ref(), env, QueryMsg::GetUserDeposits { users }).is_err());
+    }
+    
+    #[test]
+    fn test_repair_index_rebuilds_corrupted_index() {
+        use crate::state::{TOTAL_DEPOSITS, USERS, USER_COUNT, USER_INDEX, USER_INDEX_REVERSE};
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        for i in 1..=5 {
+            let user = format!("terra1user{}", i);
+            let info = mock_info(&user, &coins(1000u128 * i, USTC_DENOM));
+            execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        }
+        
+        // Corrupt storage: gap at index 1, user missing from the index entirely,
+        // orphaned reverse entry, wrong count and wrong total
+        let user2 = Addr::unchecked("terra1user2");
+        let user4 = Addr::unchecked("terra1user4");
+        let ghost = Addr::unchecked("terra1ghost");
+        USER_INDEX.remove(deps.as_mut().storage, 1);
+        USER_INDEX_REVERSE.remove(deps.as_mut().storage, &user2);
+        USER_INDEX.remove(deps.as_mut().storage, 3);
+        USER_INDEX_REVERSE.remove(deps.as_mut().storage, &user4);
+        USER_INDEX_REVERSE.save(deps.as_mut().storage, &ghost, &7).unwrap();
+        USERS.save(deps.as_mut().storage, &Addr::unchecked("terra1zero"), &Uint128::zero()).unwrap();
+        USER_COUNT.save(deps.as_mut().storage, &9).unwrap();
+        TOTAL_DEPOSITS.save(deps.as_mut().storage, &Uint128::from(1u128)).unwrap();
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::ValidateIndex { start: None, limit: None }).unwrap();
+        let validation: crate::msg::ValidateIndexResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert!(!validation.is_consistent);
+        
+        // Repair in small batches, following the returned cursor
+        let mut start = None;
+        let mut corrections = Vec::new();
+        let mut batches = 0;
+        loop {
+            let msg = ExecuteMsg::RepairIndex { start, limit: Some(2) };
+            let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+            let repair: crate::msg::RepairIndexResponse = cosmwasm_std::from_json(res.data.unwrap()).unwrap();
+            assert_eq!(
+                res.attributes.iter().filter(|attr| attr.key == "correction").count(),
+                repair.corrections.len()
+            );
+            corrections.extend(repair.corrections);
+            batches += 1;
+            if repair.complete {
+                assert!(repair.next_start.is_none());
+                break;
+            }
+            start = repair.next_start;
+        }
+        assert!(batches > 1);
+        assert!(corrections.iter().any(|c| c.contains("terra1ghost")));
+        assert!(corrections.iter().any(|c| c.contains("terra1zero")));
+        assert!(corrections.iter().any(|c| c.starts_with("Added missing user terra1user2")));
+        assert!(corrections.iter().any(|c| c.starts_with("User count corrected from 9 to 5")));
+        assert!(corrections.iter().any(|c| c.starts_with("Total deposits corrected from 1 to 15000")));
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::ValidateIndex { start: None, limit: None }).unwrap();
+        let validation: crate::msg::ValidateIndexResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert!(validation.is_consistent, "{:?}", validation.issues);
+        assert_eq!(validation.user_count_stored, 5);
+        assert!(USER_INDEX_REVERSE.may_load(deps.as_ref().storage, &ghost).unwrap().is_none());
+        
+        let query_msg = QueryMsg::GetAllUsers { start_after: None, limit: None };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let all: crate::msg::GetAllUsersResponse = cosmwasm_std::from_json(&res).unwrap();
+        let mut users: Vec<Addr> = all.users.into_iter().map(|(user, _)| user).collect();
+        users.sort();
+        let expected: Vec<Addr> = (1..=5).map(|i| Addr::unchecked(format!("terra1user{}", i))).collect();
+        assert_eq!(users, expected);
+        
+        // Deposits work again once the repair completed, and a clean index needs no corrections
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        let msg = ExecuteMsg::RepairIndex { start: None, limit: None };
+        let res = execute(deps.as_mut(), env, mock_info(OWNER, &[]), msg).unwrap();
+        let repair: crate::msg::RepairIndexResponse = cosmwasm_std::from_json(res.data.unwrap()).unwrap();
+        assert!(repair.complete);
+        assert!(repair.corrections.is_empty());
+    }
+    
+    #[test]
+    fn test_repair_index_authorization_and_cursor() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        for i in 1..=3 {
+            let user = format!("terra1user{}", i);
+            let info = mock_info(&user, &coins(1000u128, USTC_DENOM));
+            execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        }
+        
+        let msg = ExecuteMsg::RepairIndex { start: None, limit: None };
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Admin }));
+        
+        // Start a repair that does not finish in one batch
+        let msg = ExecuteMsg::RepairIndex { start: None, limit: Some(1) };
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        let repair: crate::msg::RepairIndexResponse = cosmwasm_std::from_json(res.data.unwrap()).unwrap();
+        assert!(!repair.complete);
+        let cursor = repair.next_start.unwrap();
+        
+        // Deposits are closed while the repair is running, withdrawals stay open
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap_err();
+        assert!(matches!(err, ContractError::RepairInProgress {}));
+        fund_contract(&mut deps);
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(1u128) };
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg).unwrap();
+        
+        // A stale cursor is rejected
+        let mut stale = cursor.clone();
+        stale.after_position = None;
+        let msg = ExecuteMsg::RepairIndex { start: Some(stale), limit: None };
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::RepairCursorMismatch {}));
+        
+        // Continuing from the returned cursor finishes the repair
+        let msg = ExecuteMsg::RepairIndex { start: Some(cursor), limit: None };
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        let repair: crate::msg::RepairIndexResponse = cosmwasm_std::from_json(res.data.unwrap()).unwrap();
+        assert!(repair.complete);
+        assert!(repair.corrections.is_empty());
+        execute(deps.as_mut(), env, mock_info(USER1, &coins(1u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+    }
+    
+    #[test]
+    fn test_validate_index_paginated_detects_all_inconsistencies() {
+        use crate::state::{TOTAL_DEPOSITS, USERS, USER_INDEX, USER_INDEX_REVERSE};
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        for i in 1..=5 {
+            let user = format!("terra1user{}", i);
+            let info = mock_info(&user, &coins(1000u128, USTC_DENOM));
+            execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        }
+        
+        // Consistent state paged two entries at a time
+        let mut start = None;
+        let mut pages = 0;
+        loop {
+            let query_msg = QueryMsg::ValidateIndex { start, limit: Some(2) };
+            let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+            let page: crate::msg::ValidateIndexResponse = cosmwasm_std::from_json(&res).unwrap();
+            assert!(page.is_consistent, "{:?}", page.issues);
+            pages += 1;
+            if page.complete {
+                assert_eq!(page.user_count_actual, 5);
+                assert_eq!(page.total_users_in_index, 5);
+                assert_eq!(page.total_deposits_actual, Uint128::from(5000u128));
+                break;
+            }
+            start = page.next_start;
+        }
+        assert!(pages > 1);
+        
+        // Corrupt storage in ways the forward pass alone cannot see
+        USER_INDEX_REVERSE.save(deps.as_mut().storage, &Addr::unchecked("terra1ghost"), &9).unwrap();
+        USERS.save(deps.as_mut().storage, &Addr::unchecked("terra1stray"), &Uint128::from(50u128)).unwrap();
+        TOTAL_DEPOSITS.save(deps.as_mut().storage, &Uint128::from(4000u128)).unwrap();
+        USER_INDEX.remove(deps.as_mut().storage, 4);
+        
+        let mut start = None;
+        let mut issues = Vec::new();
+        let last = loop {
+            let query_msg = QueryMsg::ValidateIndex { start, limit: Some(2) };
+            let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+            let page: crate::msg::ValidateIndexResponse = cosmwasm_std::from_json(&res).unwrap();
+            assert_eq!(page.is_consistent, page.issues.is_empty());
+            issues.extend(page.issues.clone());
+            if page.complete {
+                break page;
+            }
+            start = page.next_start;
+        };
+        
+        assert!(issues.contains(&"Gap detected: index 4 is missing".to_string()));
+        assert!(issues.iter().any(|i| i.starts_with("Orphaned reverse index entry: user terra1ghost")));
+        assert!(issues.iter().any(|i| i.starts_with("Orphaned reverse index entry: user terra1user5")));
+        assert!(issues.iter().any(|i| i.starts_with("User terra1stray with balance 50 is missing from the index")));
+        assert!(issues.iter().any(|i| i.starts_with("User terra1user5 with balance 1000 is missing")));
+        assert!(issues.contains(&"Total deposits mismatch: stored total is 4000, sum of user balances is 5050".to_string()));
+        assert!(issues.iter().any(|i| i.starts_with("User count mismatch")));
+        assert_eq!(last.user_count_actual, 4);
+        assert_eq!(last.total_deposits_stored, Uint128::from(4000u128));
+        assert!(last.next_start.is_none());
+    }
+    
+    #[test]
+    fn test_deposit_and_withdraw_emit_typed_events() {
+        use crate::events::ContractEvent;
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        
+        let env = mock_env();
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        assert!(res.attributes.iter().all(|attr| attr.key != "event"));
+        assert_eq!(res.events.len(), 1);
+        assert_eq!(res.events[0].ty, "ustc_preregister.deposit");
+        assert_eq!(res.events[0].attributes[0].key, "schema_version");
+        assert_eq!(ContractEvent::from_event(&res.events[0]).unwrap(), Some(ContractEvent::Deposit {
+            user: Addr::unchecked(USER1),
+            amount: Uint128::from(1000u128),
+            new_balance: Uint128::from(1000u128),
+            total_deposits: Uint128::from(1000u128),
+            new_user: true,
+        }));
+        
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(500u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        let event = ContractEvent::from_event(&res.events[0]).unwrap().unwrap();
+        assert!(matches!(event, ContractEvent::Deposit { new_user: false, .. }));
+        
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(1500u128) };
+        let res = execute(deps.as_mut(), env, mock_info(USER1, &[]), msg).unwrap();
+        assert_eq!(ContractEvent::from_event(&res.events[0]).unwrap(), Some(ContractEvent::Withdraw {
+            user: Addr::unchecked(USER1),
+            amount: Uint128::from(1500u128),
+            new_balance: Uint128::zero(),
+            total_deposits: Uint128::zero(),
+            user_removed: true,
+        }));
+    }
+    
+    #[test]
+    fn test_contract_events_round_trip() {
+        use crate::events::ContractEvent;
+        
+        let events = vec![
+            ContractEvent::Instantiate { owner: Addr::unchecked(OWNER), ustc_denom: USTC_DENOM.to_string(), soft_cap: None, deadline: None },
+            ContractEvent::Instantiate {
+                owner: Addr::unchecked(OWNER),
+                ustc_denom: USTC_DENOM.to_string(),
+                soft_cap: Some(Uint128::from(1_000u128)),
+                deadline: Some(1_700_000_000),
+            },
+            ContractEvent::Deposit {
+                user: Addr::unchecked(USER1),
+                amount: Uint128::from(10u128),
+                new_balance: Uint128::from(30u128),
+                total_deposits: Uint128::from(50u128),
+                new_user: false,
+            },
+            ContractEvent::Withdraw {
+                user: Addr::unchecked(USER2),
+                amount: Uint128::from(5u128),
+                new_balance: Uint128::from(1u128),
+                total_deposits: Uint128::from(45u128),
+                user_removed: false,
+            },
+            ContractEvent::OwnerWithdraw { destination: Addr::unchecked("terra1destination"), amount: Uint128::from(45u128) },
+            ContractEvent::ConfigUpdate { owner: Addr::unchecked("terra1newowner") },
+            ContractEvent::WithdrawalDestinationSet { destination: Addr::unchecked("terra1destination"), shares: vec![], unlock_timestamp: 1_700_000_000 },
+            ContractEvent::DistributionBracketsSet { thresholds: vec![Uint128::from(100u128), Uint128::from(1000u128)] },
+            ContractEvent::DistributionBracketsSet { thresholds: vec![] },
+            ContractEvent::IndexRepaired { complete: true, corrections: 3 },
+            ContractEvent::RoleGranted { role: Role::ConfigManager, address: Addr::unchecked(USER1) },
+            ContractEvent::RoleRevoked { role: Role::Guardian, address: Addr::unchecked(USER2) },
+            ContractEvent::PausedSet { paused: true },
+            ContractEvent::WithdrawalQueued { id: 3, user: Addr::unchecked(USER1), amount: Uint128::from(200u128) },
+            ContractEvent::QueuedWithdrawalPaid { id: 3, user: Addr::unchecked(USER1), amount: Uint128::from(200u128) },
+            ContractEvent::FundsReturned { sender: Addr::unchecked(OWNER), amount: Uint128::zero() },
+            ContractEvent::CampaignCancelled { user_count: 2, total_deposits: Uint128::from(500u128) },
+            ContractEvent::Refund { user: Addr::unchecked(USER2), amount: Uint128::from(200u128) },
+            ContractEvent::CampaignSettled { succeeded: false, total_deposits: Uint128::from(400u128) },
+            ContractEvent::TokensRecovered { token: "uluna".to_string(), recipient: Addr::unchecked(USER1), amount: Uint128::from(250u128) },
+            ContractEvent::DenomSet { denom: "utest".to_string(), decimals: 6 },
+            ContractEvent::HookAdded { hook: Addr::unchecked("terra1hook") },
+            ContractEvent::HookRemoved { hook: Addr::unchecked("terra1hook") },
+            ContractEvent::HookFailed { hook: Addr::unchecked("terra1hook"), error: "out of gas".to_string() },
+            ContractEvent::SweepLimitSet { limit: Some(SweepLimit { max_amount: Uint128::from(500u128), window: 86_400 }) },
+            ContractEvent::SweepLimitSet { limit: None },
+            ContractEvent::SweepLimitProposed { limit: None, effective_at: 1_700_000_000 },
+            ContractEvent::ReserveRatioSet { ratio: Decimal::percent(10) },
+            ContractEvent::ReserveRatioProposed { ratio: Decimal::zero(), effective_at: 1_700_000_000 },
+            ContractEvent::WithdrawalDestinationProposed {
+                destination: Addr::unchecked("terra1destination2"),
+                shares: vec![
+                    DestinationShare { address: Addr::unchecked("terra1destination2"), bps: 7_000 },
+                    DestinationShare { address: Addr::unchecked("terra1partner"), bps: 3_000 },
+                ],
+                unlock_timestamp: 1_700_000_000,
+                effective_at: 1_600_000_000,
+            },
+            ContractEvent::PendingDestinationCancelled { destination: Addr::unchecked("terra1destination2") },
+        ];
+        
+        for event in &events {
+            let decoded = ContractEvent::from_event(&event.to_event()).unwrap();
+            assert_eq!(decoded.as_ref(), Some(event));
+        }
+        
+        // Events from other contracts are skipped
+        let foreign = cosmwasm_std::Event::new("transfer").add_attribute("amount", "1");
+        assert_eq!(ContractEvent::from_event(&foreign).unwrap(), None);
+    }
+    
+    #[test]
+    fn test_decode_tx_events() {
+        use crate::error::EventDecodeError;
+        use crate::events::{decode_tx_events, ContractEvent};
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        let res = execute(deps.as_mut(), env, mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        // Wrap the emitted event the way the chain reports it in tx JSON
+        let event = &res.events[0];
+        let attributes: Vec<String> = event
+            .attributes
+            .iter()
+            .map(|attr| format!(r#"{{"key":"{}","value":"{}","index":true}}"#, attr.key, attr.value))
+            .collect();
+        let wasm_event = |contract: &str| format!(
+            r#"{{"type":"wasm-{}","attributes":[{{"key":"_contract_address","value":"{}","index":true}},{}]}}"#,
+            event.ty, contract, attributes.join(",")
+        );
+        let tx_json = format!(
+            r#"{{"height":"1","txhash":"ABC","logs":[],"events":[{{"type":"message","attributes":[{{"key":"action","value":"/cosmwasm.wasm.v1.MsgExecuteContract","index":true}}]}},{},{}]}}"#,
+            wasm_event("terra1contract"),
+            wasm_event("terra1other")
+        );
+        
+        let expected = ContractEvent::Deposit {
+            user: Addr::unchecked(USER1),
+            amount: Uint128::from(1000u128),
+            new_balance: Uint128::from(1000u128),
+            total_deposits: Uint128::from(1000u128),
+            new_user: true,
+        };
+        let decoded = decode_tx_events(&tx_json, Some("terra1contract")).unwrap();
+        assert_eq!(decoded, vec![expected.clone()]);
+        let decoded = decode_tx_events(&tx_json, None).unwrap();
+        assert_eq!(decoded, vec![expected.clone(), expected]);
+        
+        // Older nodes report events per message in logs
+        let logs_json = format!(r#"{{"logs":[{{"msg_index":0,"events":[{}]}}],"events":[]}}"#, wasm_event("terra1contract"));
+        assert_eq!(decode_tx_events(&logs_json, None).unwrap().len(), 1);
+        
+        // Unsupported schema versions and malformed events are rejected
+        let future = r#"{"events":[{"type":"wasm-ustc_preregister.deposit","attributes":[{"key":"schema_version","value":"2"}]}]}"#;
+        assert!(matches!(
+            decode_tx_events(future, None).unwrap_err(),
+            EventDecodeError::UnsupportedSchemaVersion { version: 2, .. }
+        ));
+        let missing = r#"{"events":[{"type":"wasm-ustc_preregister.withdraw","attributes":[{"key":"schema_version","value":"1"}]}]}"#;
+        assert!(matches!(
+            decode_tx_events(missing, None).unwrap_err(),
+            EventDecodeError::MissingAttribute { .. }
+        ));
+        assert!(matches!(decode_tx_events("not json", None).unwrap_err(), EventDecodeError::InvalidJson { .. }));
+    }
+    
+    #[test]
+    fn test_decode_legacy_tx_events() {
+        use crate::error::EventDecodeError;
+        use crate::events::{decode_legacy_tx_events, LegacyEvent};
+        
+        // Older nodes merge the calls of every contract into one `wasm` event
+        let tx_json = r#"{"logs":[{"msg_index":0,"events":[{"type":"wasm","attributes":[
+            {"key":"_contract_address","value":"terra1contract"},{"key":"action","value":"deposit"},{"key":"user","value":"terra1user1"},{"key":"amount","value":"1000"},{"key":"event","value":"deposit"},{"key":"event","value":"user_added"},
+            {"key":"_contract_address","value":"terra1other"},{"key":"action","value":"withdraw"},{"key":"user","value":"terra1user1"},{"key":"amount","value":"5"},
+            {"key":"_contract_address","value":"terra1contract"},{"key":"action","value":"update_config"},{"key":"owner","value":"terra1owner2"},{"key":"event","value":"config_updated"}
+        ]}]}]}"#;
+        let decoded = decode_legacy_tx_events(tx_json, Some("terra1contract")).unwrap();
+        assert_eq!(decoded, vec![
+            LegacyEvent::Deposit { user: Addr::unchecked("terra1user1"), amount: Uint128::from(1000u128) },
+            LegacyEvent::ConfigUpdate { owner: Addr::unchecked("terra1owner2") },
+        ]);
+        
+        // Without the contract address the calls cannot be told apart
+        assert!(matches!(
+            decode_legacy_tx_events(tx_json, None).unwrap_err(),
+            EventDecodeError::UnattributedLegacyEvent { action } if action == "deposit"
+        ));
+        
+        // Transactions with typed events are left to decode_tx_events
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        let res = execute(deps.as_mut(), mock_env(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        assert_eq!(
+            LegacyEvent::from_attributes(&res.attributes).unwrap(),
+            Some(LegacyEvent::Deposit { user: Addr::unchecked(USER1), amount: Uint128::from(1000u128) })
+        );
+        let typed = r#"{"events":[
+            {"type":"wasm","attributes":[{"key":"_contract_address","value":"terra1contract"},{"key":"action","value":"withdraw"},{"key":"user","value":"terra1user1"},{"key":"amount","value":"5"}]},
+            {"type":"wasm-ustc_preregister.paused_set","attributes":[{"key":"_contract_address","value":"terra1contract"},{"key":"schema_version","value":"1"},{"key":"paused","value":"true"}]}
+        ]}"#;
+        assert!(decode_legacy_tx_events(typed, Some("terra1contract")).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_roles_default_to_owner() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetRoles {}).unwrap();
+        let roles: crate::msg::GetRolesResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(roles.roles.len(), 4);
+        for (holders, role) in roles.roles.iter().zip(Role::ALL) {
+            assert_eq!(holders.role, role);
+            assert_eq!(holders.holders, vec![Addr::unchecked(OWNER)]);
+        }
+    }
+    
+    #[test]
+    fn test_role_separation() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let mut env = mock_env();
+        let treasurer = Addr::unchecked("terra1treasurer");
+        let manager = Addr::unchecked("terra1manager");
+        
+        // Only admins manage roles
+        let msg = ExecuteMsg::GrantRole { role: Role::Treasurer, address: treasurer.to_string() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Admin }));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.role_granted");
+        let msg = ExecuteMsg::GrantRole { role: Role::ConfigManager, address: manager.to_string() };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        for role in [Role::Treasurer, Role::ConfigManager] {
+            let msg = ExecuteMsg::RevokeRole { role, address: OWNER.to_string() };
+            execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        }
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetRoles {}).unwrap();
+        let roles: crate::msg::GetRolesResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(roles.roles[1].holders, vec![treasurer.clone()]);
+        assert_eq!(roles.roles[2].holders, vec![manager.clone()]);
+        
+        // The owner can no longer configure or sweep
+        let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60;
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "terra1dest".to_string(), shares: vec![], unlock_timestamp };
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::ConfigManager }));
+        execute(deps.as_mut(), env.clone(), mock_info(manager.as_str(), &[]), msg).unwrap();
+        
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(1000u128, USTC_DENOM));
+        env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60);
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Treasurer }));
+        let err = execute(deps.as_mut(), env.clone(), mock_info(manager.as_str(), &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Treasurer }));
+        execute(deps.as_mut(), env.clone(), mock_info(treasurer.as_str(), &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap();
+        
+        // The treasurer cannot grant itself more power
+        let msg = ExecuteMsg::GrantRole { role: Role::Admin, address: treasurer.to_string() };
+        let err = execute(deps.as_mut(), env, mock_info(treasurer.as_str(), &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Admin }));
+    }
+    
+    #[test]
+    fn test_last_admin_cannot_be_revoked() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        let msg = ExecuteMsg::RevokeRole { role: Role::Admin, address: OWNER.to_string() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::CannotRevokeLastAdmin {}));
+        
+        // With a second admin the owner can step down
+        let msg_grant = ExecuteMsg::GrantRole { role: Role::Admin, address: "terra1admin".to_string() };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg_grant).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        let msg = ExecuteMsg::RevokeRole { role: Role::Admin, address: "terra1admin".to_string() };
+        let err = execute(deps.as_mut(), env, mock_info("terra1admin", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::CannotRevokeLastAdmin {}));
+    }
+    
+    #[test]
+    fn test_ownership_transfer_moves_roles() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        let guardian = Addr::unchecked("terra1guardian");
+        let msg = ExecuteMsg::GrantRole { role: Role::Guardian, address: guardian.to_string() };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        let msg = ExecuteMsg::UpdateConfig { owner: Some("terra1newowner".to_string()) };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        let res = query(deps.as_ref(), env, QueryMsg::GetRoles {}).unwrap();
+        let roles: crate::msg::GetRolesResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(roles.roles[0].holders, vec![Addr::unchecked("terra1newowner")]);
+        assert_eq!(roles.roles[3].holders, vec![guardian, Addr::unchecked("terra1newowner")]);
+    }
+    
+    #[test]
+    fn test_guardian_pauses_deposits_only() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        
+        let env = mock_env();
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::SetPaused { paused: true }).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Guardian }));
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetPaused { paused: true }).unwrap();
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetConfig {}).unwrap();
+        let config: crate::msg::GetConfigResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert!(config.paused);
+        
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER2, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(400u128) };
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg).unwrap();
+        
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetPaused { paused: false }).unwrap();
+        execute(deps.as_mut(), env, mock_info(USER2, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+    }
+    
+    #[test]
+    fn test_migrate_grants_roles_to_owner() {
+        use crate::contract::migrate;
+        use crate::msg::MigrateMsg;
+        use crate::state::{PAUSED, ROLES};
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        // Simulate a contract deployed before roles existed
+        ROLES.clear(deps.as_mut().storage);
+        PAUSED.remove(deps.as_mut().storage);
+        
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetRoles {}).unwrap();
+        let roles: crate::msg::GetRolesResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert!(roles.roles.iter().all(|r| r.holders == vec![Addr::unchecked(OWNER)]));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let config: crate::msg::GetConfigResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert!(!config.paused);
+        
+        // Migrating again keeps roles handed out since
+        let msg = ExecuteMsg::RevokeRole { role: Role::Treasurer, address: OWNER.to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), msg).unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetRoles {}).unwrap();
+        let roles: crate::msg::GetRolesResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert!(roles.roles[1].holders.is_empty());
+    }
+    
+    #[test]
+    fn test_destination_change_is_staged() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let mut env = mock_env();
+        let week = 7 * 24 * 60 * 60;
+        let destination1 = Addr::unchecked("terra1destination1");
+        let destination2 = Addr::unchecked("terra1destination2");
+        
+        // The first destination applies immediately
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: destination1.to_string(), shares: vec![], unlock_timestamp: env.block.time.seconds() + week };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(1000u128, USTC_DENOM));
+        
+        // A later change waits out the delay
+        env.block.time = env.block.time.plus_seconds(week);
+        let unlock_timestamp2 = env.block.time.seconds() + week;
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: destination2.to_string(), shares: vec![], unlock_timestamp: unlock_timestamp2 };
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.withdrawal_destination_proposed");
+        let effective_at = env.block.time.seconds() + week;
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetDestinationSchedule {}).unwrap();
+        let schedule: crate::msg::GetDestinationScheduleResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(schedule.current, Some(destination1.clone()));
+        assert_eq!(schedule.change_delay, week);
+        let pending = schedule.pending.unwrap();
+        assert_eq!(pending.destination, destination2);
+        assert_eq!(pending.unlock_timestamp, unlock_timestamp2);
+        assert_eq!(pending.effective_at, effective_at);
+        
+        // Sweeps during the delay still go to the current destination
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: destination1.to_string(), amount: coins(1000u128, USTC_DENOM) })
+        );
+        
+        // Once the delay elapses the next sweep applies the change
+        env.block.time = env.block.time.plus_seconds(week);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetDestinationSchedule {}).unwrap();
+        let schedule: crate::msg::GetDestinationScheduleResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(schedule.current, Some(destination2.clone()));
+        assert_eq!(schedule.current_unlock_timestamp, unlock_timestamp2);
+        assert!(schedule.pending.is_none());
+        
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.withdrawal_destination_set");
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: destination2.to_string(), amount: coins(1000u128, USTC_DENOM) })
+        );
+    }
+    
+    #[test]
+    fn test_cancel_pending_destination() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        let week = 7 * 24 * 60 * 60;
+        let guardian = Addr::unchecked("terra1guardian");
+        let msg = ExecuteMsg::GrantRole { role: Role::Guardian, address: guardian.to_string() };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        // Nothing to cancel yet
+        let err = execute(deps.as_mut(), env.clone(), mock_info(guardian.as_str(), &[]), ExecuteMsg::CancelPendingDestination {}).unwrap_err();
+        assert!(matches!(err, ContractError::NoPendingDestination {}));
+        
+        let unlock_timestamp = env.block.time.seconds() + week;
+        for destination in ["terra1destination1", "terra1attacker"] {
+            let msg = ExecuteMsg::SetWithdrawalDestination { destination: destination.to_string(), shares: vec![], unlock_timestamp };
+            execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        }
+        
+        // Only config managers and guardians can cancel
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::CancelPendingDestination {}).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Guardian }));
+        
+        let res = execute(deps.as_mut(), env.clone(), mock_info(guardian.as_str(), &[]), ExecuteMsg::CancelPendingDestination {}).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.pending_destination_cancelled");
+        assert_eq!(res.attributes[1].value, "terra1attacker");
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetDestinationSchedule {}).unwrap();
+        let schedule: crate::msg::GetDestinationScheduleResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(schedule.current, Some(Addr::unchecked("terra1destination1")));
+        assert!(schedule.pending.is_none());
+        
+        // A matured change is already in effect and cannot be cancelled
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "terra1destination2".to_string(), shares: vec![], unlock_timestamp };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        let mut later = env.clone();
+        later.block.time = later.block.time.plus_seconds(week);
+        let err = execute(deps.as_mut(), later.clone(), mock_info(guardian.as_str(), &[]), ExecuteMsg::CancelPendingDestination {}).unwrap_err();
+        assert!(matches!(err, ContractError::NoPendingDestination {}));
+        let res = query(deps.as_ref(), later, QueryMsg::GetWithdrawalInfo {}).unwrap();
+        let info: crate::msg::GetWithdrawalInfoResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(info.destination, Some(Addr::unchecked("terra1destination2")));
+    }
+    
+    #[test]
+    fn test_owner_withdraw_split_between_shares() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let mut env = mock_env();
+        let destination = Addr::unchecked("terra1destination");
+        let partner = Addr::unchecked("terra1partner");
+        let reserve = Addr::unchecked("terra1reserve");
+        let shares = vec![
+            DestinationShare { address: destination.clone(), bps: 3_334 },
+            DestinationShare { address: partner.clone(), bps: 3_333 },
+            DestinationShare { address: reserve.clone(), bps: 3_333 },
+        ];
+        let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60;
+        let unchecked = shares
+            .iter()
+            .map(|share| UncheckedDestinationShare { address: share.address.to_string(), bps: share.bps })
+            .collect();
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: destination.to_string(), shares: unchecked, unlock_timestamp };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetWithdrawalInfo {}).unwrap();
+        let info: crate::msg::GetWithdrawalInfoResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(info.shares, shares);
+        
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(1000u128, USTC_DENOM));
+        env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60);
+        
+        // Every share rounds down to 333, the destination also gets the remainder
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(
+            sends,
+            vec![
+                cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: destination.to_string(), amount: coins(334u128, USTC_DENOM) }),
+                cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: partner.to_string(), amount: coins(333u128, USTC_DENOM) }),
+                cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: reserve.to_string(), amount: coins(333u128, USTC_DENOM) }),
+            ]
+        );
+        let legs: Vec<&str> = res.attributes.iter().filter(|a| a.key == "leg").map(|a| a.value.as_str()).collect();
+        assert_eq!(legs, vec!["terra1destination:334", "terra1partner:333", "terra1reserve:333"]);
+        assert_eq!(res.attributes[2].value, "1000");
+        assert_eq!(res.events.len(), 3);
+        
+        // Each leg is its own ledger entry
+        let res = query(deps.as_ref(), env, QueryMsg::GetActivity { start_after_seq: None, limit: None }).unwrap();
+        let ledger: crate::msg::GetActivityResponse = cosmwasm_std::from_json(&res).unwrap();
+        let sweeps: Vec<_> = ledger
+            .activities
+            .iter()
+            .filter(|a| matches!(a.kind, ActivityKind::OwnerWithdraw { .. }))
+            .collect();
+        assert_eq!(sweeps.len(), 3);
+        assert_eq!(sweeps[2].kind, ActivityKind::OwnerWithdraw { destination: reserve, amount: Uint128::from(333u128) });
+    }
+    
+    #[test]
+    fn test_owner_withdraw_partial_amount() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let mut env = mock_env();
+        let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60;
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "terra1destination".to_string(), shares: vec![], unlock_timestamp };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(1000u128, USTC_DENOM));
+        env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60);
+        
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::zero()) }).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAmount {}));
+        
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(1001u128)) }).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InsufficientContractBalance { requested, available }
+                if requested == Uint128::from(1001u128) && available == Uint128::from(1000u128)
+        ));
+        
+        let res = execute(deps.as_mut(), env, mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(400u128)) }).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: "terra1destination".to_string(), amount: coins(400u128, USTC_DENOM) })
+        );
+        assert_eq!(res.attributes[2].value, "400");
+    }
+    
+    #[test]
+    fn test_invalid_destination_shares() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let env = mock_env();
+        let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60;
+        let destination = Addr::unchecked("terra1destination");
+        let share = |address: &str, bps: u16| UncheckedDestinationShare { address: address.to_string(), bps };
+        
+        let invalid = vec![
+            // Does not sum to 10,000
+            vec![share("terra1destination", 5_000), share("terra1partner", 4_000)],
+            // Zero share
+            vec![share("terra1destination", 10_000), share("terra1partner", 0)],
+            // Duplicate address
+            vec![share("terra1destination", 5_000), share("terra1destination", 5_000)],
+            // Destination missing
+            vec![share("terra1partner", 5_000), share("terra1reserve", 5_000)],
+        ];
+        for shares in invalid {
+            let msg = ExecuteMsg::SetWithdrawalDestination { destination: destination.to_string(), shares, unlock_timestamp };
+            let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidDestinationShares { total: 10_000 }));
+        }
+        
+        // Shares are optional in JSON, existing clients keep working
+        let msg: ExecuteMsg = cosmwasm_std::from_json(
+            format!(r#"{{"set_withdrawal_destination":{{"destination":"terra1destination","unlock_timestamp":{}}}}}"#, unlock_timestamp).as_bytes(),
+        )
+        .unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        let msg: ExecuteMsg = cosmwasm_std::from_json(br#"{"owner_withdraw":{}}"#).unwrap();
+        assert_eq!(msg, ExecuteMsg::OwnerWithdraw { amount: None });
+    }
+    
+    #[test]
+    fn test_sweep_limit_per_window() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let mut env = mock_env();
+        let day = 24 * 60 * 60;
+        let unlock_timestamp = env.block.time.seconds() + 7 * day;
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "terra1destination".to_string(), shares: vec![], unlock_timestamp };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        // Only config managers set the limit, and it must be non-zero
+        let limit = SweepLimit { max_amount: Uint128::from(500u128), window: day };
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::SetSweepLimit { limit: Some(limit.clone()) }).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::ConfigManager }));
+        let invalid = SweepLimit { max_amount: Uint128::from(500u128), window: 0 };
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetSweepLimit { limit: Some(invalid) }).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSweepLimit {}));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetSweepLimit { limit: Some(limit.clone()) }).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.sweep_limit_set");
+        
+        deps.querier.update_balance(&env.contract.address, coins(1200u128, USTC_DENOM));
+        env.block.time = env.block.time.plus_seconds(7 * day);
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetSweepAllowance {}).unwrap();
+        let allowance: crate::msg::GetSweepAllowanceResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(allowance.limit, Some(limit.clone()));
+        assert_eq!(allowance.available, Uint128::from(500u128));
+        assert_eq!(allowance.resets_at, None);
+        
+        // A full sweep is capped at the limit
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap();
+        assert_eq!(res.attributes[2].value, "500");
+        deps.querier.update_balance(&env.contract.address, coins(700u128, USTC_DENOM));
+        let window_end = env.block.time.seconds() + day;
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetSweepAllowance {}).unwrap();
+        let allowance: crate::msg::GetSweepAllowanceResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(allowance.swept_in_window, Uint128::from(500u128));
+        assert_eq!(allowance.available, Uint128::zero());
+        assert_eq!(allowance.resets_at, Some(window_end));
+        
+        env.block.time = env.block.time.plus_seconds(day - 1);
+        for amount in [None, Some(Uint128::from(1u128))] {
+            let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount }).unwrap_err();
+            assert!(matches!(
+                err,
+                ContractError::SweepLimitExceeded { remaining, resets_at } if remaining.is_zero() && resets_at == window_end
+            ));
+        }
+        
+        // The first sweep stops counting once it is a full window old
+        env.block.time = env.block.time.plus_seconds(1);
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(300u128)) }).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(400u128, USTC_DENOM));
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(201u128)) }).unwrap_err();
+        assert!(matches!(err, ContractError::SweepLimitExceeded { remaining, .. } if remaining == Uint128::from(200u128)));
+        
+        // Removing the limit makes the whole balance available again, once the delay has passed
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetSweepLimit { limit: None }).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetSweepAllowance {}).unwrap();
+        let allowance: crate::msg::GetSweepAllowanceResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(allowance.limit, Some(limit));
+        assert_eq!(allowance.available, Uint128::from(200u128));
+        assert_eq!(allowance.pending.map(|pending| pending.limit), Some(None));
+        
+        env.block.time = env.block.time.plus_seconds(7 * day);
+        let res = query(deps.as_ref(), env, QueryMsg::GetSweepAllowance {}).unwrap();
+        let allowance: crate::msg::GetSweepAllowanceResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(allowance.limit, None);
+        assert_eq!(allowance.available, Uint128::from(400u128));
+        assert_eq!(allowance.resets_at, None);
+        assert_eq!(allowance.pending, None);
+    }
+    
+    #[test]
+    fn test_loosening_sweep_limit_is_delayed() {
+        use crate::events::ContractEvent;
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let mut env = mock_env();
+        let day = 24 * 60 * 60;
+        let msg = ExecuteMsg::SetWithdrawalDestination {
+            destination: "terra1destination".to_string(),
+            shares: vec![],
+            unlock_timestamp: env.block.time.seconds() + 7 * day,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        let limit = SweepLimit { max_amount: Uint128::from(500u128), window: day };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetSweepLimit { limit: Some(limit.clone()) }).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(5000u128, USTC_DENOM));
+        env.block.time = env.block.time.plus_seconds(7 * day);
+        
+        // Raising the limit is staged, sweeps in the same block are still throttled
+        let raised = SweepLimit { max_amount: Uint128::from(2000u128), window: day };
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetSweepLimit { limit: Some(raised.clone()) }).unwrap();
+        assert_eq!(res.events.len(), 1);
+        let event = ContractEvent::from_event(&res.events[0]).unwrap();
+        assert_eq!(event, Some(ContractEvent::SweepLimitProposed { limit: Some(raised.clone()), effective_at: env.block.time.seconds() + 7 * day }));
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(501u128)) }).unwrap_err();
+        assert!(matches!(err, ContractError::SweepLimitExceeded { .. }));
+        
+        // A shorter window allows more sweeps, so it is staged too
+        let shorter = SweepLimit { max_amount: Uint128::from(500u128), window: day / 2 };
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetSweepLimit { limit: Some(shorter) }).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.sweep_limit_proposed");
+        
+        // Tightening applies at once and discards the staged change
+        let tighter = SweepLimit { max_amount: Uint128::from(400u128), window: 2 * day };
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetSweepLimit { limit: Some(tighter.clone()) }).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.sweep_limit_set");
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetSweepAllowance {}).unwrap();
+        let allowance: crate::msg::GetSweepAllowanceResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(allowance.limit, Some(tighter));
+        assert_eq!(allowance.available, Uint128::from(400u128));
+        assert_eq!(allowance.pending, None);
+        
+        // A staged removal is applied by the first sweep after the delay
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetSweepLimit { limit: None }).unwrap();
+        env.block.time = env.block.time.plus_seconds(7 * day - 1);
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(401u128)) }).unwrap_err();
+        assert!(matches!(err, ContractError::SweepLimitExceeded { .. }));
+        
+        env.block.time = env.block.time.plus_seconds(1);
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap();
+        assert_eq!(ContractEvent::from_event(&res.events[0]).unwrap(), Some(ContractEvent::SweepLimitSet { limit: None }));
+        assert_eq!(res.attributes[2].value, "5000");
+    }
+    
+    #[test]
+    fn test_sweep_limit_rolls_across_window_boundary() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let mut env = mock_env();
+        let day = 24 * 60 * 60;
+        let msg = ExecuteMsg::SetWithdrawalDestination {
+            destination: "terra1destination".to_string(),
+            shares: vec![],
+            unlock_timestamp: env.block.time.seconds() + 7 * day,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        let limit = SweepLimit { max_amount: Uint128::from(500u128), window: day };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetSweepLimit { limit: Some(limit) }).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(2000u128, USTC_DENOM));
+        env.block.time = env.block.time.plus_seconds(7 * day);
+        let first_sweep = env.block.time.seconds();
+        
+        // 300 early in the window and the remaining 200 just before its first sweep turns a day old
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(300u128)) }).unwrap();
+        env.block.time = env.block.time.plus_seconds(day - 1);
+        let second_sweep = env.block.time.seconds();
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(200u128)) }).unwrap();
+        
+        // Right after the boundary only the first sweep has dropped out, not the whole limit
+        env.block.time = env.block.time.plus_seconds(1);
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(500u128)) }).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::SweepLimitExceeded { remaining, resets_at }
+                if remaining == Uint128::from(300u128) && resets_at == second_sweep + day
+        ));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap();
+        assert_eq!(res.attributes[2].value, "300");
+        assert_eq!(env.block.time.seconds(), first_sweep + day);
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetSweepAllowance {}).unwrap();
+        let allowance: crate::msg::GetSweepAllowanceResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(allowance.swept_in_window, Uint128::from(500u128));
+        assert_eq!(allowance.available, Uint128::zero());
+        assert_eq!(allowance.resets_at, Some(second_sweep + day));
+        
+        // Once the 200 sweep is a day old, 200 frees up again
+        env.block.time = env.block.time.plus_seconds(day - 1);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetSweepAllowance {}).unwrap();
+        let allowance: crate::msg::GetSweepAllowanceResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(allowance.swept_in_window, Uint128::from(300u128));
+        assert_eq!(allowance.available, Uint128::from(200u128));
+        assert_eq!(allowance.resets_at, Some(first_sweep + 2 * day));
+    }
+    
+    #[test]
+    fn test_reserve_ratio_limits_sweeps() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let mut env = mock_env();
+        let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60;
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "terra1destination".to_string(), shares: vec![], unlock_timestamp };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        // Only config managers set the ratio, and it cannot exceed 1
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::SetReserveRatio { ratio: Decimal::percent(10) }).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::ConfigManager }));
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetReserveRatio { ratio: Decimal::percent(101) }).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidReserveRatio {}));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetReserveRatio { ratio: Decimal::percent(10) }).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.reserve_ratio_set");
+        
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1005u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(1005u128, USTC_DENOM));
+        env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60);
+        
+        // The reserve is rounded up: 10% of 1005 keeps 101 behind
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLiquidity {}).unwrap();
+        let liquidity: crate::msg::GetLiquidityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(liquidity.balance, Uint128::from(1005u128));
+        assert_eq!(liquidity.total_deposits, Uint128::from(1005u128));
+        assert_eq!(liquidity.reserve_ratio, Decimal::percent(10));
+        assert_eq!(liquidity.required_reserve, Uint128::from(101u128));
+        assert_eq!(liquidity.sweepable, Uint128::from(904u128));
+        
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(905u128)) }).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientContractBalance { available, .. } if available == Uint128::from(904u128)));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap();
+        assert_eq!(res.attributes[2].value, "904");
+        deps.querier.update_balance(&env.contract.address, coins(101u128, USTC_DENOM));
+        
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap_err();
+        assert!(matches!(err, ContractError::NoBalanceToWithdraw {}));
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetSweepAllowance {}).unwrap();
+        let allowance: crate::msg::GetSweepAllowanceResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(allowance.available, Uint128::zero());
+        
+        // Users can exit from the reserve; beyond what the contract holds they are queued
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(200u128) };
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.events[1].ty, "ustc_preregister.withdrawal_queued");
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(101u128) };
+        let res = execute(deps.as_mut(), env, mock_info(USER1, &[]), msg).unwrap();
+        assert!(res.messages.is_empty());
+    }
+    
+    #[test]
+    fn test_lowering_reserve_ratio_is_delayed() {
+        use crate::events::ContractEvent;
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        let mut env = mock_env();
+        let day = 24 * 60 * 60;
+        let msg = ExecuteMsg::SetWithdrawalDestination {
+            destination: "terra1destination".to_string(),
+            shares: vec![],
+            unlock_timestamp: env.block.time.seconds() + 7 * day,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetReserveRatio { ratio: Decimal::percent(50) }).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(1000u128, USTC_DENOM));
+        env.block.time = env.block.time.plus_seconds(7 * day);
+        
+        // Dropping the reserve is staged, sweeps in the same block still leave it behind
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetReserveRatio { ratio: Decimal::zero() }).unwrap();
+        assert_eq!(res.events.len(), 1);
+        let event = ContractEvent::from_event(&res.events[0]).unwrap();
+        assert_eq!(event, Some(ContractEvent::ReserveRatioProposed { ratio: Decimal::zero(), effective_at: env.block.time.seconds() + 7 * day }));
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(501u128)) }).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientContractBalance { available, .. } if available == Uint128::from(500u128)));
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLiquidity {}).unwrap();
+        let liquidity: crate::msg::GetLiquidityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(liquidity.reserve_ratio, Decimal::percent(50));
+        assert_eq!(liquidity.pending_reserve_ratio.map(|pending| pending.ratio), Some(Decimal::zero()));
+        
+        // Raising the ratio applies at once and discards the staged change
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetReserveRatio { ratio: Decimal::percent(60) }).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.reserve_ratio_set");
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLiquidity {}).unwrap();
+        let liquidity: crate::msg::GetLiquidityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(liquidity.reserve_ratio, Decimal::percent(60));
+        assert_eq!(liquidity.pending_reserve_ratio, None);
+        assert_eq!(liquidity.sweepable, Uint128::from(400u128));
+        
+        // A staged lowering counts for queries once due and is applied by the next sweep
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetReserveRatio { ratio: Decimal::percent(10) }).unwrap();
+        env.block.time = env.block.time.plus_seconds(7 * day);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLiquidity {}).unwrap();
+        let liquidity: crate::msg::GetLiquidityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(liquidity.reserve_ratio, Decimal::percent(10));
+        assert_eq!(liquidity.sweepable, Uint128::from(900u128));
+        
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap();
+        assert_eq!(ContractEvent::from_event(&res.events[0]).unwrap(), Some(ContractEvent::ReserveRatioSet { ratio: Decimal::percent(10) }));
+        assert_eq!(res.attributes[2].value, "900");
+    }
+    
+    #[test]
+    fn test_withdraw_queue_fifo_settlement() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        let env = mock_env();
+        
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(500u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER2, &coins(500u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(100u128, USTC_DENOM));
+        
+        // Paid directly while the contract can cover it and nobody waits
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(100u128) }).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        deps.querier.update_balance(&env.contract.address, coins(50u128, USTC_DENOM));
+        
+        // Short on funds: the request is queued and the balance still deducted
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(300u128) }).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.attributes[3].value, "0");
+        assert_eq!(res.attributes[4].value, "0");
+        
+        // Later requests wait behind earlier ones even if the balance covers them
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER2, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(40u128) }).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.attributes[4].value, "1");
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(100u128) }).unwrap();
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetUserDeposit { user: USER1.to_string() }).unwrap();
+        let deposit: crate::msg::GetUserDepositResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(deposit.deposit, Uint128::zero());
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetQueuePosition { user: USER1.to_string() }).unwrap();
+        let position: crate::msg::GetQueuePositionResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(position.entries.len(), 2);
+        assert_eq!(position.entries[0].position, 0);
+        assert_eq!(position.entries[1].position, 2);
+        assert_eq!(position.total_queued, Uint128::from(440u128));
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetWithdrawQueue { start_after: None, limit: Some(2) }).unwrap();
+        let page: crate::msg::GetWithdrawQueueResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(page.entries.len(), 2);
+        assert_eq!(page.length, 3);
+        assert_eq!(page.next, Some(1));
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetWithdrawQueue { start_after: page.next, limit: Some(2) }).unwrap();
+        let page: crate::msg::GetWithdrawQueueResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].entry.user, Addr::unchecked(USER1));
+        assert_eq!(page.next, None);
+        
+        // Queued funds are not sweepable
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLiquidity {}).unwrap();
+        let liquidity: crate::msg::GetLiquidityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(liquidity.queued, Uint128::from(440u128));
+        assert_eq!(liquidity.sweepable, Uint128::zero());
+        
+        // Only admins and treasurers return funds
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::ReturnFunds {}).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Treasurer }));
+        
+        // Settles in order and stops at the first entry the balance cannot cover
+        deps.querier.update_balance(&env.contract.address, coins(400u128, USTC_DENOM));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &coins(350u128, USTC_DENOM)), ExecuteMsg::ReturnFunds {}).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: USER1.to_string(), amount: coins(300u128, USTC_DENOM) })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: USER2.to_string(), amount: coins(40u128, USTC_DENOM) })
+        );
+        assert_eq!(res.events[0].ty, "ustc_preregister.funds_returned");
+        assert_eq!(res.events[1].ty, "ustc_preregister.queued_withdrawal_paid");
+        assert_eq!(res.attributes[2].value, "2");
+        assert_eq!(res.attributes[3].value, "100");
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetQueuePosition { user: USER1.to_string() }).unwrap();
+        let position: crate::msg::GetQueuePositionResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(position.entries.len(), 1);
+        assert_eq!(position.entries[0].entry.id, 2);
+        assert_eq!(position.entries[0].position, 0);
+        
+        // A call without funds settles whatever the balance now covers
+        deps.querier.update_balance(&env.contract.address, coins(100u128, USTC_DENOM));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::ReturnFunds {}).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.attributes[3].value, "0");
+        
+        // With the queue drained, withdrawals are paid directly again
+        let res = execute(deps.as_mut(), env, mock_info(USER2, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(60u128) }).unwrap();
+        assert_eq!(res.messages.len(), 1);
+    }
+    
+    #[test]
+    fn test_cancel_campaign_and_refunds() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        let mut env = mock_env();
+        
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(300u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER2, &coins(200u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(200u128, USTC_DENOM));
+        
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::ProcessRefunds { limit: None }).unwrap_err();
+        assert!(matches!(err, ContractError::CampaignNotCancelled {}));
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::CancelCampaign {}).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Admin }));
+        
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::CancelCampaign {}).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.campaign_cancelled");
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::CancelCampaign {}).unwrap_err();
+        assert!(matches!(err, ContractError::CampaignCancelled {}));
+        
+        // Deposits and sweeps are blocked
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(10u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap_err();
+        assert!(matches!(err, ContractError::CampaignCancelled {}));
+        let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60;
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "terra1destination".to_string(), shares: vec![], unlock_timestamp };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60);
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap_err();
+        assert!(matches!(err, ContractError::CampaignCancelled {}));
+        
+        // Anyone can push refunds, last indexed user first
+        let res = execute(deps.as_mut(), env.clone(), mock_info("terra1anyone", &[]), ExecuteMsg::ProcessRefunds { limit: Some(1) }).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: USER2.to_string(), amount: coins(200u128, USTC_DENOM) })
+        );
+        assert_eq!(res.events[0].ty, "ustc_preregister.refund");
+        assert_eq!(res.attributes[3].value, "1");
+        deps.querier.update_balance(&env.contract.address, coins(0u128, USTC_DENOM));
+        
+        // A refund the balance cannot cover is queued instead
+        let res = execute(deps.as_mut(), env.clone(), mock_info("terra1anyone", &[]), ExecuteMsg::ProcessRefunds { limit: None }).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.events[1].ty, "ustc_preregister.withdrawal_queued");
+        assert_eq!(res.attributes[4].value, "1");
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetRefundProgress {}).unwrap();
+        let progress: crate::msg::GetRefundProgressResponse = cosmwasm_std::from_json(&res).unwrap();
+        let cancellation = progress.cancellation.unwrap();
+        assert_eq!(cancellation.users_at_cancel, 2);
+        assert_eq!(cancellation.deposits_at_cancel, Uint128::from(500u128));
+        assert_eq!(cancellation.refunded_users, 2);
+        assert_eq!(cancellation.refunded_amount, Uint128::from(500u128));
+        assert_eq!(progress.remaining_users, 0);
+        assert_eq!(progress.remaining_deposits, Uint128::zero());
+        
+        deps.querier.update_balance(&env.contract.address, coins(300u128, USTC_DENOM));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::ReturnFunds {}).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: USER1.to_string(), amount: coins(300u128, USTC_DENOM) })
+        );
+        
+        // Nothing left to refund
+        let res = execute(deps.as_mut(), env, mock_info("terra1anyone", &[]), ExecuteMsg::ProcessRefunds { limit: None }).unwrap();
+        assert!(res.messages.is_empty());
+    }
+    
+    #[test]
+    fn test_refunds_queue_uncovered_tail_user() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        let env = mock_env();
+        
+        // The large depositor is last in the index and exceeds what is left in the contract
+        for (user, amount) in [(USER1, 100u128), (USER2, 200), ("terra1whale", 5000)] {
+            execute(deps.as_mut(), env.clone(), mock_info(user, &coins(amount, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        }
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::CancelCampaign {}).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(1000u128, USTC_DENOM));
+        
+        // The whale is queued and everyone behind them is still refunded
+        let res = execute(deps.as_mut(), env.clone(), mock_info("terra1anyone", &[]), ExecuteMsg::ProcessRefunds { limit: None }).unwrap();
+        let sends: Vec<_> = res.messages.iter().map(|m| m.msg.clone()).collect();
+        assert_eq!(
+            sends,
+            vec![
+                cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: USER2.to_string(), amount: coins(200u128, USTC_DENOM) }),
+                cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: USER1.to_string(), amount: coins(100u128, USTC_DENOM) }),
+            ]
+        );
+        assert_eq!(res.attributes[1].value, "3");
+        assert_eq!(res.attributes[3].value, "0");
+        assert_eq!(res.attributes[4].value, "1");
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetQueuePosition { user: "terra1whale".to_string() }).unwrap();
+        let position: crate::msg::GetQueuePositionResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(position.entries.len(), 1);
+        assert_eq!(position.entries[0].entry.amount, Uint128::from(5000u128));
+        assert_eq!(position.total_queued, Uint128::from(5000u128));
+        
+        // The queued refund keeps its claim on the balance until funds are returned
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLiquidity {}).unwrap();
+        let liquidity: crate::msg::GetLiquidityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(liquidity.queued, Uint128::from(5000u128));
+        
+        // The queried balance already includes the returned funds
+        deps.querier.update_balance(&env.contract.address, coins(5000u128, USTC_DENOM));
+        let res = execute(deps.as_mut(), env, mock_info(OWNER, &coins(4300u128, USTC_DENOM)), ExecuteMsg::ReturnFunds {}).unwrap();
+        assert_eq!(res.attributes[2].value, "1");
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: "terra1whale".to_string(), amount: coins(5000u128, USTC_DENOM) })
+        );
+    }
+    
+    #[test]
+    fn test_campaign_soft_cap() {
+        let env = mock_env();
+        let now = env.block.time.seconds();
+        let instantiate_msg = |soft_cap: Option<u128>, deadline: Option<u64>| InstantiateMsg {
+            owner: OWNER.to_string(),
+            soft_cap: soft_cap.map(Uint128::from),
+            deadline,
+            denom: None,
+            decimals: None,
+        };
+        
+        // Cap and deadline go together, with a non-zero cap and a future deadline
+        for (soft_cap, deadline) in [(Some(1000), None), (None, Some(now + 100)), (Some(0), Some(now + 100)), (Some(1000), Some(now))] {
+            let mut deps = mock_dependencies();
+            let err = instantiate(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), instantiate_msg(soft_cap, deadline)).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidCampaignTarget {}));
+        }
+        
+        // Missing the soft cap: refund-only
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), instantiate_msg(Some(1000), Some(now + 100))).unwrap();
+        fund_contract(&mut deps);
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(400u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "terra1destination".to_string(), shares: vec![], unlock_timestamp: now + 7 * 24 * 60 * 60 };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetCampaignStatus {}).unwrap();
+        let status: crate::msg::GetCampaignStatusResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(status.status, CampaignStatus::Active);
+        assert_eq!(status.shortfall, Uint128::from(600u128));
+        
+        let mut late = env.clone();
+        late.block.time = late.block.time.plus_seconds(7 * 24 * 60 * 60);
+        let res = query(deps.as_ref(), late.clone(), QueryMsg::GetCampaignStatus {}).unwrap();
+        let status: crate::msg::GetCampaignStatusResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(status.status, CampaignStatus::Failed);
+        
+        let err = execute(deps.as_mut(), late.clone(), mock_info(USER1, &coins(600u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap_err();
+        assert!(matches!(err, ContractError::CampaignEnded {}));
+        let err = execute(deps.as_mut(), late.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap_err();
+        assert!(matches!(err, ContractError::CampaignFailed {}));
+        
+        // Withdrawals stay open and record the outcome
+        let res = execute(deps.as_mut(), late.clone(), mock_info(USER1, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(100u128) }).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.campaign_settled");
+        assert_eq!(res.events[0].attributes[1].value, "false");
+        let res = execute(deps.as_mut(), late.clone(), mock_info(USER2, &[]), ExecuteMsg::ProcessRefunds { limit: None }).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: USER1.to_string(), amount: coins(300u128, USTC_DENOM) })
+        );
+        
+        // Reaching the soft cap: sweeps open, and later withdrawals do not undo the outcome
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), instantiate_msg(Some(500), Some(now + 100))).unwrap();
+        fund_contract(&mut deps);
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(600u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "terra1destination".to_string(), shares: vec![], unlock_timestamp: now + 7 * 24 * 60 * 60 };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        let res = execute(deps.as_mut(), late.clone(), mock_info(USER1, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(200u128) }).unwrap();
+        assert_eq!(res.events[0].attributes[1].value, "true");
+        let res = query(deps.as_ref(), late.clone(), QueryMsg::GetCampaignStatus {}).unwrap();
+        let status: crate::msg::GetCampaignStatusResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(status.status, CampaignStatus::Succeeded);
+        assert_eq!(status.total_deposits, Uint128::from(400u128));
+        
+        let err = execute(deps.as_mut(), late.clone(), mock_info(USER2, &[]), ExecuteMsg::ProcessRefunds { limit: None }).unwrap_err();
+        assert!(matches!(err, ContractError::CampaignNotCancelled {}));
+        execute(deps.as_mut(), late, mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(100u128)) }).unwrap();
+    }
+    
+    #[test]
+    fn test_campaign_soft_cap_blocks_early_sweeps() {
+        let env = mock_env();
+        let now = env.block.time.seconds();
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            owner: OWNER.to_string(),
+            soft_cap: Some(Uint128::from(1000u128)),
+            deadline: Some(now + 8 * 24 * 60 * 60),
+            denom: None,
+            decimals: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(400u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER2, &coins(300u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        deps.querier.update_balance(env.contract.address.clone(), coins(700u128, USTC_DENOM));
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "terra1destination".to_string(), shares: vec![], unlock_timestamp: now + 7 * 24 * 60 * 60 };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        
+        // Unlocked but before the deadline: deposits are still at stake
+        let mut unlocked = env.clone();
+        unlocked.block.time = unlocked.block.time.plus_seconds(7 * 24 * 60 * 60);
+        let err = execute(deps.as_mut(), unlocked.clone(), mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap_err();
+        assert!(matches!(err, ContractError::CampaignInProgress {}));
+        let err = execute(deps.as_mut(), unlocked, mock_info(OWNER, &[]), ExecuteMsg::OwnerWithdraw { amount: Some(Uint128::from(100u128)) }).unwrap_err();
+        assert!(matches!(err, ContractError::CampaignInProgress {}));
+        
+        // The cap is missed and every depositor gets their full balance back
+        let mut late = env;
+        late.block.time = late.block.time.plus_seconds(8 * 24 * 60 * 60);
+        let res = execute(deps.as_mut(), late.clone(), mock_info(USER2, &[]), ExecuteMsg::ProcessRefunds { limit: None }).unwrap();
+        let refunded: Uint128 = res
+            .messages
+            .iter()
+            .filter_map(|sub| match &sub.msg {
+                cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { amount, .. }) => Some(amount[0].amount),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(refunded, Uint128::from(700u128));
+        let res = query(deps.as_ref(), late, QueryMsg::GetRefundProgress {}).unwrap();
+        let progress: crate::msg::GetRefundProgressResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(progress.remaining_users, 0);
+        assert_eq!(progress.remaining_deposits, Uint128::zero());
+    }
+    
+    #[test]
+    fn test_recover_tokens() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        let env = mock_env();
+        deps.querier.update_balance(&env.contract.address, vec![coin(1_000u128, USTC_DENOM), coin(250u128, "uluna")]);
+        deps.querier.update_wasm(|query| match query {
+            cosmwasm_std::WasmQuery::Smart { contract_addr, .. } if contract_addr == "terra1cw20" => {
+                let balance = cw20::BalanceResponse { balance: Uint128::from(75u128) };
+                cosmwasm_std::SystemResult::Ok(cosmwasm_std::ContractResult::Ok(cosmwasm_std::to_json_binary(&balance).unwrap()))
+            },
+            _ => cosmwasm_std::SystemResult::Err(cosmwasm_std::SystemError::UnsupportedRequest { kind: "wasm".to_string() }),
+        });
+        let recover = |denom_or_cw20: RecoverableToken| ExecuteMsg::RecoverTokens {
+            denom_or_cw20,
+            recipient: "terra1recipient".to_string(),
+        };
+        
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), recover(RecoverableToken::Native { denom: "uluna".to_string() })).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Admin }));
+        
+        // The deposit denom belongs to depositors
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), recover(RecoverableToken::Native { denom: USTC_DENOM.to_string() })).unwrap_err();
+        assert!(matches!(err, ContractError::CannotRecoverTrackedAsset { .. }));
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), recover(RecoverableToken::Native { denom: "ukrw".to_string() })).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToRecover { .. }));
+        
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), recover(RecoverableToken::Native { denom: "uluna".to_string() })).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: "terra1recipient".to_string(), amount: coins(250u128, "uluna") })
+        );
+        assert_eq!(res.events[0].ty, "ustc_preregister.tokens_recovered");
+        
+        let res = execute(deps.as_mut(), env, mock_info(OWNER, &[]), recover(RecoverableToken::Cw20 { address: "terra1cw20".to_string() })).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Wasm(cosmwasm_std::WasmMsg::Execute {
+                contract_addr: "terra1cw20".to_string(),
+                msg: cosmwasm_std::to_json_binary(&cw20::Cw20ExecuteMsg::Transfer {
+                    recipient: "terra1recipient".to_string(),
+                    amount: Uint128::from(75u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+    }
+    
+    #[test]
+    fn test_nonpayable_and_deposit_refunds() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        let env = mock_env();
+        
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(500u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        // Funds on messages that take none are rejected instead of becoming untracked balance
+        let msgs = [
+            ExecuteMsg::Withdraw { amount: Uint128::from(100u128) },
+            ExecuteMsg::UpdateConfig { owner: None },
+            ExecuteMsg::OwnerWithdraw { amount: None },
+            ExecuteMsg::SetPaused { paused: true },
+        ];
+        for msg in msgs {
+            let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &coins(1u128, USTC_DENOM)), msg).unwrap_err();
+            assert!(matches!(err, ContractError::NonPayable {}));
+        }
+        
+        // Deposits keep the USTC and send other denominations back
+        let funds = vec![coin(300u128, USTC_DENOM), coin(40u128, "uluna"), coin(7u128, "ukrw")];
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &funds), ExecuteMsg::Deposit {}).unwrap();
+        assert_eq!(res.attributes[2].value, "300");
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: USER1.to_string(), amount: vec![coin(40u128, "uluna"), coin(7u128, "ukrw")] })
+        );
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetUserDeposit { user: USER1.to_string() }).unwrap();
+        let deposit: crate::msg::GetUserDepositResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(deposit.deposit, Uint128::from(800u128));
+        
+        // Without any USTC the deposit is still rejected
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(40u128, "uluna")), ExecuteMsg::Deposit {}).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDenom { .. }));
+        let err = execute(deps.as_mut(), env, mock_info(USER1, &[]), ExecuteMsg::Deposit {}).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAmount {}));
+    }
+    
+    #[test]
+    fn test_configurable_denom() {
+        use crate::state::DISTRIBUTION_THRESHOLDS;
+        
+        let env = mock_env();
+        let instantiate_msg = |denom: Option<&str>, decimals: Option<u8>| InstantiateMsg {
+            owner: OWNER.to_string(),
+            soft_cap: None,
+            deadline: None,
+            denom: denom.map(str::to_string),
+            decimals,
+        };
+        
+        // Defaults to uusd with 6 decimals
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), instantiate_msg(None, None)).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetConfig {}).unwrap();
+        let config: crate::msg::GetConfigResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(config.ustc_denom, USTC_DENOM);
+        assert_eq!(config.decimals, 6);
+        assert_eq!(DISTRIBUTION_THRESHOLDS.load(&deps.storage).unwrap()[0], Uint128::from(1_000_000_000u128));
+        
+        for denom in ["ab", "1usd", "u usd", "uusd!"] {
+            let mut deps = mock_dependencies();
+            let err = instantiate(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), instantiate_msg(Some(denom), None)).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidDenomName { .. }));
+        }
+        let mut deps = mock_dependencies();
+        let err = instantiate(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), instantiate_msg(None, Some(19))).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDecimals { max: 18 }));
+        
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), instantiate_msg(Some("ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2"), Some(8))).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(100u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDenom { .. }));
+        let funds = coins(100u128, "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2");
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &funds), ExecuteMsg::Deposit {}).unwrap();
+        
+        // Default brackets are whole-token amounts in the denom's own decimals
+        let thresholds = DISTRIBUTION_THRESHOLDS.load(&deps.storage).unwrap();
+        assert_eq!(thresholds[0], Uint128::from(100_000_000_000u128));
+        assert_eq!(thresholds[3], Uint128::from(100_000_000_000_000u128));
+        
+        // Only changeable while nothing is deposited
+        let msg = ExecuteMsg::SetDenom { denom: "utest".to_string(), decimals: 6 };
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::ConfigManager }));
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::DenomLocked {}));
+        
+        deps.querier.update_balance(&env.contract.address, funds);
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(100u128) }).unwrap();
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        assert_eq!(res.events[0].ty, "ustc_preregister.denom_set");
+        assert_eq!(res.events[1].ty, "ustc_preregister.distribution_brackets_set");
+        assert_eq!(DISTRIBUTION_THRESHOLDS.load(&deps.storage).unwrap()[0], Uint128::from(1_000_000_000u128));
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetConfig {}).unwrap();
+        let config: crate::msg::GetConfigResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(config.ustc_denom, "utest");
+        assert_eq!(config.decimals, 6);
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(50u128, "utest")), ExecuteMsg::Deposit {}).unwrap();
+        
+        // Brackets chosen by the config manager survive a change of decimals
+        let custom = vec![Uint128::from(10u128), Uint128::from(100u128)];
+        deps.querier.update_balance(&env.contract.address, coins(50u128, "utest"));
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(50u128) }).unwrap();
+        let msg = ExecuteMsg::SetDistributionBrackets { thresholds: custom.clone() };
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        let msg = ExecuteMsg::SetDenom { denom: "uother".to_string(), decimals: 18 };
+        let res = execute(deps.as_mut(), env, mock_info(OWNER, &[]), msg).unwrap();
+        assert_eq!(res.events.len(), 1);
+        assert_eq!(DISTRIBUTION_THRESHOLDS.load(&deps.storage).unwrap(), custom);
+    }
+    
+    #[test]
+    fn test_invalid_addresses_rejected() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        let env = mock_env();
+        let admin = || mock_info(OWNER, &[]);
+        
+        let msg = ExecuteMsg::UpdateConfig { owner: Some("Terra1NewOwner".to_string()) };
+        let err = execute(deps.as_mut(), env.clone(), admin(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidOwnerAddress { address } if address == "Terra1NewOwner"));
+        
+        let msg = ExecuteMsg::GrantRole { role: Role::Treasurer, address: "t1".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), admin(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRoleAddress { address } if address == "t1"));
+        let msg = ExecuteMsg::RevokeRole { role: Role::Admin, address: "Terra1Owner".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), admin(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRoleAddress { .. }));
+        
+        let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60;
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "Terra1Treasury".to_string(), shares: vec![], unlock_timestamp };
+        let err = execute(deps.as_mut(), env.clone(), admin(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDestinationAddress { address } if address == "Terra1Treasury"));
+        let shares = vec![
+            UncheckedDestinationShare { address: "terra1treasury".to_string(), bps: 5_000 },
+            UncheckedDestinationShare { address: "Terra1Partner".to_string(), bps: 5_000 },
+        ];
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "terra1treasury".to_string(), shares, unlock_timestamp };
+        let err = execute(deps.as_mut(), env.clone(), admin(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDestinationAddress { address } if address == "Terra1Partner"));
+        
+        let msg = ExecuteMsg::RecoverTokens {
+            denom_or_cw20: RecoverableToken::Native { denom: "uluna".to_string() },
+            recipient: "Terra1Recipient".to_string(),
+        };
+        let err = execute(deps.as_mut(), env.clone(), admin(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRecipientAddress { .. }));
+        let msg = ExecuteMsg::RecoverTokens {
+            denom_or_cw20: RecoverableToken::Cw20 { address: "Terra1Cw20".to_string() },
+            recipient: "terra1recipient".to_string(),
+        };
+        let err = execute(deps.as_mut(), env.clone(), admin(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTokenAddress { address } if address == "Terra1Cw20"));
+        
+        // Cursors passed back by the caller are validated too
+        let start = crate::msg::RepairIndexCursor {
+            phase: crate::state::RepairPhase::Users,
+            after_position: None,
+            after_address: Some("Terra1User1".to_string()),
+        };
+        let err = execute(deps.as_mut(), env.clone(), admin(), ExecuteMsg::RepairIndex { start: Some(start), limit: None }).unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)));
+        let start = crate::msg::ValidateIndexCursor {
+            phase: crate::msg::ValidatePhase::Users,
+            after_position: None,
+            after_address: Some("Terra1User1".to_string()),
+            total_users_in_index: 0,
+            user_count_actual: 0,
+            total_deposits_actual: Uint128::zero(),
+        };
+        assert!(query(deps.as_ref(), env.clone(), QueryMsg::ValidateIndex { start: Some(start), limit: None }).is_err());
+        
+        // Queries reject malformed addresses instead of silently reporting zero
+        let query_msg = QueryMsg::GetUserDeposit { user: "Terra1User1".to_string() };
+        assert!(query(deps.as_ref(), env.clone(), query_msg).is_err());
+        let query_msg = QueryMsg::GetUserDeposit { user: USER1.to_string() };
+        assert!(query(deps.as_ref(), env, query_msg).is_ok());
+    }
+    
+    #[test]
+    fn test_sudo_set_destination_discards_pending() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        let env = mock_env();
+        let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60;
+        
+        // The second change is staged behind the change delay
+        for destination in ["terra1treasury", "terra1attacker"] {
+            let msg = ExecuteMsg::SetWithdrawalDestination { destination: destination.to_string(), shares: vec![], unlock_timestamp };
+            execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        }
+        assert!(crate::state::PENDING_DESTINATION.load(&deps.storage).unwrap().is_some());
+        
+        let msg = crate::msg::SudoMsg::SetWithdrawalDestination { destination: "terra1safe".to_string(), shares: vec![], unlock_timestamp };
+        let res = crate::contract::sudo(deps.as_mut(), env.clone(), msg).unwrap();
+        assert_eq!(res.events.len(), 2);
+        assert!(crate::state::PENDING_DESTINATION.load(&deps.storage).unwrap().is_none());
+        let destination = crate::state::WITHDRAWAL_DESTINATION.load(&deps.storage).unwrap();
+        assert_eq!(destination, Some(Addr::unchecked("terra1safe")));
+        
+        // The unlock timestamp rule still applies
+        let msg = crate::msg::SudoMsg::SetWithdrawalDestination { destination: "terra1safe".to_string(), shares: vec![], unlock_timestamp: env.block.time.seconds() };
+        let err = crate::contract::sudo(deps.as_mut(), env, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTimestamp {}));
+    }
+    
+    #[test]
+    fn test_balance_change_hooks() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        let env = mock_env();
+        let add = |hook: &str| ExecuteMsg::AddHook { addr: hook.to_string() };
+        
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), add("terra1points")).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { role: Role::Admin }));
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), add("Terra1Points")).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidHookAddress { .. }));
+        
+        for hook in ["terra1points", "terra1convert"] {
+            let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), add(hook)).unwrap();
+            assert_eq!(res.events[0].ty, "ustc_preregister.hook_added");
+        }
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), add("terra1points")).unwrap_err();
+        assert!(matches!(err, ContractError::HookAlreadyRegistered { .. }));
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::ListHooks {}).unwrap();
+        let hooks: crate::msg::ListHooksResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(hooks.hooks, vec![Addr::unchecked("terra1convert"), Addr::unchecked("terra1points")]);
+        
+        // Every hook hears about the deposit, in address order, and replies only on error
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(1000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        let expected = |reply_id: u64, hook: &str, old_balance: u128, new_balance: u128| {
+            let msg = crate::msg::BalanceChangedHookMsg {
+                user: USER1.to_string(),
+                old_balance: Uint128::from(old_balance),
+                new_balance: Uint128::from(new_balance),
+            }
+            .into_cosmos_msg(hook.to_string())
+            .unwrap();
+            cosmwasm_std::SubMsg::reply_on_error(msg, reply_id).with_gas_limit(crate::state::HOOK_GAS_LIMIT)
+        };
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(res.messages[0], expected(1, "terra1convert", 0, 1000));
+        assert_eq!(res.messages[1], expected(0, "terra1points", 0, 1000));
+        
+        // Withdrawals notify the remaining hooks next to the payout
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::RemoveHook { addr: "terra1convert".to_string() }).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::RemoveHook { addr: "terra1convert".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::HookNotRegistered { .. }));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(400u128) }).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(res.messages[0], expected(0, "terra1points", 1000, 600));
+        assert!(matches!(res.messages[1].msg, cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { .. })));
+        
+        // The registry is capped
+        for i in 0..crate::state::MAX_HOOKS - 1 {
+            execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), add(&format!("terra1hook{}", i))).unwrap();
+        }
+        let err = execute(deps.as_mut(), env, mock_info(OWNER, &[]), add("terra1extra")).unwrap_err();
+        assert!(matches!(err, ContractError::TooManyHooks { max: 10 }));
+    }
+    
+    #[test]
+    fn test_hooks_on_refunds_and_queued_withdrawals() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        let env = mock_env();
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::AddHook { addr: "terra1points".to_string() }).unwrap();
+        let expected = |user: &str, old_balance: u128, new_balance: u128| {
+            let msg = crate::msg::BalanceChangedHookMsg {
+                user: user.to_string(),
+                old_balance: Uint128::from(old_balance),
+                new_balance: Uint128::from(new_balance),
+            }
+            .into_cosmos_msg("terra1points".to_string())
+            .unwrap();
+            cosmwasm_std::SubMsg::reply_on_error(msg, 0).with_gas_limit(crate::state::HOOK_GAS_LIMIT)
+        };
+        
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(300u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER2, &coins(200u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        // The balance drops when the withdrawal is queued, the payout is not a balance change
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(100u128) }).unwrap();
+        assert_eq!(res.messages, vec![expected(USER1, 300, 200)]);
+        deps.querier.update_balance(&env.contract.address, coins(100u128, USTC_DENOM));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::ReturnFunds {}).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(matches!(res.messages[0].msg, cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { .. })));
+        
+        // Refunds zero each balance
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::CancelCampaign {}).unwrap();
+        deps.querier.update_balance(&env.contract.address, coins(400u128, USTC_DENOM));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::ProcessRefunds { limit: None }).unwrap();
+        assert_eq!(res.messages.len(), 4);
+        assert_eq!(res.messages[0], expected(USER2, 200, 0));
+        assert_eq!(res.messages[2], expected(USER1, 200, 0));
+    }
+    
+    #[test]
+    fn test_refund_batches_shrink_with_hooks() {
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        let env = mock_env();
+        for i in 0..12 {
+            let info = mock_info(&format!("terra1user{}", i), &coins(100u128, USTC_DENOM));
+            execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        }
+        for i in 0..crate::state::MAX_HOOKS {
+            let msg = ExecuteMsg::AddHook { addr: format!("terra1hook{}", i) };
+            execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        }
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::CancelCampaign {}).unwrap();
+        
+        // Ten hooks at 500k gas each leave room for five refunds in the 25M hook gas budget
+        let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::ProcessRefunds { limit: Some(100) }).unwrap();
+        assert_eq!(res.attributes[1].value, "5");
+        assert_eq!(res.messages.len(), 5 * 11);
+        let hook_gas: u64 = res.messages.iter().filter_map(|msg| msg.gas_limit).sum();
+        assert_eq!(hook_gas, 25_000_000);
+        
+        // Without hooks the requested limit applies again
+        for i in 0..crate::state::MAX_HOOKS {
+            let msg = ExecuteMsg::RemoveHook { addr: format!("terra1hook{}", i) };
+            execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+        }
+        let res = execute(deps.as_mut(), env, mock_info(USER1, &[]), ExecuteMsg::ProcessRefunds { limit: Some(100) }).unwrap();
+        assert_eq!(res.attributes[1].value, "7");
+    }
+    
+    #[test]
+    fn test_failed_hook_reply() {
+        use crate::contract::reply;
+        use crate::events::ContractEvent;
+        use cosmwasm_std::{Reply, SubMsgResponse, SubMsgResult};
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        let env = mock_env();
+        for hook in ["terra1convert", "terra1points"] {
+            execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::AddHook { addr: hook.to_string() }).unwrap();
+        }
+        
+        // The failure is reported against the hook the reply id was assigned to
+        let failed = |id: u64| Reply { id, result: SubMsgResult::Err("out of gas".to_string()) };
+        let res = reply(deps.as_mut(), env.clone(), failed(1)).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.events.len(), 1);
+        assert_eq!(
+            ContractEvent::from_event(&res.events[0]).unwrap(),
+            Some(ContractEvent::HookFailed { hook: Addr::unchecked("terra1points"), error: "out of gas".to_string() })
+        );
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetActivity { start_after_seq: None, limit: None }).unwrap();
+        let ledger: crate::msg::GetActivityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(ledger.activities.last().unwrap().kind, ActivityKind::HookFailed { hook: Addr::unchecked("terra1points") });
+        
+        // Removing a hook while its call is in flight neither shifts the ids nor reverts the reply
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::RemoveHook { addr: "terra1convert".to_string() }).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::AddHook { addr: "terra1convert".to_string() }).unwrap();
+        let res = reply(deps.as_mut(), env.clone(), failed(0)).unwrap();
+        assert_eq!(
+            ContractEvent::from_event(&res.events[0]).unwrap(),
+            Some(ContractEvent::HookFailed { hook: Addr::unchecked("terra1convert"), error: "out of gas".to_string() })
+        );
+        let res = reply(deps.as_mut(), env.clone(), failed(1)).unwrap();
+        assert_eq!(
+            ContractEvent::from_event(&res.events[0]).unwrap(),
+            Some(ContractEvent::HookFailed { hook: Addr::unchecked("terra1points"), error: "out of gas".to_string() })
+        );
+        let res = reply(deps.as_mut(), env.clone(), failed(7)).unwrap();
+        assert!(res.events.is_empty());
+        assert_eq!(res.attributes[1].value, "7");
+        let succeeded = Reply { id: 0, result: SubMsgResult::Ok(SubMsgResponse { events: vec![], data: None }) };
+        let err = reply(deps.as_mut(), env, succeeded).unwrap_err();
+        assert!(matches!(err, ContractError::UnexpectedReply { id: 0 }));
+    }
+    
+    #[test]
+    fn test_migrate_backfills_deposit_index() {
+        use crate::contract::migrate;
+        use crate::msg::MigrateMsg;
+        use crate::state::{TOTAL_DEPOSITS, USER_COUNT, USER_INDEX, USER_INDEX_REVERSE};
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        let env = mock_env();
+        
+        // Balances written by the baseline contract, as a plain map without the deposit index
+        let legacy_users: cw_storage_plus::Map<&Addr, Uint128> = cw_storage_plus::Map::new("users");
+        let balances = [("terra1alice", 300u128), ("terra1bob", 500u128), ("terra1carol", 100u128)];
+        for (position, (user, balance)) in balances.iter().enumerate() {
+            let user = Addr::unchecked(*user);
+            legacy_users.save(deps.as_mut().storage, &user, &Uint128::from(*balance)).unwrap();
+            USER_INDEX.save(deps.as_mut().storage, position as u32, &user).unwrap();
+            USER_INDEX_REVERSE.save(deps.as_mut().storage, &user, &(position as u32)).unwrap();
+        }
+        USER_COUNT.save(deps.as_mut().storage, &3).unwrap();
+        TOTAL_DEPOSITS.save(deps.as_mut().storage, &Uint128::from(900u128)).unwrap();
+        
+        let top_depositors = |deps: &cosmwasm_std::OwnedDeps<_, _, _>| {
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::GetTopDepositors { start_after: None, limit: None }).unwrap();
+            let page: crate::msg::GetTopDepositorsResponse = cosmwasm_std::from_json(&res).unwrap();
+            page.depositors
+        };
+        assert!(top_depositors(&deps).is_empty());
+        
+        // Migrating starts a repair, balances stay frozen until it completes
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        assert!(res.attributes.iter().any(|attr| attr.key == "repair_required" && attr.value == "true"));
+        let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(100u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap_err();
+        assert!(matches!(err, ContractError::RepairInProgress {}));
+        
+        let mut start = None;
+        let mut corrections = Vec::new();
+        loop {
+            let msg = ExecuteMsg::RepairIndex { start, limit: Some(2) };
+            let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+            let repair: crate::msg::RepairIndexResponse = cosmwasm_std::from_json(res.data.unwrap()).unwrap();
+            corrections.extend(repair.corrections);
+            if repair.complete {
+                break;
+            }
+            start = repair.next_start;
+        }
+        assert_eq!(corrections.iter().filter(|c| c.starts_with("Added missing deposit index entry")).count(), 3);
+        
+        assert_eq!(
+            top_depositors(&deps),
+            vec![
+                (Addr::unchecked("terra1bob"), Uint128::from(500u128)),
+                (Addr::unchecked("terra1alice"), Uint128::from(300u128)),
+                (Addr::unchecked("terra1carol"), Uint128::from(100u128)),
+            ]
+        );
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetUserRank { user: "terra1carol".to_string() }).unwrap();
+        let rank: crate::msg::GetUserRankResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(rank.rank, Some(3));
+        
+        // A consistent store does not need another repair
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        assert!(res.attributes.iter().any(|attr| attr.key == "repair_required" && attr.value == "false"));
+        execute(deps.as_mut(), env, mock_info(USER1, &coins(100u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+    }
+    
+    #[test]
+    fn test_exits_stay_open_during_migration_repair() {
+        use crate::contract::migrate;
+        use crate::msg::MigrateMsg;
+        use crate::state::{TOTAL_DEPOSITS, USER_COUNT, USER_INDEX, USER_INDEX_REVERSE};
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        let env = mock_env();
+        
+        // Baseline state: plain balances, no deposit index and no distribution brackets
+        let legacy_users: cw_storage_plus::Map<&Addr, Uint128> = cw_storage_plus::Map::new("users");
+        let balances = [("terra1alice", 300u128), ("terra1bob", 500u128), ("terra1carol", 100u128), ("terra1dave", 200u128)];
+        for (position, (user, balance)) in balances.iter().enumerate() {
+            let user = Addr::unchecked(*user);
+            legacy_users.save(deps.as_mut().storage, &user, &Uint128::from(*balance)).unwrap();
+            USER_INDEX.save(deps.as_mut().storage, position as u32, &user).unwrap();
+            USER_INDEX_REVERSE.save(deps.as_mut().storage, &user, &(position as u32)).unwrap();
+        }
+        USER_COUNT.save(deps.as_mut().storage, &4).unwrap();
+        TOTAL_DEPOSITS.save(deps.as_mut().storage, &Uint128::from(1100u128)).unwrap();
+        
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        assert!(res.attributes.iter().any(|attr| attr.key == "repair_required" && attr.value == "true"));
+        
+        // Users exit without anyone running RepairIndex first
+        let withdraw = |deps: &mut cosmwasm_std::OwnedDeps<_, _, _>, user: &str, amount: u128| {
+            let msg = ExecuteMsg::Withdraw { amount: Uint128::from(amount) };
+            let res = execute(deps.as_mut(), mock_env(), mock_info(user, &[]), msg).unwrap();
+            assert_eq!(res.messages.len(), 1);
+        };
+        withdraw(&mut deps, "terra1bob", 500);
+        
+        let repair = |deps: &mut cosmwasm_std::OwnedDeps<_, _, _>, start, limit| {
+            let msg = ExecuteMsg::RepairIndex { start, limit: Some(limit) };
+            let res = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), msg).unwrap();
+            let repair: crate::msg::RepairIndexResponse = cosmwasm_std::from_json(res.data.unwrap()).unwrap();
+            repair
+        };
+        let mut corrections = Vec::new();
+        
+        // Carol was already compacted by the Index phase when she leaves
+        let batch = repair(&mut deps, None, 3);
+        corrections.extend(batch.corrections);
+        withdraw(&mut deps, "terra1carol", 100);
+        
+        // Alice was already counted by the Users phase when she lowers her balance
+        let batch = repair(&mut deps, batch.next_start, 2);
+        corrections.extend(batch.corrections);
+        withdraw(&mut deps, "terra1alice", 100);
+        
+        // Refunds take users by address while the index is rebuilt
+        execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::CancelCampaign {}).unwrap();
+        let res = execute(deps.as_mut(), env.clone(), mock_info("terra1anyone", &[]), ExecuteMsg::ProcessRefunds { limit: Some(1) }).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address: "terra1dave".to_string(), amount: coins(200u128, USTC_DENOM) })
+        );
+        
+        let batch = repair(&mut deps, batch.next_start, 200);
+        assert!(batch.complete);
+        corrections.extend(batch.corrections);
+        assert!(!corrections.iter().any(|c| c.starts_with("User count corrected") || c.starts_with("Total deposits corrected")));
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::ValidateIndex { start: None, limit: None }).unwrap();
+        let validation: crate::msg::ValidateIndexResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert!(validation.is_consistent);
+        assert_eq!(validation.user_count_stored, 1);
+        assert_eq!(validation.total_deposits_stored, Uint128::from(200u128));
+        
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetDistribution {}).unwrap();
+        let distribution: crate::msg::GetDistributionResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(distribution.total_users, 1);
+        assert_eq!(distribution.total_volume, Uint128::from(200u128));
+        
+        let res = query(deps.as_ref(), env, QueryMsg::GetTopDepositors { start_after: None, limit: None }).unwrap();
+        let page: crate::msg::GetTopDepositorsResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(page.depositors, vec![(Addr::unchecked("terra1alice"), Uint128::from(200u128))]);
+    }
+    
+    #[test]
+    fn test_user_rank_is_bounded() {
+        use crate::state::USERS;
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        
+        // Only the deposit index is walked, so writing balances directly is enough
+        for i in 0..1000u128 {
+            let user = Addr::unchecked(format!("terra1whale{}", i));
+            USERS.save(deps.as_mut().storage, &user, &Uint128::from(1000 + i)).unwrap();
+        }
+        USERS.save(deps.as_mut().storage, &Addr::unchecked("terra1minnow"), &Uint128::from(1u128)).unwrap();
+        
+        let rank_of = |deps: &cosmwasm_std::OwnedDeps<_, _, _>, user: &str| {
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::GetUserRank { user: user.to_string() }).unwrap();
+            let rank: crate::msg::GetUserRankResponse = cosmwasm_std::from_json(&res).unwrap();
+            rank
+        };
+        let rank = rank_of(&deps, "terra1whale0");
+        assert_eq!(rank.rank, Some(1000));
+        assert!(!rank.beyond_max_rank);
+        let rank = rank_of(&deps, "terra1minnow");
+        assert_eq!(rank.rank, None);
+        assert!(rank.beyond_max_rank);
+    }
+    
+    #[test]
+    fn test_repair_rebuckets_distribution() {
+        use crate::contract::migrate;
+        use crate::msg::MigrateMsg;
+        use crate::state::{DISTRIBUTION, DISTRIBUTION_THRESHOLDS};
+        
+        let mut deps = mock_dependencies();
+        setup_contract(&mut deps);
+        fund_contract(&mut deps);
+        let env = mock_env();
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &coins(2_000_000_000u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info(USER2, &coins(500u128, USTC_DENOM)), ExecuteMsg::Deposit {}).unwrap();
+        
+        // A deployment from before distribution tracking: no brackets, nobody bucketed
+        DISTRIBUTION.clear(deps.as_mut().storage);
+        DISTRIBUTION_THRESHOLDS.remove(deps.as_mut().storage);
+        
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        assert!(res.attributes.iter().any(|attr| attr.key == "repair_required" && attr.value == "true"));
+        let res = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::RepairIndex { start: None, limit: None }).unwrap();
+        let repair: crate::msg::RepairIndexResponse = cosmwasm_std::from_json(res.data.unwrap()).unwrap();
+        assert!(repair.complete);
+        assert_eq!(
+            repair.corrections,
+            vec![
+                "Distribution bracket 0 corrected from 0 users (0) to 1 users (500)".to_string(),
+                "Distribution bracket 1 corrected from 0 users (0) to 1 users (2000000000)".to_string(),
+            ]
+        );
+        
+        let distribution = |deps: &cosmwasm_std::OwnedDeps<_, _, _>| {
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::GetDistribution {}).unwrap();
+            let distribution: crate::msg::GetDistributionResponse = cosmwasm_std::from_json(&res).unwrap();
+            distribution
+        };
+        let rebuilt = distribution(&deps);
+        assert_eq!(rebuilt.total_users, 2);
+        assert_eq!(rebuilt.brackets[1].count, 1);
+        
+        // Ledger consumers see the rewrite
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetActivity { start_after_seq: None, limit: None }).unwrap();
+        let ledger: crate::msg::GetActivityResponse = cosmwasm_std::from_json(&res).unwrap();
+        assert_eq!(
+            ledger.activities.last().unwrap().kind,
+            ActivityKind::IndexRepaired { complete: true, corrections: 2, total: Uint128::from(2_000_000_500u128) }
+        );
+        
+        // Balances leave their brackets exactly, and a second repair finds nothing to fix
+        execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::Withdraw { amount: Uint128::from(2_000_000_000u128) }).unwrap();
+        assert_eq!(distribution(&deps).brackets[1].count, 0);
+        let res = execute(deps.as_mut(), env, mock_info(OWNER, &[]), ExecuteMsg::RepairIndex { start: None, limit: None }).unwrap();
+        let repair: crate::msg::RepairIndexResponse = cosmwasm_std::from_json(res.data.unwrap()).unwrap();
+        assert!(repair.corrections.is_empty());
+    }
 }
 
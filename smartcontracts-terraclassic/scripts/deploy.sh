@@ -48,7 +48,7 @@ echo ""
 echo "⚠️  IMPORTANT: This contract will be deployed as NON-UPGRADEABLE (immutable)."
 echo "The contract cannot be upgraded after deployment. Ensure thorough testing before deployment."
 echo ""
-echo "terrad tx wasm instantiate <code-id> '{\"owner\":\"$OWNER\"}' --from <your-key> --no-admin --chain-id $CHAIN_ID --gas auto --gas-adjustment 1.3 --fees 500000000uluna --node $RPC_URL --broadcast-mode sync -y --label \"ustc-ustr-swap-preregister\""
+echo "terrad tx wasm instantiate <code-id> '{\"owner\":\"$OWNER\",\"denom\":\"$USTC_DENOM\"}' --from <your-key> --no-admin --chain-id $CHAIN_ID --gas auto --gas-adjustment 1.3 --fees 500000000uluna --node $RPC_URL --broadcast-mode sync -y --label \"ustc-ustr-swap-preregister\""
 echo ""
 echo "Note: Using --admin \"\" makes the contract non-upgradeable. If your terrad version supports --no-admin, you can use that instead."
 echo ""
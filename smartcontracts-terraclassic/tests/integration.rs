@@ -1,6 +1,7 @@
-use cosmwasm_std::{Addr, Coin, Uint128};
+use cosmwasm_std::{to_json_binary, Addr, Binary, Coin, Deps, DepsMut, Empty, Env, Event, MessageInfo, Response, StdError, StdResult, Uint128};
 use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
-use ustc_preregister::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, GetUserDepositResponse, GetAllUsersResponse, GetUserCountResponse, GetTotalDepositsResponse, GetConfigResponse};
+use ustc_preregister::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, SudoMsg, GetUserDepositResponse, GetAllUsersResponse, GetUserCountResponse, GetTotalDepositsResponse, GetConfigResponse, GetRolesResponse};
+use ustc_preregister::state::Role;
 
 fn mock_app() -> App {
     AppBuilder::new().build(|router, _, storage| {
@@ -21,11 +22,24 @@ fn mock_app() -> App {
     })
 }
 
-fn contract_ustc_preregister() -> Box<dyn Contract<cosmwasm_std::Empty>> {
+fn contract_ustc_preregister() -> Box<dyn Contract<Empty>> {
     let contract = ContractWrapper::new(
         ustc_preregister::contract::execute,
         ustc_preregister::contract::instantiate,
         ustc_preregister::contract::query,
+    )
+    .with_sudo(ustc_preregister::contract::sudo)
+    .with_reply(ustc_preregister::contract::reply);
+    Box::new(contract)
+}
+
+fn contract_failing_hook() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(
+        |_: DepsMut, _: Env, _: MessageInfo, _: Empty| -> StdResult<Response> {
+            Err(StdError::generic_err("hook always fails"))
+        },
+        |_: DepsMut, _: Env, _: MessageInfo, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+        |_: Deps, _: Env, _: Empty| -> StdResult<Binary> { to_json_binary(&Empty {}) },
     );
     Box::new(contract)
 }
@@ -42,7 +56,11 @@ fn test_instantiate() {
     let code_id = app.store_code(contract_ustc_preregister());
 
     let msg = InstantiateMsg {
-        owner: Addr::unchecked(OWNER),
+        owner: OWNER.to_string(),
+        soft_cap: None,
+        deadline: None,
+        denom: None,
+        decimals: None,
     };
 
     let contract_addr = app
@@ -72,7 +90,11 @@ fn test_deposit() {
     let code_id = app.store_code(contract_ustc_preregister());
 
     let msg = InstantiateMsg {
-        owner: Addr::unchecked(OWNER),
+        owner: OWNER.to_string(),
+        soft_cap: None,
+        deadline: None,
+        denom: None,
+        decimals: None,
     };
 
     let contract_addr = app
@@ -102,7 +124,7 @@ fn test_deposit() {
 
     // Query user deposit
     let query_msg = QueryMsg::GetUserDeposit {
-        user: Addr::unchecked(USER1),
+        user: USER1.to_string(),
     };
     let res: GetUserDepositResponse = app
         .wrap()
@@ -133,7 +155,11 @@ fn test_withdraw() {
     let code_id = app.store_code(contract_ustc_preregister());
 
     let msg = InstantiateMsg {
-        owner: Addr::unchecked(OWNER),
+        owner: OWNER.to_string(),
+        soft_cap: None,
+        deadline: None,
+        denom: None,
+        decimals: None,
     };
 
     let contract_addr = app
@@ -176,7 +202,7 @@ fn test_withdraw() {
 
     // Query user deposit - should be 500
     let query_msg = QueryMsg::GetUserDeposit {
-        user: Addr::unchecked(USER1),
+        user: USER1.to_string(),
     };
     let res: GetUserDepositResponse = app
         .wrap()
@@ -191,7 +217,11 @@ fn test_multiple_users() {
     let code_id = app.store_code(contract_ustc_preregister());
 
     let msg = InstantiateMsg {
-        owner: Addr::unchecked(OWNER),
+        owner: OWNER.to_string(),
+        soft_cap: None,
+        deadline: None,
+        denom: None,
+        decimals: None,
     };
 
     let contract_addr = app
@@ -254,7 +284,11 @@ fn test_owner_withdraw() {
     let code_id = app.store_code(contract_ustc_preregister());
 
     let msg = InstantiateMsg {
-        owner: Addr::unchecked(OWNER),
+        owner: OWNER.to_string(),
+        soft_cap: None,
+        deadline: None,
+        denom: None,
+        decimals: None,
     };
 
     let contract_addr = app
@@ -288,7 +322,8 @@ fn test_owner_withdraw() {
     // Set withdrawal destination with unlock timestamp (7 days + 1 second in the future)
     let unlock_timestamp = current_time + 7 * 24 * 60 * 60 + 1; // 7 days + 1 second
     let msg = ExecuteMsg::SetWithdrawalDestination {
-        destination: Addr::unchecked(WITHDRAWAL_DEST),
+        destination: WITHDRAWAL_DEST.to_string(),
+        shares: vec![],
         unlock_timestamp,
     };
     app.execute_contract(
@@ -305,7 +340,7 @@ fn test_owner_withdraw() {
     });
 
     // Owner withdraws
-    let msg = ExecuteMsg::OwnerWithdraw {};
+    let msg = ExecuteMsg::OwnerWithdraw { amount: None };
     app.execute_contract(
         Addr::unchecked(OWNER),
         contract_addr.clone(),
@@ -328,7 +363,11 @@ fn test_unauthorized_owner_withdraw() {
     let code_id = app.store_code(contract_ustc_preregister());
 
     let msg = InstantiateMsg {
-        owner: Addr::unchecked(OWNER),
+        owner: OWNER.to_string(),
+        soft_cap: None,
+        deadline: None,
+        denom: None,
+        decimals: None,
     };
 
     let contract_addr = app
@@ -343,7 +382,7 @@ fn test_unauthorized_owner_withdraw() {
         .unwrap();
 
     // Non-owner tries to withdraw
-    let msg = ExecuteMsg::OwnerWithdraw {};
+    let msg = ExecuteMsg::OwnerWithdraw { amount: None };
     let res = app.execute_contract(
         Addr::unchecked(USER1),
         contract_addr.clone(),
@@ -353,3 +392,247 @@ fn test_unauthorized_owner_withdraw() {
     assert!(res.is_err());
 }
 
+#[test]
+fn test_sudo_replace_owner_revokes_roles_granted_away() {
+    let mut app = mock_app();
+    let code_id = app.store_code(contract_ustc_preregister());
+
+    let msg = InstantiateMsg {
+        owner: OWNER.to_string(),
+        soft_cap: None,
+        deadline: None,
+        denom: None,
+        decimals: None,
+    };
+
+    let contract_addr = app
+        .instantiate_contract(
+            code_id,
+            Addr::unchecked(OWNER),
+            &msg,
+            &[],
+            "USTC Preregister",
+            None,
+        )
+        .unwrap();
+
+    // The owner hands admin and treasury to separate keys and keeps nothing but ownership
+    for (role, address) in [(Role::Admin, "admin_key"), (Role::Treasurer, "treasurer_key")] {
+        let msg = ExecuteMsg::GrantRole { role, address: address.to_string() };
+        app.execute_contract(Addr::unchecked(OWNER), contract_addr.clone(), &msg, &[]).unwrap();
+    }
+    let msg = ExecuteMsg::RevokeRole { role: Role::Admin, address: OWNER.to_string() };
+    app.execute_contract(Addr::unchecked(OWNER), contract_addr.clone(), &msg, &[]).unwrap();
+
+    // The admin key is lost, governance replaces the owner
+    let msg = SudoMsg::ReplaceOwner { owner: "new_owner".to_string() };
+    app.wasm_sudo(contract_addr.clone(), &msg).unwrap();
+
+    let roles: GetRolesResponse = app
+        .wrap()
+        .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetRoles {})
+        .unwrap();
+    for role in roles.roles {
+        assert_eq!(role.holders, vec![Addr::unchecked("new_owner")]);
+    }
+
+    // Only the new owner can administer roles
+    let msg = ExecuteMsg::GrantRole { role: Role::Guardian, address: "guardian_key".to_string() };
+    assert!(app.execute_contract(Addr::unchecked("admin_key"), contract_addr.clone(), &msg, &[]).is_err());
+    app.execute_contract(Addr::unchecked("new_owner"), contract_addr.clone(), &msg, &[]).unwrap();
+}
+
+#[test]
+fn test_sudo_replace_owner_and_destination() {
+    let mut app = mock_app();
+    let code_id = app.store_code(contract_ustc_preregister());
+
+    let msg = InstantiateMsg {
+        owner: OWNER.to_string(),
+        soft_cap: None,
+        deadline: None,
+        denom: None,
+        decimals: None,
+    };
+
+    let contract_addr = app
+        .instantiate_contract(
+            code_id,
+            Addr::unchecked(OWNER),
+            &msg,
+            &[],
+            "USTC Preregister",
+            None,
+        )
+        .unwrap();
+
+    app.execute_contract(
+        Addr::unchecked(USER1),
+        contract_addr.clone(),
+        &ExecuteMsg::Deposit {},
+        &[Coin {
+            denom: USTC_DENOM.to_string(),
+            amount: Uint128::from(1000u128),
+        }],
+    )
+    .unwrap();
+
+    // The owner sets a destination, then loses its key
+    let unlock_timestamp = app.block_info().time.seconds() + 7 * 24 * 60 * 60 + 1;
+    let msg = ExecuteMsg::SetWithdrawalDestination {
+        destination: "lost_dest".to_string(),
+        shares: vec![],
+        unlock_timestamp,
+    };
+    app.execute_contract(Addr::unchecked(OWNER), contract_addr.clone(), &msg, &[]).unwrap();
+
+    // Governance hands the contract to a new owner
+    let msg = SudoMsg::ReplaceOwner { owner: "new_owner".to_string() };
+    app.wasm_sudo(contract_addr.clone(), &msg).unwrap();
+    let config: GetConfigResponse = app
+        .wrap()
+        .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetConfig {})
+        .unwrap();
+    assert_eq!(config.owner, Addr::unchecked("new_owner"));
+
+    // The old owner no longer holds any role
+    let msg = ExecuteMsg::OwnerWithdraw { amount: None };
+    assert!(app.execute_contract(Addr::unchecked(OWNER), contract_addr.clone(), &msg, &[]).is_err());
+
+    // Governance replaces the destination without waiting out the change delay
+    let msg = SudoMsg::SetWithdrawalDestination {
+        destination: WITHDRAWAL_DEST.to_string(),
+        shares: vec![],
+        unlock_timestamp,
+    };
+    app.wasm_sudo(contract_addr.clone(), &msg).unwrap();
+
+    app.update_block(|block| {
+        block.time = block.time.plus_seconds(7 * 24 * 60 * 60 + 1);
+    });
+
+    let msg = ExecuteMsg::OwnerWithdraw { amount: None };
+    app.execute_contract(Addr::unchecked("new_owner"), contract_addr.clone(), &msg, &[]).unwrap();
+    let balance = app
+        .wrap()
+        .query_balance(Addr::unchecked(WITHDRAWAL_DEST), USTC_DENOM)
+        .unwrap();
+    assert_eq!(balance.amount, Uint128::from(1000u128));
+}
+
+#[test]
+fn test_sudo_force_pause_and_cancel() {
+    let mut app = mock_app();
+    let code_id = app.store_code(contract_ustc_preregister());
+
+    let msg = InstantiateMsg {
+        owner: OWNER.to_string(),
+        soft_cap: None,
+        deadline: None,
+        denom: None,
+        decimals: None,
+    };
+
+    let contract_addr = app
+        .instantiate_contract(
+            code_id,
+            Addr::unchecked(OWNER),
+            &msg,
+            &[],
+            "USTC Preregister",
+            None,
+        )
+        .unwrap();
+
+    let deposit = vec![Coin {
+        denom: USTC_DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }];
+    app.execute_contract(Addr::unchecked(USER1), contract_addr.clone(), &ExecuteMsg::Deposit {}, &deposit)
+        .unwrap();
+
+    // A forced pause blocks deposits until governance lifts it
+    app.wasm_sudo(contract_addr.clone(), &SudoMsg::ForcePause { paused: true }).unwrap();
+    let res = app.execute_contract(Addr::unchecked(USER2), contract_addr.clone(), &ExecuteMsg::Deposit {}, &deposit);
+    assert!(res.is_err());
+    app.wasm_sudo(contract_addr.clone(), &SudoMsg::ForcePause { paused: false }).unwrap();
+
+    // A forced cancellation opens refunds and cannot be repeated
+    app.wasm_sudo(contract_addr.clone(), &SudoMsg::ForceCancelCampaign {}).unwrap();
+    assert!(app.wasm_sudo(contract_addr.clone(), &SudoMsg::ForceCancelCampaign {}).is_err());
+    let res = app.execute_contract(Addr::unchecked(USER2), contract_addr.clone(), &ExecuteMsg::Deposit {}, &deposit);
+    assert!(res.is_err());
+
+    let msg = ExecuteMsg::ProcessRefunds { limit: None };
+    app.execute_contract(Addr::unchecked(USER2), contract_addr.clone(), &msg, &[]).unwrap();
+    let balance = app
+        .wrap()
+        .query_balance(Addr::unchecked(USER1), USTC_DENOM)
+        .unwrap();
+    assert_eq!(balance.amount, Uint128::from(1000000u128));
+}
+
+#[test]
+fn test_failing_hook_does_not_block_balance_changes() {
+    let mut app = mock_app();
+    let code_id = app.store_code(contract_ustc_preregister());
+    let hook_code_id = app.store_code(contract_failing_hook());
+
+    let msg = InstantiateMsg {
+        owner: OWNER.to_string(),
+        soft_cap: None,
+        deadline: None,
+        denom: None,
+        decimals: None,
+    };
+
+    let contract_addr = app
+        .instantiate_contract(
+            code_id,
+            Addr::unchecked(OWNER),
+            &msg,
+            &[],
+            "USTC Preregister",
+            None,
+        )
+        .unwrap();
+    let hook_addr = app
+        .instantiate_contract(hook_code_id, Addr::unchecked(OWNER), &Empty {}, &[], "Failing hook", None)
+        .unwrap();
+    let msg = ExecuteMsg::AddHook { addr: hook_addr.to_string() };
+    app.execute_contract(Addr::unchecked(OWNER), contract_addr.clone(), &msg, &[]).unwrap();
+
+    // Deposits and withdrawals go through, the hook failure is reported as an event
+    let deposit = vec![Coin {
+        denom: USTC_DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }];
+    let res = app
+        .execute_contract(Addr::unchecked(USER1), contract_addr.clone(), &ExecuteMsg::Deposit {}, &deposit)
+        .unwrap();
+    assert!(res.has_event(
+        &Event::new("wasm-ustc_preregister.hook_failed").add_attribute("hook", hook_addr.to_string())
+    ));
+
+    let msg = ExecuteMsg::Withdraw { amount: Uint128::from(400u128) };
+    let res = app.execute_contract(Addr::unchecked(USER1), contract_addr.clone(), &msg, &[]).unwrap();
+    assert!(res.has_event(
+        &Event::new("wasm-ustc_preregister.hook_failed").add_attribute("hook", hook_addr.to_string())
+    ));
+
+    let res: GetUserDepositResponse = app
+        .wrap()
+        .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetUserDeposit { user: USER1.to_string() })
+        .unwrap();
+    assert_eq!(res.deposit, Uint128::from(600u128));
+
+    // Refunds go through as well
+    app.wasm_sudo(contract_addr.clone(), &SudoMsg::ForceCancelCampaign {}).unwrap();
+    let msg = ExecuteMsg::ProcessRefunds { limit: None };
+    app.execute_contract(Addr::unchecked(USER2), contract_addr.clone(), &msg, &[]).unwrap();
+    let balance = app
+        .wrap()
+        .query_balance(Addr::unchecked(USER1), USTC_DENOM)
+        .unwrap();
+    assert_eq!(balance.amount, Uint128::from(1000000u128));
+}
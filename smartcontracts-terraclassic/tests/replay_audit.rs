@@ -0,0 +1,746 @@
+//! Offline event-replay auditor
+//!
+//! Replays the contract events of exported transactions through the real
+//! `contract::execute` in a mock environment, then diffs the resulting USERS,
+//! TOTAL_DEPOSITS and USER_COUNT against a state dump taken from the chain.
+//!
+//! Usage:
+//!     replay-audit <events.jsonl> <state.json> [contract_address]
+//!
+//! `events.jsonl` holds one tx response per line, in chain order, as returned by
+//! `terrad query tx <hash> --output json`. If `contract_address` is given, only events
+//! emitted by that contract are replayed.
+//! Transactions from before typed events are replayed from their flat `action`
+//! attributes, which requires `contract_address` to attribute them.
+//!
+//! `state.json` holds the on-chain state to compare against:
+//!     {"users": [["terra1...", "1000"], ...], "total_deposits": "1000", "user_count": 1}
+//! where `users` is the concatenation of all `get_all_users_by_address` pages.
+//!
+//! Exits with status 0 if the replayed state matches the dump, 1 if any divergence
+//! was found and 2 if the input could not be read.
+
+use std::collections::BTreeMap;
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier};
+use cosmwasm_std::{coins, from_json, Addr, BankMsg, Coin, CosmosMsg, Env, MemoryStorage, Order, OwnedDeps, Response, StdResult, Timestamp, Uint128};
+use serde::Deserialize;
+use ustc_preregister::contract::{execute, instantiate, query};
+use ustc_preregister::events::{decode_legacy_tx_events, decode_tx_events, ContractEvent, LegacyEvent};
+use ustc_preregister::msg::{ExecuteMsg, GetDestinationScheduleResponse, InstantiateMsg, QueryMsg, RepairIndexResponse, UncheckedDestinationShare};
+use ustc_preregister::state::{DestinationShare, Role, CONFIG, PENDING_DESTINATION, ROLES, TOTAL_DEPOSITS, USERS, USER_COUNT};
+
+/// Owner used when the export does not start with the instantiate transaction
+const PLACEHOLDER_OWNER: &str = "replay_owner";
+/// Denomination used when the export does not start with the instantiate transaction
+const DEFAULT_DENOM: &str = "uusd";
+
+/// On-chain state to compare the replayed state against
+#[derive(Deserialize, Debug)]
+struct StateDump {
+    users: Vec<(Addr, Uint128)>,
+    total_deposits: Uint128,
+    user_count: u32,
+}
+
+/// Contract instance the exported events are replayed through
+struct Replayer {
+    deps: OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+    env: Env,
+    instantiated: bool,
+    denom: String,
+    /// Expected bank balance of the contract
+    balance: Uint128,
+    /// Events replayed, by event name
+    replayed: BTreeMap<&'static str, u32>,
+    /// Events that do not affect the audited state and were not replayed, by event name
+    skipped: BTreeMap<&'static str, u32>,
+    divergences: Vec<String>,
+}
+
+impl Replayer {
+    fn new() -> Self {
+        Replayer {
+            deps: mock_dependencies(),
+            env: mock_env(),
+            instantiated: false,
+            denom: DEFAULT_DENOM.to_string(),
+            balance: Uint128::zero(),
+            replayed: BTreeMap::new(),
+            skipped: BTreeMap::new(),
+            divergences: Vec::new(),
+        }
+    }
+    
+    fn instantiate(&mut self, owner: &str, denom: Option<String>, soft_cap: Option<Uint128>, deadline: Option<u64>) {
+        let msg = InstantiateMsg {
+            owner: owner.to_string(),
+            soft_cap,
+            deadline,
+            denom,
+            decimals: None,
+        };
+        instantiate(self.deps.as_mut(), self.env.clone(), mock_info(owner, &[]), msg)
+            .expect("instantiating the replay contract cannot fail");
+        self.denom = CONFIG.load(&self.deps.storage).unwrap().ustc_denom;
+        self.instantiated = true;
+    }
+    
+    /// Address allowed to replay an action requiring the role
+    /// 
+    /// Falls back to the owner if nobody holds the role, in which case replay fails
+    /// with the contract's own authorization error.
+    fn role_holder(&self, role: Role) -> Addr {
+        ROLES
+            .prefix(role.as_str())
+            .keys(&self.deps.storage, None, None, Order::Ascending)
+            .next()
+            .transpose()
+            .unwrap()
+            .unwrap_or_else(|| CONFIG.load(&self.deps.storage).unwrap().owner)
+    }
+    
+    /// Current and pending withdrawal destination of the replayed contract
+    fn destination_schedule(&self) -> GetDestinationScheduleResponse {
+        let schedule = query(self.deps.as_ref(), self.env.clone(), QueryMsg::GetDestinationSchedule {}).unwrap();
+        from_json(schedule).unwrap()
+    }
+    
+    /// Moves the replay clock forward to `seconds`, never backwards
+    fn advance_clock(&mut self, seconds: u64) {
+        if self.env.block.time.seconds() < seconds {
+            self.env.block.time = Timestamp::from_seconds(seconds);
+        }
+    }
+    
+    /// Replays every contract event of one tx response line
+    fn replay_line(&mut self, line_no: usize, line: &str, contract: Option<&str>) -> Result<(), String> {
+        let events = decode_tx_events(line, contract).map_err(|e| format!("line {}: {}", line_no, e))?;
+        
+        self.env.block.height += 1;
+        self.env.block.time = self.env.block.time.plus_seconds(5);
+        
+        // History from before typed events only carries the flat action attributes
+        if events.is_empty() {
+            let legacy = decode_legacy_tx_events(line, contract).map_err(|e| format!("line {}: {}", line_no, e))?;
+            for event in legacy {
+                self.replay_legacy_event(line_no, event);
+            }
+        }
+        let mut events = events.into_iter().peekable();
+        while let Some(event) = events.next() {
+            // The legs of a split sweep are emitted by one call and replay as one sweep
+            if let ContractEvent::OwnerWithdraw { .. } = event {
+                let mut legs = vec![event];
+                while let Some(leg) = events.next_if(|event| matches!(event, ContractEvent::OwnerWithdraw { .. })) {
+                    legs.push(leg);
+                }
+                self.replay_sweep(line_no, legs);
+            } else {
+                self.replay_event(line_no, event);
+            }
+        }
+        Ok(())
+    }
+    
+    fn replay_event(&mut self, line_no: usize, event: ContractEvent) {
+        if let ContractEvent::Instantiate { owner, ustc_denom, soft_cap, deadline } = &event {
+            if self.instantiated {
+                self.divergences.push(format!("line {}: contract instantiated twice", line_no));
+            } else {
+                self.instantiate(owner.as_str(), Some(ustc_denom.clone()), *soft_cap, *deadline);
+            }
+            *self.replayed.entry(event.name()).or_default() += 1;
+            return;
+        }
+        if !self.instantiated {
+            self.instantiate(PLACEHOLDER_OWNER, None, None, None);
+        }
+        
+        // The replay clock does not follow the chain, move it past the deadline so the
+        // event's own transaction settles the campaign the same way
+        if let ContractEvent::CampaignSettled { .. } = &event {
+            if let Some(deadline) = CONFIG.load(&self.deps.storage).unwrap().deadline {
+                self.advance_clock(deadline);
+            }
+            *self.replayed.entry(event.name()).or_default() += 1;
+            return;
+        }
+        
+        // A staged destination change is applied by the next call of its transaction once
+        // its delay has elapsed, so only the clock has to catch up with it
+        if let ContractEvent::WithdrawalDestinationSet { destination, shares, unlock_timestamp } = &event {
+            if let Some(pending) = PENDING_DESTINATION.load(&self.deps.storage).unwrap() {
+                if pending.destination == *destination && pending.shares == *shares && pending.unlock_timestamp == *unlock_timestamp {
+                    self.advance_clock(pending.effective_at);
+                    *self.replayed.entry(event.name()).or_default() += 1;
+                    return;
+                }
+            }
+        }
+        
+        // The chain was at `effective_at - change_delay` when the change was staged. The
+        // replay clock may run a few blocks ahead of the chain, so it is set, not advanced
+        if let ContractEvent::WithdrawalDestinationProposed { effective_at, .. } = &event {
+            let change_delay = self.destination_schedule().change_delay;
+            self.env.block.time = Timestamp::from_seconds(effective_at.saturating_sub(change_delay));
+        }
+        
+        // A repair rebuilds the index, totals and distribution from the user balances. The
+        // batches of the chain cannot be told apart, so the whole repair replays at once
+        // with the batch that completed it
+        if let ContractEvent::IndexRepaired { complete, .. } = &event {
+            if *complete {
+                self.replay_repair(line_no);
+            }
+            *self.replayed.entry(event.name()).or_default() += 1;
+            return;
+        }
+        
+        let (sender, funds, msg) = match &event {
+            ContractEvent::Deposit { user, amount, .. } => {
+                (user.clone(), coins(amount.u128(), &self.denom), ExecuteMsg::Deposit {})
+            }
+            ContractEvent::Withdraw { user, amount, .. } => {
+                (user.clone(), vec![], ExecuteMsg::Withdraw { amount: *amount })
+            }
+            ContractEvent::WithdrawalDestinationSet { destination, shares, unlock_timestamp }
+            | ContractEvent::WithdrawalDestinationProposed { destination, shares, unlock_timestamp, .. } => {
+                let msg = ExecuteMsg::SetWithdrawalDestination {
+                    destination: destination.to_string(),
+                    shares: unchecked_shares(shares),
+                    unlock_timestamp: *unlock_timestamp,
+                };
+                (self.role_holder(Role::ConfigManager), vec![], msg)
+            }
+            ContractEvent::PendingDestinationCancelled { .. } => {
+                (self.role_holder(Role::ConfigManager), vec![], ExecuteMsg::CancelPendingDestination {})
+            }
+            ContractEvent::ConfigUpdate { owner: new_owner } => {
+                (self.role_holder(Role::Admin), vec![], ExecuteMsg::UpdateConfig { owner: Some(new_owner.to_string()) })
+            }
+            // Roles and pausing decide which of the later events are accepted
+            ContractEvent::RoleGranted { role, address } => {
+                (self.role_holder(Role::Admin), vec![], ExecuteMsg::GrantRole { role: *role, address: address.to_string() })
+            }
+            ContractEvent::RoleRevoked { role, address } => {
+                (self.role_holder(Role::Admin), vec![], ExecuteMsg::RevokeRole { role: *role, address: address.to_string() })
+            }
+            ContractEvent::PausedSet { paused } => {
+                (self.role_holder(Role::Guardian), vec![], ExecuteMsg::SetPaused { paused: *paused })
+            }
+            // Queue settlement is derived from the returned funds and replays with them
+            ContractEvent::FundsReturned { amount, .. } => {
+                let funds = if amount.is_zero() { vec![] } else { coins(amount.u128(), &self.denom) };
+                (self.role_holder(Role::Treasurer), funds, ExecuteMsg::ReturnFunds {})
+            }
+            ContractEvent::DenomSet { denom, decimals } => {
+                (self.role_holder(Role::ConfigManager), vec![], ExecuteMsg::SetDenom { denom: denom.clone(), decimals: *decimals })
+            }
+            ContractEvent::CampaignCancelled { .. } => {
+                (self.role_holder(Role::Admin), vec![], ExecuteMsg::CancelCampaign {})
+            }
+            // Refunds pop the last indexed user, so each one replays as a batch of one
+            ContractEvent::Refund { user, .. } => {
+                (user.clone(), vec![], ExecuteMsg::ProcessRefunds { limit: Some(1) })
+            }
+            _ => {
+                *self.skipped.entry(event.name()).or_default() += 1;
+                return;
+            }
+        };
+        
+        let res = match self.execute_replayed(line_no, &format!("{:?}", event), sender, funds, msg) {
+            Some(res) => res,
+            None => return,
+        };
+        *self.replayed.entry(event.name()).or_default() += 1;
+        
+        // The replayed event must match the recorded one exactly
+        let replayed = res
+            .events
+            .iter()
+            .filter_map(|e| ContractEvent::from_event(e).ok().flatten())
+            .find(|e| e.name() == event.name());
+        if replayed.as_ref() != Some(&event) {
+            self.divergences.push(format!(
+                "line {}: recorded {:?} but replay emitted {:?}",
+                line_no, event, replayed
+            ));
+        }
+    }
+    
+    /// Replays an event of the contract before typed events, from its flat attributes
+    fn replay_legacy_event(&mut self, line_no: usize, event: LegacyEvent) {
+        if let LegacyEvent::Instantiate { owner, ustc_denom } = &event {
+            if self.instantiated {
+                self.divergences.push(format!("line {}: contract instantiated twice", line_no));
+            } else {
+                self.instantiate(owner.as_str(), Some(ustc_denom.clone()), None, None);
+            }
+            *self.replayed.entry(event.name()).or_default() += 1;
+            return;
+        }
+        if !self.instantiated {
+            self.instantiate(PLACEHOLDER_OWNER, None, None, None);
+        }
+        
+        let (sender, funds, msg) = match &event {
+            LegacyEvent::Deposit { user, amount } => {
+                (user.clone(), coins(amount.u128(), &self.denom), ExecuteMsg::Deposit {})
+            }
+            LegacyEvent::Withdraw { user, amount } => {
+                (user.clone(), vec![], ExecuteMsg::Withdraw { amount: *amount })
+            }
+            LegacyEvent::OwnerWithdraw { amount, .. } => self.prepare_sweep(line_no, *amount),
+            LegacyEvent::WithdrawalDestinationSet { destination, unlock_timestamp } => {
+                let msg = ExecuteMsg::SetWithdrawalDestination {
+                    destination: destination.to_string(),
+                    shares: vec![],
+                    unlock_timestamp: *unlock_timestamp,
+                };
+                (self.role_holder(Role::ConfigManager), vec![], msg)
+            }
+            LegacyEvent::ConfigUpdate { owner: new_owner } => {
+                (self.role_holder(Role::Admin), vec![], ExecuteMsg::UpdateConfig { owner: Some(new_owner.to_string()) })
+            }
+            LegacyEvent::Instantiate { .. } => {
+                *self.skipped.entry(event.name()).or_default() += 1;
+                return;
+            }
+        };
+        
+        let res = match self.execute_replayed(line_no, &format!("{:?}", event), sender, funds, msg) {
+            Some(res) => res,
+            None => return,
+        };
+        *self.replayed.entry(event.name()).or_default() += 1;
+        
+        // The contract still emits the legacy attributes, they must match the recorded ones
+        let replayed = LegacyEvent::from_attributes(&res.attributes).ok().flatten();
+        if replayed.as_ref() != Some(&event) {
+            self.divergences.push(format!(
+                "line {}: recorded {:?} but replay emitted {:?}",
+                line_no, event, replayed
+            ));
+        }
+        
+        // The contract before typed events changed the destination immediately, the
+        // current one stages a change of an existing destination
+        if let LegacyEvent::WithdrawalDestinationSet { .. } = &event {
+            if let Some(pending) = self.destination_schedule().pending {
+                self.advance_clock(pending.effective_at);
+            }
+        }
+    }
+    
+    /// Replays the legs of one sweep as a single sweep of their sum
+    fn replay_sweep(&mut self, line_no: usize, legs: Vec<ContractEvent>) {
+        if !self.instantiated {
+            self.instantiate(PLACEHOLDER_OWNER, None, None, None);
+        }
+        
+        let amount = legs
+            .iter()
+            .filter_map(|leg| match leg {
+                ContractEvent::OwnerWithdraw { amount, .. } => Some(*amount),
+                _ => None,
+            })
+            .sum();
+        let (sender, funds, msg) = self.prepare_sweep(line_no, amount);
+        let res = match self.execute_replayed(line_no, &format!("{:?}", legs), sender, funds, msg) {
+            Some(res) => res,
+            None => return,
+        };
+        *self.replayed.entry(legs[0].name()).or_default() += legs.len() as u32;
+        
+        // Every leg must go to the recorded destination with the recorded amount
+        let replayed: Vec<ContractEvent> = res
+            .events
+            .iter()
+            .filter_map(|e| ContractEvent::from_event(e).ok().flatten())
+            .filter(|e| e.name() == legs[0].name())
+            .collect();
+        if replayed != legs {
+            self.divergences.push(format!(
+                "line {}: recorded {:?} but replay emitted {:?}",
+                line_no, legs, replayed
+            ));
+        }
+    }
+    
+    /// Runs a complete index repair on the replayed contract
+    fn replay_repair(&mut self, line_no: usize) {
+        let mut start = None;
+        loop {
+            let sender = self.role_holder(Role::Admin);
+            let msg = ExecuteMsg::RepairIndex { start, limit: None };
+            let res = match self.execute_replayed(line_no, "IndexRepaired", sender, vec![], msg) {
+                Some(res) => res,
+                None => return,
+            };
+            let batch: RepairIndexResponse = from_json(res.data.unwrap()).unwrap();
+            if batch.complete {
+                return;
+            }
+            start = batch.next_start;
+        }
+    }
+    
+    /// Returns the message replaying a sweep of `amount`
+    fn prepare_sweep(&mut self, line_no: usize, amount: Uint128) -> (Addr, Vec<Coin>, ExecuteMsg) {
+        if amount > self.balance {
+            self.divergences.push(format!(
+                "line {}: sweep of {} but replayed contract balance is {}",
+                line_no, amount, self.balance
+            ));
+            self.balance = amount;
+        }
+        
+        // The sweep happened on chain, so the destination had unlocked by then
+        let unlock_timestamp = self.destination_schedule().current_unlock_timestamp;
+        self.advance_clock(unlock_timestamp);
+        (self.role_holder(Role::Treasurer), vec![], ExecuteMsg::OwnerWithdraw { amount: Some(amount) })
+    }
+    
+    /// Executes a replayed message and tracks the contract balance
+    /// 
+    /// # Returns
+    /// * The response, or None if the contract rejected the message (recorded as a divergence)
+    fn execute_replayed(&mut self, line_no: usize, recorded: &str, sender: Addr, funds: Vec<Coin>, msg: ExecuteMsg) -> Option<Response> {
+        // The contract sees the funds before execution, as on chain
+        self.balance += funds.iter().map(|coin| coin.amount).sum::<Uint128>();
+        self.deps.querier.update_balance(self.env.contract.address.clone(), coins(self.balance.u128(), &self.denom));
+        
+        let info = mock_info(sender.as_str(), &funds);
+        let res = match execute(self.deps.as_mut(), self.env.clone(), info, msg) {
+            Ok(res) => res,
+            Err(err) => {
+                self.divergences.push(format!("line {}: replaying {} failed: {}", line_no, recorded, err));
+                return None;
+            }
+        };
+        self.denom = CONFIG.load(&self.deps.storage).unwrap().ustc_denom;
+        
+        // Payouts leave the contract, whether paid directly or settled from the queue
+        let paid = res
+            .messages
+            .iter()
+            .filter_map(|sub| match &sub.msg {
+                CosmosMsg::Bank(BankMsg::Send { amount, .. }) => Some(amount.iter().map(|coin| coin.amount).sum::<Uint128>()),
+                _ => None,
+            })
+            .sum::<Uint128>();
+        self.balance = self.balance.saturating_sub(paid);
+        Some(res)
+    }
+    
+    /// Compares the replayed state with the dump, returning every divergence
+    fn diff(&self, dump: &StateDump) -> StdResult<Vec<String>> {
+        let mut divergences = Vec::new();
+        
+        let replayed: BTreeMap<Addr, Uint128> = USERS
+            .range(&self.deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        let mut dumped: BTreeMap<Addr, Uint128> = BTreeMap::new();
+        for (user, balance) in &dump.users {
+            if dumped.insert(user.clone(), *balance).is_some() {
+                divergences.push(format!("user {}: listed more than once in the dump", user));
+            }
+        }
+        
+        let users: std::collections::BTreeSet<&Addr> = replayed.keys().chain(dumped.keys()).collect();
+        for user in users {
+            let replayed_balance = replayed.get(user).copied().unwrap_or_default();
+            let dumped_balance = dumped.get(user).copied().unwrap_or_default();
+            if replayed_balance != dumped_balance {
+                divergences.push(format!(
+                    "user {}: replayed balance {}, on-chain balance {}",
+                    user, replayed_balance, dumped_balance
+                ));
+            }
+        }
+        
+        let (total_deposits, user_count) = if self.instantiated {
+            (TOTAL_DEPOSITS.load(&self.deps.storage)?, USER_COUNT.load(&self.deps.storage)?)
+        } else {
+            (Uint128::zero(), 0)
+        };
+        if total_deposits != dump.total_deposits {
+            divergences.push(format!(
+                "total_deposits: replayed {}, on-chain {}",
+                total_deposits, dump.total_deposits
+            ));
+        }
+        if user_count != dump.user_count {
+            divergences.push(format!(
+                "user_count: replayed {}, on-chain {}",
+                user_count, dump.user_count
+            ));
+        }
+        
+        Ok(divergences)
+    }
+}
+
+/// Destination shares as passed to SetWithdrawalDestination
+fn unchecked_shares(shares: &[DestinationShare]) -> Vec<UncheckedDestinationShare> {
+    shares
+        .iter()
+        .map(|share| UncheckedDestinationShare { address: share.address.to_string(), bps: share.bps })
+        .collect()
+}
+
+/// Replays all lines and diffs the result against the dump
+///
+/// # Returns
+/// * The replayer and every divergence found, replay divergences first
+fn audit(events: &str, dump: &StateDump, contract: Option<&str>) -> Result<(Replayer, Vec<String>), String> {
+    let mut replayer = Replayer::new();
+    for (idx, line) in events.lines().enumerate() {
+        if !line.trim().is_empty() {
+            replayer.replay_line(idx + 1, line, contract)?;
+        }
+    }
+    
+    let mut divergences = replayer.divergences.clone();
+    divergences.extend(replayer.diff(dump).map_err(|e| e.to_string())?);
+    Ok((replayer, divergences))
+}
+
+fn run(args: &[String]) -> Result<bool, String> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err("usage: replay-audit <events.jsonl> <state.json> [contract_address]".to_string());
+    }
+    let events = std::fs::read_to_string(&args[0]).map_err(|e| format!("{}: {}", args[0], e))?;
+    let dump = std::fs::read_to_string(&args[1]).map_err(|e| format!("{}: {}", args[1], e))?;
+    let dump: StateDump = from_json(dump.as_bytes()).map_err(|e| format!("{}: {}", args[1], e))?;
+    
+    let (replayer, divergences) = audit(&events, &dump, args.get(2).map(String::as_str))?;
+    
+    for (name, count) in &replayer.replayed {
+        println!("replayed {} {}", count, name);
+    }
+    for (name, count) in &replayer.skipped {
+        println!("skipped {} {} (not part of the audited state)", count, name);
+    }
+    for divergence in &divergences {
+        println!("DIVERGENCE {}", divergence);
+    }
+    if divergences.is_empty() {
+        println!("OK: replayed state matches the dump ({} users)", dump.users.len());
+    } else {
+        println!("FAILED: {} divergence(s)", divergences.len());
+    }
+    
+    Ok(divergences.is_empty())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{Attribute, Event};
+    
+    const CONTRACT: &str = "terra1contract";
+    
+    /// Formats an event emitted by the contract the way `terrad query tx` reports it
+    fn event_json(ty: &str, attributes: &[Attribute]) -> String {
+        let attributes: Vec<String> = attributes
+            .iter()
+            .map(|attr| format!(r#"{{"key":"{}","value":"{}"}}"#, attr.key, attr.value))
+            .collect();
+        format!(
+            r#"{{"type":"{}","attributes":[{{"key":"_contract_address","value":"{}"}},{}]}}"#,
+            ty, CONTRACT, attributes.join(",")
+        )
+    }
+    
+    /// Wraps the flat attributes and custom events of a response as a tx response line
+    fn tx_line(res: &Response) -> String {
+        let mut events = vec![event_json("wasm", &res.attributes)];
+        events.extend(res.events.iter().map(|event: &Event| event_json(&format!("wasm-{}", event.ty), &event.attributes)));
+        format!(r#"{{"logs":[],"events":[{}]}}"#, events.join(","))
+    }
+    
+    /// Wraps only the flat attributes of a response, as emitted before typed events
+    fn legacy_tx_line(res: &Response) -> String {
+        format!(r#"{{"logs":[],"events":[{}]}}"#, event_json("wasm", &res.attributes))
+    }
+    
+    /// Runs a short campaign on a "chain" contract and exports its events and final state
+    fn export_campaign() -> (String, StateDump) {
+        export_campaign_with_legacy_history(0)
+    }
+    
+    /// Same as `export_campaign`, with the first `legacy_txs` transactions exported
+    /// the way the contract emitted them before typed events
+    fn export_campaign_with_legacy_history(legacy_txs: usize) -> (String, StateDump) {
+        let mut chain = mock_dependencies();
+        let mut env = mock_env();
+        // The chain is well past the time the replay clock starts from
+        env.block.time = env.block.time.plus_seconds(24 * 60 * 60);
+        let mut txs: Vec<Response> = Vec::new();
+        
+        let msg = InstantiateMsg { owner: "owner".to_string(), soft_cap: None, deadline: None, denom: None, decimals: None };
+        txs.push(instantiate(chain.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap());
+        
+        for (user, amount) in [("user1", 1000u128), ("user2", 2000), ("user3", 3000), ("user1", 500)] {
+            let res = execute(chain.as_mut(), env.clone(), mock_info(user, &coins(amount, "uusd")), ExecuteMsg::Deposit {}).unwrap();
+            txs.push(res);
+        }
+        chain.querier.update_balance(env.contract.address.clone(), coins(6500, "uusd"));
+        let msg = ExecuteMsg::Withdraw { amount: Uint128::from(2000u128) };
+        txs.push(execute(chain.as_mut(), env.clone(), mock_info("user2", &[]), msg).unwrap());
+        
+        let unlock_timestamp = env.block.time.seconds() + 7 * 24 * 60 * 60;
+        let msg = ExecuteMsg::SetWithdrawalDestination { destination: "treasury".to_string(), shares: vec![], unlock_timestamp };
+        txs.push(execute(chain.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap());
+        
+        // Sweeping is handed over to a dedicated treasurer
+        let msg = ExecuteMsg::GrantRole { role: Role::Treasurer, address: "treasurer".to_string() };
+        txs.push(execute(chain.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap());
+        let msg = ExecuteMsg::RevokeRole { role: Role::Treasurer, address: "owner".to_string() };
+        txs.push(execute(chain.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap());
+        
+        env.block.time = env.block.time.plus_seconds(7 * 24 * 60 * 60);
+        chain.querier.update_balance(env.contract.address.clone(), coins(4500, "uusd"));
+        txs.push(execute(chain.as_mut(), env.clone(), mock_info("treasurer", &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap());
+        
+        let msg = ExecuteMsg::UpdateConfig { owner: Some("owner2".to_string()) };
+        txs.push(execute(chain.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap());
+        
+        let res = execute(chain.as_mut(), env.clone(), mock_info("user4", &coins(700u128, "uusd")), ExecuteMsg::Deposit {}).unwrap();
+        txs.push(res);
+        
+        // The new owner stages a split destination, which the next sweep applies
+        let msg = ExecuteMsg::SetWithdrawalDestination {
+            destination: "treasury2".to_string(),
+            shares: vec![
+                UncheckedDestinationShare { address: "treasury2".to_string(), bps: 6000 },
+                UncheckedDestinationShare { address: "reserve".to_string(), bps: 4000 },
+            ],
+            unlock_timestamp: env.block.time.seconds() + 7 * 24 * 60 * 60,
+        };
+        txs.push(execute(chain.as_mut(), env.clone(), mock_info("owner2", &[]), msg).unwrap());
+        
+        env.block.time = env.block.time.plus_seconds(8 * 24 * 60 * 60);
+        chain.querier.update_balance(env.contract.address.clone(), coins(700, "uusd"));
+        txs.push(execute(chain.as_mut(), env.clone(), mock_info("treasurer", &[]), ExecuteMsg::OwnerWithdraw { amount: None }).unwrap());
+        
+        let msg = ExecuteMsg::RepairIndex { start: None, limit: None };
+        txs.push(execute(chain.as_mut(), env.clone(), mock_info("owner2", &[]), msg).unwrap());
+        
+        let dump = StateDump {
+            users: USERS
+                .range(&chain.storage, None, None, Order::Ascending)
+                .collect::<StdResult<_>>()
+                .unwrap(),
+            total_deposits: TOTAL_DEPOSITS.load(&chain.storage).unwrap(),
+            user_count: USER_COUNT.load(&chain.storage).unwrap(),
+        };
+        let lines: Vec<String> = txs
+            .iter()
+            .enumerate()
+            .map(|(idx, res)| if idx < legacy_txs { legacy_tx_line(res) } else { tx_line(res) })
+            .collect();
+        (lines.join("\n"), dump)
+    }
+    
+    #[test]
+    fn test_replay_matches_chain_state() {
+        let (events, dump) = export_campaign();
+        
+        let (replayer, divergences) = audit(&events, &dump, Some(CONTRACT)).unwrap();
+        assert!(divergences.is_empty(), "{:?}", divergences);
+        assert_eq!(replayer.replayed.get("deposit"), Some(&5));
+        assert_eq!(replayer.replayed.get("withdraw"), Some(&1));
+        assert_eq!(replayer.replayed.get("owner_withdraw"), Some(&3));
+        assert_eq!(replayer.replayed.get("role_granted"), Some(&1));
+        assert_eq!(replayer.replayed.get("withdrawal_destination_set"), Some(&2));
+        assert_eq!(replayer.replayed.get("withdrawal_destination_proposed"), Some(&1));
+        assert_eq!(replayer.replayed.get("index_repaired"), Some(&1));
+        assert!(replayer.skipped.is_empty(), "{:?}", replayer.skipped);
+        assert_eq!(dump.user_count, 3);
+        
+        // Events of other contracts are ignored
+        let (replayer, _) = audit(&events, &dump, Some("terra1other")).unwrap();
+        assert!(replayer.replayed.is_empty());
+    }
+    
+    #[test]
+    fn test_replay_reports_every_divergence() {
+        let (events, mut dump) = export_campaign();
+        
+        dump.users[0].1 += Uint128::from(1u128);
+        dump.users.push((Addr::unchecked("user9"), Uint128::from(5u128)));
+        dump.total_deposits = Uint128::from(1u128);
+        dump.user_count = 7;
+        
+        let (_, divergences) = audit(&events, &dump, None).unwrap();
+        assert_eq!(divergences, vec![
+            "user user1: replayed balance 1500, on-chain balance 1501".to_string(),
+            "user user9: replayed balance 0, on-chain balance 5".to_string(),
+            "total_deposits: replayed 5200, on-chain 1".to_string(),
+            "user_count: replayed 3, on-chain 7".to_string(),
+        ]);
+    }
+    
+    #[test]
+    fn test_replay_reports_missing_history() {
+        let (events, dump) = export_campaign();
+        
+        // Drop the second deposit of user1: balances, the sweep and later events no longer line up
+        let events: Vec<&str> = events.lines().collect();
+        let truncated = [&events[..4], &events[5..]].concat().join("\n");
+        
+        let (_, divergences) = audit(&truncated, &dump, None).unwrap();
+        assert!(divergences.iter().any(|d| d.starts_with("line 5: recorded Withdraw")));
+        assert!(divergences.iter().any(|d| d.contains("sweep of 4500 but replayed contract balance is 4000")));
+        assert!(divergences.contains(&"user user1: replayed balance 1000, on-chain balance 1500".to_string()));
+    }
+    
+    #[test]
+    fn test_replay_mixed_legacy_and_typed_history() {
+        // Instantiation, the deposits, the withdrawal and the destination predate typed events
+        let (events, dump) = export_campaign_with_legacy_history(7);
+        assert!(!events.lines().next().unwrap().contains("wasm-ustc_preregister"));
+        
+        let (replayer, divergences) = audit(&events, &dump, Some(CONTRACT)).unwrap();
+        assert!(divergences.is_empty(), "{:?}", divergences);
+        assert_eq!(replayer.replayed.get("instantiate"), Some(&1));
+        assert_eq!(replayer.replayed.get("deposit"), Some(&5));
+        assert_eq!(replayer.replayed.get("withdraw"), Some(&1));
+        assert_eq!(replayer.replayed.get("owner_withdraw"), Some(&3));
+        assert_eq!(replayer.replayed.get("set_withdrawal_destination"), Some(&1));
+        
+        // A lost legacy deposit shows up like a lost typed one
+        let lines: Vec<&str> = events.lines().collect();
+        let truncated = [&lines[..4], &lines[5..]].concat().join("\n");
+        let (_, divergences) = audit(&truncated, &dump, Some(CONTRACT)).unwrap();
+        assert!(divergences.contains(&"user user1: replayed balance 1000, on-chain balance 1500".to_string()));
+        
+        // Legacy actions cannot be attributed without the contract address
+        let err = audit(&events, &dump, None).err().unwrap();
+        assert!(err.starts_with("line 1:") && err.contains("instantiate"), "{}", err);
+    }
+    
+    #[test]
+    fn test_replay_rejects_malformed_lines() {
+        let err = audit("{\"events\":[]}\nnot json", &StateDump { users: vec![], total_deposits: Uint128::zero(), user_count: 0 }, None)
+            .err()
+            .unwrap();
+        assert!(err.starts_with("line 2:"));
+    }
+}